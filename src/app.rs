@@ -1,7 +1,10 @@
-use crate::game::{GameState, LevelUp};
+use crate::game::{GameState, LevelUp, MovePreview};
 use crate::ui::{StatsData, UiData, UiView};
 use crate::visibility::{CellVisibility, VisibilityAlgorithm};
-use crate::world::{ItemType, ItemUsage, Layer, NpcType, ProjectileType, Tile};
+use crate::world::{
+    DamageKind, EquipSlot, Inventory, ItemType, ItemUsage, KeyColour, Layer, NpcType,
+    ProjectileType, Tile,
+};
 use chargrid::{
     app::App as ChargridApp,
     decorator::{
@@ -22,7 +25,7 @@ use chargrid::{
     text::{RichTextPart, RichTextViewSingleLine, StringViewSingleLine},
 };
 use coord_2d::{Coord, Size};
-use direction::CardinalDirection;
+use direction::Direction;
 use general_storage_file::{format, FileStorage, IfDirectoryMissing, Storage};
 use maplit::hashmap;
 use rgb24::Rgb24;
@@ -31,6 +34,7 @@ use std::time::Duration;
 
 const UI_NUM_ROWS: u32 = 5;
 const BETWEEN_ANIMATION_TICKS: Duration = Duration::from_millis(33);
+const MAX_REST_TURNS: u32 = 100;
 
 const SAVE_DIR: &str = "save";
 const SAVE_FILE: &str = "save";
@@ -445,21 +449,26 @@ impl<'a> View<&'a AppData> for InventorySlotMenuView {
     ) {
         let player_inventory_slots = data.game_state.player_inventory().slots();
         self.mouse_tracker.new_frame(context.offset);
-        let equipped_indices = data.game_state.player_equipped_inventory_indices();
-        for ((i, entry, maybe_selected), &slot) in data
+        for ((i, entry, maybe_selected), slot) in data
             .inventory_slot_menu
             .menu_instance()
             .enumerate()
             .zip(player_inventory_slots.into_iter())
         {
-            let (name, name_colour) = if let Some(item_entity) = slot {
+            let (name, name_colour, count, charges) = if let Some(slot) = slot {
                 let item_type = data
                     .game_state
-                    .item_type(item_entity)
+                    .item_type(slot.entity())
                     .expect("non-item in player inventory");
-                (item_type.name(), colours::item_colour(item_type))
+                let identified = data.game_state.item_is_identified(slot.entity());
+                (
+                    item_type.display_name(identified),
+                    colours::item_display_colour(item_type, identified),
+                    slot.count(),
+                    data.game_state.item_charges(slot.entity()),
+                )
             } else {
-                ("-", Rgb24::new_grey(187))
+                ("-", Rgb24::new_grey(187), 0, None)
             };
             let (selected_prefix, prefix_style, name_style) = if maybe_selected.is_some() {
                 (
@@ -477,12 +486,12 @@ impl<'a> View<&'a AppData> for InventorySlotMenuView {
                 )
             };
             let prefix = format!("{} {}) ", selected_prefix, entry.key);
-            let equipment_suffix = if equipped_indices.held == Some(i) {
-                " (held)"
-            } else if equipped_indices.worn == Some(i) {
-                " (worn)"
+            let count_suffix = if let Some(charges) = charges {
+                format!(" ({} charges)", charges)
+            } else if count > 1 {
+                format!(" x{}", count)
             } else {
-                ""
+                String::new()
             };
             let text = &[
                 RichTextPart {
@@ -494,7 +503,7 @@ impl<'a> View<&'a AppData> for InventorySlotMenuView {
                     style: name_style,
                 },
                 RichTextPart {
-                    text: equipment_suffix,
+                    text: &count_suffix,
                     style: name_style,
                 },
             ];
@@ -505,9 +514,54 @@ impl<'a> View<&'a AppData> for InventorySlotMenuView {
             );
             self.mouse_tracker.on_entry_view_size(size);
         }
+        // List the player's equipment below the inventory, leaving a blank line in between
+        let equipment_offset = player_inventory_slots.len() as i32 + 1;
+        for (i, &slot) in EquipSlot::ALL.iter().enumerate() {
+            let (name, name_colour) = match data.game_state.player_equipped_item_type(slot) {
+                Some(item_type) => (item_type.name(), colours::item_colour(item_type)),
+                None => ("-", Rgb24::new_grey(187)),
+            };
+            let label = format!("{}: ", slot.name());
+            let text = &[
+                RichTextPart {
+                    text: &label,
+                    style: Style::new().with_foreground(Rgb24::new_grey(187)),
+                },
+                RichTextPart {
+                    text: name,
+                    style: Style::new().with_foreground(name_colour),
+                },
+            ];
+            RichTextViewSingleLine::new().view(
+                text.iter().cloned(),
+                context.add_offset(Coord::new(0, equipment_offset + i as i32)),
+                frame,
+            );
+        }
     }
 }
 
+fn inventory_slot_menu_instance(
+    inventory: &Inventory,
+) -> MenuInstanceChooseOrEscape<InventorySlotMenuEntry> {
+    let items = (0..inventory.slots().len())
+        .zip('a'..)
+        .map(|(index, key)| InventorySlotMenuEntry { index, key })
+        .collect::<Vec<_>>();
+    let hotkeys = items
+        .iter()
+        .map(|&entry| (entry.key, entry))
+        .collect::<HashMap<_, _>>();
+    MenuInstanceBuilder {
+        items,
+        hotkeys: Some(hotkeys),
+        selected_index: 0,
+    }
+    .build()
+    .unwrap()
+    .into_choose_or_escape()
+}
+
 fn inventory_slot_menu<'a>(
     title: &'a str,
 ) -> impl 'a
@@ -528,6 +582,8 @@ enum GameReturn {
     Menu,
     UseItem,
     DropItem,
+    SwapItem,
+    ThrowItem,
     GameOver,
     Examine,
     LevelUpAndDescend,
@@ -587,6 +643,26 @@ impl EventRoutine for GameEventRoutine {
 
 struct TargetEventRoutine {
     name: &'static str,
+    // The inventory slot of the item being aimed, if any. Aiming it somewhere that would hurt the
+    // player must be confirmed a second time.
+    aimed_item: Option<usize>,
+    confirmed_target: Option<Coord>,
+}
+
+impl TargetEventRoutine {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            aimed_item: None,
+            confirmed_target: None,
+        }
+    }
+    fn aiming_item(name: &'static str, inventory_index: usize) -> Self {
+        Self {
+            aimed_item: Some(inventory_index),
+            ..Self::new(name)
+        }
+    }
 }
 
 impl EventRoutine for TargetEventRoutine {
@@ -604,7 +680,7 @@ impl EventRoutine for TargetEventRoutine {
     where
         EP: EventOrPeek<Event = Self::Event>,
     {
-        event_routine::event_or_peek_with_handled(event_or_peek, self, |s, event| {
+        event_routine::event_or_peek_with_handled(event_or_peek, self, |mut s, event| {
             match event {
                 CommonEvent::Input(input) => match input {
                     Input::Keyboard(key) => {
@@ -615,6 +691,18 @@ impl EventRoutine for TargetEventRoutine {
                             KeyboardInput::Down => Coord::new(0, 1),
                             keys::RETURN => {
                                 let cursor = data.cursor;
+                                if let (Some(inventory_index), Some(target)) =
+                                    (s.aimed_item, cursor)
+                                {
+                                    if s.confirmed_target != Some(target)
+                                        && data
+                                            .game_state
+                                            .warn_if_aim_would_harm_player(inventory_index, target)
+                                    {
+                                        s.confirmed_target = Some(target);
+                                        return Handled::Continue(s);
+                                    }
+                                }
                                 data.cursor = None;
                                 return Handled::Return(cursor);
                             }
@@ -622,6 +710,18 @@ impl EventRoutine for TargetEventRoutine {
                                 data.cursor = None;
                                 return Handled::Return(None);
                             }
+                            // Tab moves the cursor to the next visible monster
+                            KeyboardInput::Char('\t') => {
+                                let from = data
+                                    .cursor
+                                    .unwrap_or_else(|| data.game_state.player_coord());
+                                if let Some(coord) =
+                                    data.game_state.next_visible_hostile_coord(from)
+                                {
+                                    data.cursor = Some(coord);
+                                }
+                                Coord::new(0, 0)
+                            }
                             _ => Coord::new(0, 0),
                         };
                         data.cursor = Some(
@@ -636,6 +736,16 @@ impl EventRoutine for TargetEventRoutine {
                             button: MouseButton::Left,
                             coord,
                         } => {
+                            if let Some(inventory_index) = s.aimed_item {
+                                if s.confirmed_target != Some(coord)
+                                    && data
+                                        .game_state
+                                        .warn_if_aim_would_harm_player(inventory_index, coord)
+                                {
+                                    s.confirmed_target = Some(coord);
+                                    return Handled::Continue(s);
+                                }
+                            }
                             data.cursor = None;
                             return Handled::Return(Some(coord));
                         }
@@ -659,6 +769,19 @@ impl EventRoutine for TargetEventRoutine {
         C: ColModify,
     {
         view.game_view.view(&data.game_state, context, frame);
+        if let Some(cursor) = data.cursor {
+            // Highlight the path a projectile would take towards the cursor
+            for coord in data.game_state.aim_line(cursor) {
+                frame.blend_cell_background_relative(
+                    coord,
+                    1,
+                    Rgb24::new(255, 127, 0),
+                    63,
+                    blend_mode::LinearInterpolate,
+                    context,
+                );
+            }
+        }
         view.render_ui(Some(self.name), &data, context, frame);
     }
 }
@@ -667,6 +790,8 @@ struct AppData {
     game_state: GameState,
     visibility_algorithm: VisibilityAlgorithm,
     inventory_slot_menu: MenuInstanceChooseOrEscape<InventorySlotMenuEntry>,
+    // The number of inventory slots the inventory slot menu was built for
+    inventory_capacity: usize,
     cursor: Option<Coord>,
     until_next_animation_tick: Duration,
     main_menu: MenuInstanceChooseOrEscape<MainMenuEntry>,
@@ -680,29 +805,13 @@ impl AppData {
         let game_area_size = screen_size.set_height(screen_size.height() - UI_NUM_ROWS);
         let game_state = Self::load_game()
             .unwrap_or_else(|| GameState::new(game_area_size, rng_seed, visibility_algorithm));
-        let player_inventory = game_state.player_inventory();
-        let inventory_slot_menu = {
-            let items = (0..player_inventory.slots().len())
-                .zip('a'..)
-                .map(|(index, key)| InventorySlotMenuEntry { index, key })
-                .collect::<Vec<_>>();
-            let hotkeys = items
-                .iter()
-                .map(|&entry| (entry.key, entry))
-                .collect::<HashMap<_, _>>();
-            MenuInstanceBuilder {
-                items,
-                hotkeys: Some(hotkeys),
-                selected_index: 0,
-            }
-            .build()
-            .unwrap()
-            .into_choose_or_escape()
-        };
+        let inventory_slot_menu = inventory_slot_menu_instance(game_state.player_inventory());
+        let inventory_capacity = game_state.player_inventory().slots().len();
         Self {
             game_state,
             visibility_algorithm,
             inventory_slot_menu,
+            inventory_capacity,
             cursor: None,
             until_next_animation_tick: Duration::from_millis(0),
             main_menu: main_menu_instance(),
@@ -718,6 +827,15 @@ impl AppData {
             self.rng_seed,
             self.visibility_algorithm,
         );
+        self.refresh_inventory_slot_menu();
+    }
+    // Rebuilds the inventory slot menu if the size of the player's inventory has changed
+    fn refresh_inventory_slot_menu(&mut self) {
+        let inventory = self.game_state.player_inventory();
+        if inventory.slots().len() != self.inventory_capacity {
+            self.inventory_capacity = inventory.slots().len();
+            self.inventory_slot_menu = inventory_slot_menu_instance(inventory);
+        }
     }
     fn save_game(&self) {
         let mut file_storage = match FileStorage::next_to_exe(SAVE_DIR, IfDirectoryMissing::Create)
@@ -761,17 +879,21 @@ impl AppData {
         match input {
             Input::Keyboard(key) => {
                 match key {
-                    KeyboardInput::Left => {
-                        self.game_state.maybe_move_player(CardinalDirection::West)
+                    KeyboardInput::Left => self.game_state.maybe_move_player(Direction::West),
+                    KeyboardInput::Right => self.game_state.maybe_move_player(Direction::East),
+                    KeyboardInput::Up => self.game_state.maybe_move_player(Direction::North),
+                    KeyboardInput::Down => self.game_state.maybe_move_player(Direction::South),
+                    KeyboardInput::Char('y') => {
+                        self.game_state.maybe_move_player(Direction::NorthWest)
                     }
-                    KeyboardInput::Right => {
-                        self.game_state.maybe_move_player(CardinalDirection::East)
+                    KeyboardInput::Char('u') => {
+                        self.game_state.maybe_move_player(Direction::NorthEast)
                     }
-                    KeyboardInput::Up => {
-                        self.game_state.maybe_move_player(CardinalDirection::North)
+                    KeyboardInput::Char('b') => {
+                        self.game_state.maybe_move_player(Direction::SouthWest)
                     }
-                    KeyboardInput::Down => {
-                        self.game_state.maybe_move_player(CardinalDirection::South)
+                    KeyboardInput::Char('n') => {
+                        self.game_state.maybe_move_player(Direction::SouthEast)
                     }
                     KeyboardInput::Char('>') => {
                         if self.game_state.is_player_on_stairs() {
@@ -779,9 +901,29 @@ impl AppData {
                         }
                     }
                     KeyboardInput::Char(' ') => self.game_state.wait_player(),
+                    KeyboardInput::Char('r') => {
+                        self.game_state.player_rest(MAX_REST_TURNS);
+                    }
                     KeyboardInput::Char('g') => self.game_state.maybe_player_get_item(),
+                    KeyboardInput::Char('p') => self.game_state.toggle_auto_pickup(),
                     KeyboardInput::Char('i') => return Some(GameReturn::UseItem),
+                    KeyboardInput::Char('s') => self.game_state.sort_player_inventory(),
                     KeyboardInput::Char('d') => return Some(GameReturn::DropItem),
+                    KeyboardInput::Char('t') => return Some(GameReturn::ThrowItem),
+                    KeyboardInput::Char('w') => return Some(GameReturn::SwapItem),
+                    KeyboardInput::Char('a') => self.game_state.maybe_player_sweep_attack(),
+                    // Take off the equipped weapon, offhand item or armour
+                    KeyboardInput::Char('e') => {
+                        let _ = self.game_state.maybe_player_unequip_item(EquipSlot::Weapon);
+                    }
+                    KeyboardInput::Char('o') => {
+                        let _ = self
+                            .game_state
+                            .maybe_player_unequip_item(EquipSlot::Offhand);
+                    }
+                    KeyboardInput::Char('c') => {
+                        let _ = self.game_state.maybe_player_unequip_item(EquipSlot::Armour);
+                    }
                     KeyboardInput::Char('x') => {
                         if self.cursor.is_none() {
                             self.cursor = Some(self.game_state.player_coord());
@@ -842,10 +984,16 @@ impl AppView {
         let player_hit_points = data.game_state.player_hit_points();
         let messages = data.game_state.message_log();
         let examine_cell = if let Some(cursor) = data.cursor {
+            // When the cursor is next to the player, its colour hints at what moving there does
+            let cursor_colour = match data.game_state.player_move_preview(cursor) {
+                Some(MovePreview::Attack) => Rgb24::new(255, 0, 0),
+                Some(MovePreview::Blocked) => Rgb24::new_grey(63),
+                Some(MovePreview::Enter) | None => Rgb24::new_grey(255),
+            };
             frame.blend_cell_background_relative(
                 cursor,
                 1,
-                Rgb24::new_grey(255),
+                cursor_colour,
                 127,
                 blend_mode::LinearInterpolate,
                 context,
@@ -864,6 +1012,10 @@ impl AppView {
                     strength: data.game_state.player_strength(),
                     dexterity: data.game_state.player_dexterity(),
                     intelligence: data.game_state.player_intelligence(),
+                    nutrition: data.game_state.player_nutrition(),
+                    gold: data.game_state.player_gold(),
+                    carry_weight: data.game_state.player_carry_weight(),
+                    status_effects: data.game_state.player_status_effects(),
                 },
                 dungeon_level: data.game_state.dungeon_level(),
             },
@@ -878,30 +1030,114 @@ pub mod colours {
     pub const PLAYER: Rgb24 = Rgb24::new_grey(255);
     pub const ORC: Rgb24 = Rgb24::new(0, 187, 0);
     pub const TROLL: Rgb24 = Rgb24::new(187, 0, 0);
+    pub const ARCHER: Rgb24 = Rgb24::new(187, 187, 0);
+    pub const DIGGER: Rgb24 = Rgb24::new(187, 127, 63);
+    pub const ARROW: Rgb24 = Rgb24::new_grey(187);
+    pub const TRAP: Rgb24 = Rgb24::new(255, 63, 0);
+    pub const GOLD: Rgb24 = Rgb24::new(255, 215, 0);
+    pub const FIRE: Rgb24 = Rgb24::new(255, 95, 0);
     pub const HEALTH_POTION: Rgb24 = Rgb24::new(255, 0, 255);
+    pub const ACID_POTION: Rgb24 = Rgb24::new(127, 255, 0);
+    pub const STRENGTH_POTION: Rgb24 = Rgb24::new(255, 127, 0);
     pub const FIREBALL_SCROLL: Rgb24 = Rgb24::new(255, 127, 0);
     pub const CONFUSION_SCROLL: Rgb24 = Rgb24::new(187, 0, 255);
+    pub const LIGHTNING_SCROLL: Rgb24 = Rgb24::new(255, 255, 63);
+    pub const CHAIN_LIGHTNING_SCROLL: Rgb24 = Rgb24::new(191, 191, 255);
+    pub const TELEPORT_SCROLL: Rgb24 = Rgb24::new(0, 187, 255);
+    pub const MAPPING_SCROLL: Rgb24 = Rgb24::new(127, 255, 127);
+    pub const EARTHQUAKE_SCROLL: Rgb24 = Rgb24::new(187, 127, 63);
+    pub const HASTE_SCROLL: Rgb24 = Rgb24::new(255, 255, 187);
+    pub const BLINDNESS_SCROLL: Rgb24 = Rgb24::new(63, 63, 127);
+    pub const CLAIRVOYANCE_SCROLL: Rgb24 = Rgb24::new(187, 255, 255);
+    pub const DETECT_LIFE_SCROLL: Rgb24 = Rgb24::new(255, 127, 255);
+    pub const POLYMORPH_SCROLL: Rgb24 = Rgb24::new(63, 255, 191);
+    pub const TELEPORT_OTHER_SCROLL: Rgb24 = Rgb24::new(191, 63, 255);
+    pub const IDENTIFY_SCROLL: Rgb24 = Rgb24::new(255, 255, 255);
+    pub const REMOVE_CURSE_SCROLL: Rgb24 = Rgb24::new(255, 255, 127);
+    pub const UNIDENTIFIED_ITEM: Rgb24 = Rgb24::new_grey(187);
+    pub const BACKPACK: Rgb24 = Rgb24::new(127, 95, 63);
+    pub const RATION: Rgb24 = Rgb24::new(187, 127, 63);
+    pub const NET: Rgb24 = Rgb24::new(187, 187, 127);
+    pub const PICKAXE: Rgb24 = Rgb24::new(127, 187, 187);
+    pub const FIREBALL_WAND: Rgb24 = Rgb24::new(255, 63, 0);
+    pub const SUMMON_SCROLL: Rgb24 = Rgb24::new(255, 187, 187);
     pub const SWORD: Rgb24 = Rgb24::new(187, 187, 187);
+    pub const DAGGER: Rgb24 = Rgb24::new(187, 187, 127);
+    pub const POISON_DAGGER: Rgb24 = Rgb24::new(127, 187, 63);
+    pub const VAMPIRIC_DAGGER: Rgb24 = Rgb24::new(187, 0, 63);
     pub const STAFF: Rgb24 = Rgb24::new(187, 127, 255);
+    pub const SHIELD: Rgb24 = Rgb24::new(187, 187, 255);
+    pub const REFLECT_SHIELD: Rgb24 = Rgb24::new(255, 255, 187);
     pub const ARMOUR: Rgb24 = Rgb24::new(127, 127, 127);
+    pub const LEATHER_ARMOUR: Rgb24 = Rgb24::new(187, 127, 63);
     pub const ROBE: Rgb24 = Rgb24::new(127, 127, 187);
+    pub const RED_KEY: Rgb24 = Rgb24::new(255, 63, 63);
+    pub const GREEN_KEY: Rgb24 = Rgb24::new(63, 255, 63);
+    pub const BLUE_KEY: Rgb24 = Rgb24::new(63, 127, 255);
 
     pub fn npc_colour(npc_type: NpcType) -> Rgb24 {
         match npc_type {
             NpcType::Orc => ORC,
             NpcType::Troll => TROLL,
+            NpcType::Archer => ARCHER,
+            NpcType::Digger => DIGGER,
         }
     }
 
     pub fn item_colour(item_type: ItemType) -> Rgb24 {
         match item_type {
             ItemType::HealthPotion => HEALTH_POTION,
+            ItemType::AcidPotion => ACID_POTION,
+            ItemType::StrengthPotion => STRENGTH_POTION,
             ItemType::FireballScroll => FIREBALL_SCROLL,
             ItemType::ConfusionScroll => CONFUSION_SCROLL,
+            ItemType::LightningScroll => LIGHTNING_SCROLL,
+            ItemType::ChainLightningScroll => CHAIN_LIGHTNING_SCROLL,
+            ItemType::TeleportScroll => TELEPORT_SCROLL,
+            ItemType::SummonScroll => SUMMON_SCROLL,
+            ItemType::MappingScroll => MAPPING_SCROLL,
+            ItemType::EarthquakeScroll => EARTHQUAKE_SCROLL,
+            ItemType::HasteScroll => HASTE_SCROLL,
+            ItemType::BlindnessScroll => BLINDNESS_SCROLL,
+            ItemType::PolymorphScroll => POLYMORPH_SCROLL,
+            ItemType::TeleportOtherScroll => TELEPORT_OTHER_SCROLL,
+            ItemType::ClairvoyanceScroll => CLAIRVOYANCE_SCROLL,
+            ItemType::DetectLifeScroll => DETECT_LIFE_SCROLL,
+            ItemType::IdentifyScroll => IDENTIFY_SCROLL,
+            ItemType::RemoveCurseScroll => REMOVE_CURSE_SCROLL,
+            ItemType::Ration => RATION,
+            ItemType::Backpack => BACKPACK,
+            ItemType::Net => NET,
+            ItemType::Pickaxe => PICKAXE,
+            ItemType::FireballWand => FIREBALL_WAND,
             ItemType::Sword => SWORD,
+            ItemType::Dagger => DAGGER,
+            ItemType::PoisonDagger => POISON_DAGGER,
+            ItemType::VampiricDagger => VAMPIRIC_DAGGER,
             ItemType::Staff => STAFF,
+            ItemType::Shield => SHIELD,
+            ItemType::ReflectShield => REFLECT_SHIELD,
             ItemType::Armour => ARMOUR,
+            ItemType::LeatherArmour => LEATHER_ARMOUR,
             ItemType::Robe => ROBE,
+            ItemType::Key(colour) => key_colour(colour),
+        }
+    }
+
+    pub fn key_colour(colour: KeyColour) -> Rgb24 {
+        match colour {
+            KeyColour::Red => RED_KEY,
+            KeyColour::Green => GREEN_KEY,
+            KeyColour::Blue => BLUE_KEY,
+        }
+    }
+
+    // Unidentified items share a colour so it doesn't give away their type
+    pub fn item_display_colour(item_type: ItemType, identified: bool) -> Rgb24 {
+        if identified {
+            item_colour(item_type)
+        } else {
+            UNIDENTIFIED_ITEM
         }
     }
 
@@ -909,6 +1145,12 @@ pub mod colours {
         match projcetile_type {
             ProjectileType::Fireball { .. } => FIREBALL_SCROLL,
             ProjectileType::Confusion { .. } => CONFUSION_SCROLL,
+            ProjectileType::Blindness { .. } => BLINDNESS_SCROLL,
+            ProjectileType::Polymorph => POLYMORPH_SCROLL,
+            ProjectileType::ChainLightning { .. } => CHAIN_LIGHTNING_SCROLL,
+            ProjectileType::Net { .. } => NET,
+            ProjectileType::Arrow { .. } => ARROW,
+            ProjectileType::Thrown { item_type, .. } => item_colour(item_type),
         }
     }
 }
@@ -930,10 +1172,39 @@ fn currently_visible_view_cell_of_tile(tile: Tile) -> ViewCell {
             .with_bold(true)
             .with_foreground(Rgb24::new_grey(255))
             .with_background(Rgb24::new(0, 0, 63)),
-        Tile::Wall => ViewCell::new()
+        Tile::Wall | Tile::SecretWall => ViewCell::new()
             .with_character('#')
             .with_foreground(Rgb24::new(0, 63, 63))
             .with_background(Rgb24::new(63, 127, 127)),
+        Tile::DoorClosed => ViewCell::new()
+            .with_character('+')
+            .with_bold(true)
+            .with_foreground(Rgb24::new(127, 63, 0))
+            .with_background(Rgb24::new(63, 127, 127)),
+        Tile::LockedDoor(colour) => ViewCell::new()
+            .with_character('+')
+            .with_bold(true)
+            .with_foreground(colours::key_colour(colour))
+            .with_background(Rgb24::new(63, 127, 127)),
+        Tile::DoorOpen => ViewCell::new()
+            .with_character('\'')
+            .with_bold(true)
+            .with_foreground(Rgb24::new(127, 63, 0))
+            .with_background(Rgb24::new(0, 0, 63)),
+        Tile::Trap => ViewCell::new()
+            .with_character('^')
+            .with_bold(true)
+            .with_foreground(colours::TRAP)
+            .with_background(Rgb24::new(0, 0, 63)),
+        Tile::Fire => ViewCell::new()
+            .with_character('≈')
+            .with_bold(true)
+            .with_foreground(colours::FIRE)
+            .with_background(Rgb24::new(63, 0, 0)),
+        Tile::Gold => ViewCell::new()
+            .with_character('$')
+            .with_bold(true)
+            .with_foreground(colours::GOLD),
         Tile::Npc(NpcType::Orc) => ViewCell::new()
             .with_character('o')
             .with_bold(true)
@@ -942,6 +1213,14 @@ fn currently_visible_view_cell_of_tile(tile: Tile) -> ViewCell {
             .with_character('T')
             .with_bold(true)
             .with_foreground(colours::TROLL),
+        Tile::Npc(NpcType::Archer) => ViewCell::new()
+            .with_character('a')
+            .with_bold(true)
+            .with_foreground(colours::ARCHER),
+        Tile::Npc(NpcType::Digger) => ViewCell::new()
+            .with_character('d')
+            .with_bold(true)
+            .with_foreground(colours::DIGGER),
         Tile::NpcCorpse(NpcType::Orc) => ViewCell::new()
             .with_character('%')
             .with_bold(true)
@@ -950,37 +1229,158 @@ fn currently_visible_view_cell_of_tile(tile: Tile) -> ViewCell {
             .with_character('%')
             .with_bold(true)
             .with_foreground(colours::TROLL),
+        Tile::NpcCorpse(NpcType::Archer) => ViewCell::new()
+            .with_character('%')
+            .with_bold(true)
+            .with_foreground(colours::ARCHER),
+        Tile::NpcCorpse(NpcType::Digger) => ViewCell::new()
+            .with_character('%')
+            .with_bold(true)
+            .with_foreground(colours::DIGGER),
         Tile::Item(ItemType::HealthPotion) => ViewCell::new()
             .with_character('!')
             .with_foreground(colours::HEALTH_POTION),
+        Tile::Item(ItemType::AcidPotion) => ViewCell::new()
+            .with_character('!')
+            .with_foreground(colours::ACID_POTION),
+        Tile::Item(ItemType::StrengthPotion) => ViewCell::new()
+            .with_character('!')
+            .with_foreground(colours::STRENGTH_POTION),
         Tile::Item(ItemType::FireballScroll) => ViewCell::new()
             .with_character('♫')
             .with_foreground(colours::FIREBALL_SCROLL),
         Tile::Item(ItemType::ConfusionScroll) => ViewCell::new()
             .with_character('♫')
             .with_foreground(colours::CONFUSION_SCROLL),
+        Tile::Item(ItemType::LightningScroll) => ViewCell::new()
+            .with_character('♫')
+            .with_foreground(colours::LIGHTNING_SCROLL),
+        Tile::Item(ItemType::ChainLightningScroll) => ViewCell::new()
+            .with_character('♫')
+            .with_foreground(colours::CHAIN_LIGHTNING_SCROLL),
+        Tile::Item(ItemType::TeleportScroll) => ViewCell::new()
+            .with_character('♫')
+            .with_foreground(colours::TELEPORT_SCROLL),
+        Tile::Item(ItemType::SummonScroll) => ViewCell::new()
+            .with_character('♫')
+            .with_foreground(colours::SUMMON_SCROLL),
+        Tile::Item(ItemType::MappingScroll) => ViewCell::new()
+            .with_character('♫')
+            .with_foreground(colours::MAPPING_SCROLL),
+        Tile::Item(ItemType::EarthquakeScroll) => ViewCell::new()
+            .with_character('♫')
+            .with_foreground(colours::EARTHQUAKE_SCROLL),
+        Tile::Item(ItemType::HasteScroll) => ViewCell::new()
+            .with_character('♫')
+            .with_foreground(colours::HASTE_SCROLL),
+        Tile::Item(ItemType::BlindnessScroll) => ViewCell::new()
+            .with_character('♫')
+            .with_foreground(colours::BLINDNESS_SCROLL),
+        Tile::Item(ItemType::PolymorphScroll) => ViewCell::new()
+            .with_character('♫')
+            .with_foreground(colours::POLYMORPH_SCROLL),
+        Tile::Item(ItemType::TeleportOtherScroll) => ViewCell::new()
+            .with_character('♫')
+            .with_foreground(colours::TELEPORT_OTHER_SCROLL),
+        Tile::Item(ItemType::ClairvoyanceScroll) => ViewCell::new()
+            .with_character('♫')
+            .with_foreground(colours::CLAIRVOYANCE_SCROLL),
+        Tile::Item(ItemType::DetectLifeScroll) => ViewCell::new()
+            .with_character('♫')
+            .with_foreground(colours::DETECT_LIFE_SCROLL),
+        Tile::Item(ItemType::IdentifyScroll) => ViewCell::new()
+            .with_character('♫')
+            .with_foreground(colours::IDENTIFY_SCROLL),
+        Tile::Item(ItemType::RemoveCurseScroll) => ViewCell::new()
+            .with_character('♫')
+            .with_foreground(colours::REMOVE_CURSE_SCROLL),
+        Tile::Item(ItemType::Ration) => ViewCell::new()
+            .with_character(':')
+            .with_foreground(colours::RATION),
+        Tile::Item(ItemType::Backpack) => ViewCell::new()
+            .with_bold(true)
+            .with_character('{')
+            .with_foreground(colours::BACKPACK),
+        Tile::Item(ItemType::Net) => ViewCell::new()
+            .with_character('≡')
+            .with_foreground(colours::NET),
+        Tile::Item(ItemType::Pickaxe) => ViewCell::new()
+            .with_bold(true)
+            .with_character('(')
+            .with_foreground(colours::PICKAXE),
+        Tile::Item(ItemType::FireballWand) => ViewCell::new()
+            .with_bold(true)
+            .with_character('-')
+            .with_foreground(colours::FIREBALL_WAND),
         Tile::Item(ItemType::Sword) => ViewCell::new()
             .with_bold(true)
             .with_character('/')
             .with_foreground(colours::SWORD),
+        Tile::Item(ItemType::Dagger) => ViewCell::new()
+            .with_bold(true)
+            .with_character('|')
+            .with_foreground(colours::DAGGER),
+        Tile::Item(ItemType::PoisonDagger) => ViewCell::new()
+            .with_bold(true)
+            .with_character('|')
+            .with_foreground(colours::POISON_DAGGER),
+        Tile::Item(ItemType::VampiricDagger) => ViewCell::new()
+            .with_bold(true)
+            .with_character('|')
+            .with_foreground(colours::VAMPIRIC_DAGGER),
         Tile::Item(ItemType::Staff) => ViewCell::new()
             .with_bold(true)
             .with_character('\\')
             .with_foreground(colours::STAFF),
+        Tile::Item(ItemType::Shield) => ViewCell::new()
+            .with_bold(true)
+            .with_character(')')
+            .with_foreground(colours::SHIELD),
+        Tile::Item(ItemType::ReflectShield) => ViewCell::new()
+            .with_bold(true)
+            .with_character(')')
+            .with_foreground(colours::REFLECT_SHIELD),
         Tile::Item(ItemType::Armour) => ViewCell::new()
             .with_bold(true)
             .with_character(']')
             .with_foreground(colours::ARMOUR),
+        Tile::Item(ItemType::LeatherArmour) => ViewCell::new()
+            .with_bold(true)
+            .with_character('[')
+            .with_foreground(colours::LEATHER_ARMOUR),
         Tile::Item(ItemType::Robe) => ViewCell::new()
             .with_bold(true)
             .with_character('}')
             .with_foreground(colours::ROBE),
+        Tile::Item(ItemType::Key(colour)) => ViewCell::new()
+            .with_bold(true)
+            .with_character('¬')
+            .with_foreground(colours::key_colour(colour)),
         Tile::Projectile(ProjectileType::Fireball { .. }) => ViewCell::new()
             .with_character('*')
             .with_foreground(colours::FIREBALL_SCROLL),
         Tile::Projectile(ProjectileType::Confusion { .. }) => ViewCell::new()
             .with_character('*')
             .with_foreground(colours::CONFUSION_SCROLL),
+        Tile::Projectile(ProjectileType::Blindness { .. }) => ViewCell::new()
+            .with_character('*')
+            .with_foreground(colours::BLINDNESS_SCROLL),
+        Tile::Projectile(ProjectileType::Polymorph) => ViewCell::new()
+            .with_character('*')
+            .with_foreground(colours::POLYMORPH_SCROLL),
+        Tile::Projectile(ProjectileType::ChainLightning { .. }) => ViewCell::new()
+            .with_character('*')
+            .with_foreground(colours::CHAIN_LIGHTNING_SCROLL),
+        Tile::Projectile(ProjectileType::Net { .. }) => ViewCell::new()
+            .with_character('≡')
+            .with_foreground(colours::NET),
+        Tile::Projectile(ProjectileType::Arrow { .. }) => ViewCell::new()
+            .with_character('-')
+            .with_foreground(colours::ARROW),
+        // A thrown item looks the same in flight as it does on the floor
+        Tile::Projectile(ProjectileType::Thrown { item_type, .. }) => {
+            currently_visible_view_cell_of_tile(Tile::Item(item_type))
+        }
     }
 }
 
@@ -990,10 +1390,22 @@ fn previously_visible_view_cell_of_tile(tile: Tile) -> ViewCell {
             .with_character('.')
             .with_foreground(Rgb24::new_grey(63))
             .with_background(Rgb24::new_grey(0)),
-        Tile::Wall => ViewCell::new()
+        Tile::Wall | Tile::SecretWall => ViewCell::new()
             .with_character('#')
             .with_foreground(Rgb24::new_grey(63))
             .with_background(Rgb24::new_grey(0)),
+        Tile::DoorClosed | Tile::LockedDoor(_) => ViewCell::new()
+            .with_character('+')
+            .with_foreground(Rgb24::new_grey(63))
+            .with_background(Rgb24::new_grey(0)),
+        Tile::DoorOpen => ViewCell::new()
+            .with_character('\'')
+            .with_foreground(Rgb24::new_grey(63))
+            .with_background(Rgb24::new_grey(0)),
+        Tile::Trap => ViewCell::new()
+            .with_character('^')
+            .with_foreground(Rgb24::new_grey(63))
+            .with_background(Rgb24::new_grey(0)),
         _ => ViewCell::new(),
     }
 }
@@ -1028,6 +1440,37 @@ impl<'a> View<&'a GameState> for GameView {
             };
             frame.set_cell_relative(entity_to_render.location.coord, depth, view_cell, context);
         }
+        // In-flight projectiles light up the cell they're passing through
+        for (coord, projectile_type) in game_state.visible_projectiles() {
+            let glow = colours::projectile_colour(projectile_type).saturating_scalar_mul_div(1, 3);
+            frame.set_cell_relative(coord, 4, ViewCell::new().with_background(glow), context);
+        }
+        // Recent damage is shown as a number above the cell where it was dealt
+        for event in game_state.visible_damage_events() {
+            let colour = match event.kind {
+                DamageKind::Fire => colours::FIRE,
+                DamageKind::Poison => colours::POISON_DAGGER,
+                DamageKind::Acid => colours::ACID_POTION,
+                _ => Rgb24::new(255, 0, 0),
+            };
+            let text = event.amount.to_string();
+            let origin = event.coord - Coord::new(0, 1);
+            for (i, ch) in text.chars().enumerate() {
+                let view_cell = ViewCell::new()
+                    .with_character(ch)
+                    .with_bold(true)
+                    .with_foreground(colour);
+                frame.set_cell_relative(origin + Coord::new(i as i32, 0), 5, view_cell, context);
+            }
+        }
+        // Characters sensed through walls are marked, but not identified
+        for coord in game_state.detected_life_coords() {
+            let view_cell = ViewCell::new()
+                .with_character('&')
+                .with_bold(true)
+                .with_foreground(colours::DETECT_LIFE_SCROLL);
+            frame.set_cell_relative(coord, 5, view_cell, context);
+        }
     }
 }
 
@@ -1039,17 +1482,48 @@ fn use_item() -> impl EventRoutine<Return = (), Data = AppData, View = AppView,
             Err(menu::Escape) => Ei::A(Value::new(Some(()))),
             Ok(entry) => Ei::B(SideEffectThen::new_with_view(
                 move |data: &mut AppData, _: &_| {
-                    make_either!(Ei = A | B | C);
-                    if let Ok(usage) = data.game_state.maybe_player_use_item(entry.index) {
+                    make_either!(Ei = A | B | C | D);
+                    let result = data.game_state.maybe_player_use_item(entry.index);
+                    data.refresh_inventory_slot_menu();
+                    if let Ok(usage) = result {
                         match usage {
                             ItemUsage::Immediate => Ei::A(Value::new(Some(()))),
-                            ItemUsage::Aim => Ei::B(TargetEventRoutine { name: "AIM" }.and_then(
-                                move |maybe_coord| {
+                            ItemUsage::Aim => Ei::B(
+                                TargetEventRoutine::aiming_item("AIM", entry.index).and_then(
+                                    move |maybe_coord| {
+                                        SideEffect::new_with_view(
+                                            move |data: &mut AppData, _: &_| {
+                                                if let Some(coord) = maybe_coord {
+                                                    if data
+                                                        .game_state
+                                                        .maybe_player_use_item_aim(
+                                                            entry.index,
+                                                            coord,
+                                                        )
+                                                        .is_ok()
+                                                    {
+                                                        Some(())
+                                                    } else {
+                                                        None
+                                                    }
+                                                } else {
+                                                    None
+                                                }
+                                            },
+                                        )
+                                    },
+                                ),
+                            ),
+                            ItemUsage::ChooseItem => {
+                                Ei::D(inventory_slot_menu("Choose Item").and_then(move |result| {
                                     SideEffect::new_with_view(move |data: &mut AppData, _: &_| {
-                                        if let Some(coord) = maybe_coord {
+                                        if let Ok(target) = result {
                                             if data
                                                 .game_state
-                                                .maybe_player_use_item_aim(entry.index, coord)
+                                                .maybe_player_use_item_choose(
+                                                    entry.index,
+                                                    target.index,
+                                                )
                                                 .is_ok()
                                             {
                                                 Some(())
@@ -1060,8 +1534,8 @@ fn use_item() -> impl EventRoutine<Return = (), Data = AppData, View = AppView,
                                             None
                                         }
                                     })
-                                },
-                            )),
+                                }))
+                            }
                         }
                     } else {
                         Ei::C(Value::new(None))
@@ -1091,6 +1565,67 @@ fn drop_item() -> impl EventRoutine<Return = (), Data = AppData, View = AppView,
     })
 }
 
+fn swap_item() -> impl EventRoutine<Return = (), Data = AppData, View = AppView, Event = CommonEvent>
+{
+    make_either!(Ei = A | B);
+    Loop::new(|| {
+        inventory_slot_menu("Swap Item").and_then(|result| match result {
+            Err(menu::Escape) => Ei::A(Value::new(Some(()))),
+            Ok(entry) => Ei::B(SideEffect::new_with_view(
+                move |data: &mut AppData, _: &_| {
+                    if data.game_state.maybe_player_swap_item(entry.index).is_ok() {
+                        Some(())
+                    } else {
+                        None
+                    }
+                },
+            )),
+        })
+    })
+}
+
+fn throw_item(
+) -> impl EventRoutine<Return = (), Data = AppData, View = AppView, Event = CommonEvent> {
+    make_either!(Ei = A | B);
+    Loop::new(|| {
+        inventory_slot_menu("Throw Item").and_then(|result| match result {
+            Err(menu::Escape) => Ei::A(Value::new(Some(()))),
+            Ok(entry) => Ei::B(SideEffectThen::new_with_view(
+                move |data: &mut AppData, _: &_| {
+                    make_either!(Ei = A | B);
+                    if data
+                        .game_state
+                        .check_player_can_throw_item(entry.index)
+                        .is_ok()
+                    {
+                        Ei::A(
+                            TargetEventRoutine::new("THROW").and_then(move |maybe_coord| {
+                                SideEffect::new_with_view(move |data: &mut AppData, _: &_| {
+                                    if let Some(coord) = maybe_coord {
+                                        if data
+                                            .game_state
+                                            .maybe_player_throw_item(entry.index, coord)
+                                            .is_ok()
+                                        {
+                                            Some(())
+                                        } else {
+                                            None
+                                        }
+                                    } else {
+                                        None
+                                    }
+                                })
+                            }),
+                        )
+                    } else {
+                        Ei::B(Value::new(None))
+                    }
+                },
+            )),
+        })
+    })
+}
+
 fn game_over() -> impl EventRoutine<Return = (), Data = AppData, View = AppView, Event = CommonEvent>
 {
     struct GameOverDecorate;
@@ -1117,6 +1652,21 @@ fn game_over() -> impl EventRoutine<Return = (), Data = AppData, View = AppView,
                 ),
             }
             .view("YOU DIED", context.add_depth(10), frame);
+            // The level's seed lets the level where the player died be generated again
+            let summary = format!(
+                "Died on level {} (level seed: {})",
+                data.game_state.dungeon_level(),
+                data.game_state.level_seed()
+            );
+            AlignView {
+                alignment: Alignment::centre(),
+                view: StringViewSingleLine::new(Style::new().with_foreground(Rgb24::new_grey(187))),
+            }
+            .view(
+                summary.as_str(),
+                context.add_offset(Coord::new(0, 2)).add_depth(10),
+                frame,
+            );
             FillBackgroundView {
                 rgb24: Rgb24::new(31, 0, 0),
                 view: &mut event_routine_view.view.game_view,
@@ -1139,7 +1689,7 @@ fn game_over() -> impl EventRoutine<Return = (), Data = AppData, View = AppView,
 
 fn game_loop() -> impl EventRoutine<Return = (), Data = AppData, View = AppView, Event = CommonEvent>
 {
-    make_either!(Ei = A | B | C | D | E | F);
+    make_either!(Ei = A | B | C | D | E | F | G | H);
     Loop::new(|| {
         GameEventRoutine.and_then(|game_return| match game_return {
             GameReturn::Menu => Ei::A(main_menu().and_then(|choice| {
@@ -1169,7 +1719,9 @@ fn game_loop() -> impl EventRoutine<Return = (), Data = AppData, View = AppView,
             })),
             GameReturn::UseItem => Ei::C(use_item().map(|_| None)),
             GameReturn::DropItem => Ei::D(drop_item().map(|_| None)),
-            GameReturn::Examine => Ei::E(TargetEventRoutine { name: "EXAMINE" }.map(|_| None)),
+            GameReturn::SwapItem => Ei::H(swap_item().map(|_| None)),
+            GameReturn::ThrowItem => Ei::G(throw_item().map(|_| None)),
+            GameReturn::Examine => Ei::E(TargetEventRoutine::new("EXAMINE").map(|_| None)),
             GameReturn::LevelUpAndDescend => Ei::F(level_up_menu().and_then(|maybe_level_up| {
                 SideEffect::new_with_view(move |data: &mut AppData, _: &_| {
                     match maybe_level_up {
@@ -1,4 +1,4 @@
-use crate::world::World;
+use crate::world::{Faction, NpcType, World};
 use coord_2d::{Coord, Size};
 use direction::CardinalDirection;
 use entity_table::Entity;
@@ -9,7 +9,7 @@ use grid_search_cardinal::{
     },
     CanEnter,
 };
-use line_2d::LineSegment;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use shadowcast::{vision_distance, VisionDistance};
 
@@ -52,45 +52,111 @@ impl BehaviourContext {
 pub enum NpcAction {
     Wait,
     Move(CardinalDirection),
+    FireProjectile(Coord),
+    Dig(Coord),
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct Agent {
     turns_since_last_saw_player: u32,
+    fleeing: bool,
+    // An alerted npc pursues the player even without seeing them, until it next sees the player
+    alerted: bool,
+    // A sleeping npc stays where it is until it notices the player
+    asleep: bool,
 }
 
 fn npc_has_line_of_sight(src: Coord, dst: Coord, world: &World) -> bool {
     const NPC_VISION_DISTANCE_SQUARED: u32 = 100;
     const NPC_VISION_DISTANCE: vision_distance::Circle =
         vision_distance::Circle::new_squared(NPC_VISION_DISTANCE_SQUARED);
-    if src == dst {
-        return true;
+    if !NPC_VISION_DISTANCE.in_range(dst - src) {
+        return false;
     }
-    for coord in LineSegment::new(src, dst).iter() {
-        let src_to_coord = coord - src;
-        if !NPC_VISION_DISTANCE.in_range(src_to_coord) {
-            return false;
-        }
-        if !world.can_npc_see_through_cell(coord) {
-            return false;
-        }
-    }
-    true
+    world.has_line_of_sight(src, dst)
 }
 
 impl Agent {
     pub fn new() -> Self {
         Self {
             turns_since_last_saw_player: u32::MAX,
+            fleeing: false,
+            alerted: false,
+            asleep: false,
         }
     }
 
-    pub fn act(
+    // Npcs generated with the level start asleep so they don't all converge on the player at once
+    pub fn new_asleep() -> Self {
+        Self {
+            asleep: true,
+            ..Self::new()
+        }
+    }
+
+    pub fn is_asleep(&self) -> bool {
+        self.asleep
+    }
+
+    pub fn alert(&mut self) {
+        self.alerted = true;
+    }
+
+    // True once the npc has started fleeing from the player
+    pub fn is_fleeing(&self) -> bool {
+        self.fleeing
+    }
+
+    // The step which takes the npc furthest from the player, if any step increases the distance
+    // between them
+    fn flee_direction(
+        npc_coord: Coord,
+        player_coord: Coord,
+        world: &World,
+    ) -> Option<CardinalDirection> {
+        let distance_squared = |coord: Coord| {
+            let delta = coord - player_coord;
+            delta.x * delta.x + delta.y * delta.y
+        };
+        let current_distance_squared = distance_squared(npc_coord);
+        CardinalDirection::all()
+            .filter(|direction| world.can_npc_enter(npc_coord + direction.coord()))
+            .map(|direction| (direction, distance_squared(npc_coord + direction.coord())))
+            .filter(|&(_, distance)| distance > current_distance_squared)
+            .max_by_key(|&(_, distance)| distance)
+            .map(|(direction, _)| direction)
+    }
+
+    // Allies of the player attack the nearest visible hostile character, or otherwise follow the
+    // player around
+    fn act_ally(entity: Entity, npc_coord: Coord, player_coord: Coord, world: &World) -> NpcAction {
+        const ALLY_VISION_RANGE: u32 = 10;
+        let target_coord = match world
+            .nearest_visible_hostile(entity, ALLY_VISION_RANGE)
+            .and_then(|hostile| world.entity_coord(hostile))
+        {
+            Some(hostile_coord) => hostile_coord,
+            None => {
+                let delta = player_coord - npc_coord;
+                if delta.x.abs() + delta.y.abs() <= 1 {
+                    return NpcAction::Wait;
+                }
+                player_coord
+            }
+        };
+        match world.path_toward(npc_coord, target_coord) {
+            Some(direction) => NpcAction::Move(direction),
+            None => NpcAction::Wait,
+        }
+    }
+
+    pub fn act<R: Rng>(
         &mut self,
         entity: Entity,
         player: Entity,
         world: &World,
         behaviour_context: &mut BehaviourContext,
+        rng: &mut R,
     ) -> NpcAction {
         struct NpcCanEnter<'a> {
             world: &'a World,
@@ -102,15 +168,79 @@ impl Agent {
         }
         let npc_coord = world.entity_coord(entity).expect("npc has no coord");
         let player_coord = world.entity_coord(player).expect("player has no coord");
-        if npc_has_line_of_sight(npc_coord, player_coord, world) {
+        let player_is_adjacent = {
+            let delta = player_coord - npc_coord;
+            delta.x.abs() <= 1 && delta.y.abs() <= 1
+        };
+        let can_see_player = if world.entity_is_blind(entity) {
+            // A blind npc can only sense the player when they're adjacent
+            player_is_adjacent
+        } else {
+            npc_has_line_of_sight(npc_coord, player_coord, world)
+        };
+        if self.asleep {
+            if can_see_player || player_is_adjacent || self.alerted {
+                self.asleep = false;
+            } else {
+                return NpcAction::Wait;
+            }
+        }
+        if can_see_player {
             self.turns_since_last_saw_player = 0;
+            self.alerted = false;
         } else {
             self.turns_since_last_saw_player = self.turns_since_last_saw_player.saturating_add(1);
         }
+        if world.is_confused(entity) {
+            // A confused npc stumbles around at random rather than pursuing the player
+            return NpcAction::Move(rng.gen());
+        }
+        if world.is_rooted(entity) && !player_is_adjacent {
+            // There's no point trying to move while stuck in a net
+            return NpcAction::Wait;
+        }
+        if world.faction(entity) == Some(Faction::Player) {
+            return Self::act_ally(entity, npc_coord, player_coord, world);
+        }
+        let badly_hurt = match (world.npc_type(entity), world.hit_points(entity)) {
+            (Some(npc_type), Some(hit_points)) => hit_points.current <= npc_type.flee_threshold(),
+            _ => false,
+        };
+        if badly_hurt && self.turns_since_last_saw_player == 0 {
+            // A cornered npc with nowhere to flee to fights on as usual
+            if let Some(direction) = Self::flee_direction(npc_coord, player_coord, world) {
+                self.fleeing = true;
+                return NpcAction::Move(direction);
+            }
+        }
+        if self.turns_since_last_saw_player == 0 && world.npc_type(entity) == Some(NpcType::Archer)
+        {
+            // Archers shoot at the player from a distance rather than approaching
+            let delta = player_coord - npc_coord;
+            if delta.x.abs() + delta.y.abs() > 1 {
+                return NpcAction::FireProjectile(player_coord);
+            }
+        }
         const MAX_TURNS_TO_CHASE_PLAYER_AFTER_LOSING_SIGHT: u32 = 3;
-        if self.turns_since_last_saw_player > MAX_TURNS_TO_CHASE_PLAYER_AFTER_LOSING_SIGHT {
+        if !self.alerted
+            && self.turns_since_last_saw_player > MAX_TURNS_TO_CHASE_PLAYER_AFTER_LOSING_SIGHT
+        {
             return NpcAction::Wait;
         }
+        if world.npc_type(entity) == Some(NpcType::Digger) {
+            // Diggers tunnel straight towards the player rather than following corridors
+            return match world.path_toward_digging(npc_coord, player_coord) {
+                Some(direction) => {
+                    let coord = npc_coord + direction.coord();
+                    if world.can_npc_dig(coord) {
+                        NpcAction::Dig(coord)
+                    } else {
+                        NpcAction::Move(direction)
+                    }
+                }
+                None => NpcAction::Wait,
+            };
+        }
         const SEARCH_DISTANCE: u32 = 5;
         match behaviour_context.distance_map_search_context.search_first(
             &NpcCanEnter { world },
@@ -118,8 +248,13 @@ impl Agent {
             SEARCH_DISTANCE,
             &behaviour_context.distance_map_to_player,
         ) {
-            None => NpcAction::Wait,
             Some(direction) => NpcAction::Move(direction),
+            // The distance map ignores other npcs and only extends a limited distance from the
+            // player, so fall back to searching for a complete path to the player
+            None => match world.path_toward(npc_coord, player_coord) {
+                Some(direction) => NpcAction::Move(direction),
+                None => NpcAction::Wait,
+            },
         }
     }
 }
@@ -1,16 +1,19 @@
 use crate::behaviour::{Agent, BehaviourContext, NpcAction};
+use crate::terrain::{GenerationConfig, MonsterSpawnTable};
 use crate::visibility::{CellVisibility, VisibilityAlgorithm, VisibilityGrid};
 use crate::world::{
-    EquippedInventoryIndices, HitPoints, Inventory, ItemType, ItemUsage, Location, NpcType,
-    Populate, ProjectileType, Tile, World,
+    DamageEvent, DifficultyScale, EquipSlot, HitPoints, Inventory, ItemType, ItemUsage, KeyColour,
+    Location, MoveResult, NpcType, PlayerConfig, Populate, ProjectileType, SpawnError,
+    StatusEffect, Tile, World,
 };
 use coord_2d::{Coord, Size};
-use direction::CardinalDirection;
+use direction::Direction;
 use entity_table::ComponentTable;
 use entity_table::Entity;
-use rand::SeedableRng;
+use rand::{Rng, SeedableRng};
 use rand_isaac::Isaac64Rng;
 use serde::{Deserialize, Serialize};
+use std::mem;
 
 pub struct EntityToRender {
     pub tile: Tile,
@@ -21,31 +24,172 @@ pub struct EntityToRender {
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum LogMessage {
     PlayerAttacksNpc(NpcType),
+    PlayerCriticalHit(NpcType),
+    PlayerDrainsLife,
     NpcAttacksPlayer(NpcType),
     PlayerKillsNpc(NpcType),
     NpcKillsPlayer(NpcType),
-    PlayerGets(ItemType),
+    PlayerDies,
+    PlayerDiesOfPoison,
+    PlayerStarvesToDeath,
+    PlayerStarving,
+    PlayerEats,
+    PlayerTakesPoisonDamage,
+    NpcTakesPoisonDamage(NpcType),
+    NpcBleeds(NpcType),
+    NpcLaunchesProjectile(NpcType, ProjectileType),
+    PlayerIsHitByProjectile(ProjectileType),
+    ProjectileReflected(ProjectileType),
+    PlayerIsBurned,
+    PlayerIsSplashedWithAcid,
+    NpcIsSplashedWithAcid(NpcType),
+    NpcIsBurned(NpcType),
+    PlayerGets(ItemType, bool),
+    PlayerGetsGold(u32),
+    AutoPickup(bool),
     PlayerInventoryIsFull,
+    TooHeavy(ItemType, bool),
+    PlayerInventoryExpands,
+    PlayerInventoryCannotExpand,
     NoItemUnderPlayer,
     NoItemInInventorySlot,
     PlayerHeals,
-    PlayerDrops(ItemType),
+    PlayerFeelsStronger,
+    PlayerZapsNpc(NpcType),
+    NoTargetInRange,
+    PlayerTeleports,
+    PlayerSummons(NpcType),
+    CannotSummonThere,
+    CannotSeeTarget,
+    TeleportFails,
+    NpcTeleports(NpcType),
+    NothingHappens,
+    NpcHeals(NpcType),
+    PotionShatters,
+    CannotThrowItem(ItemType, bool),
+    PlayerDrops(ItemType, bool),
     NoSpaceToDropItem,
     PlayerLaunchesProjectile(ProjectileType),
+    PlayerThrows(ItemType),
+    ThrownItemHitsNpc(ItemType, NpcType),
     NpcDies(NpcType),
+    CorpseCrumbles(NpcType),
     NpcBecomesConfused(NpcType),
+    NpcPolymorphs { from: NpcType, to: NpcType },
     NpcIsNoLongerConfused(NpcType),
+    NpcIsBlinded(NpcType),
+    NpcIsNoLongerBlind(NpcType),
+    NpcIsRooted(NpcType),
+    NpcIsNoLongerRooted(NpcType),
+    NpcFlees(NpcType),
+    NpcWakesUp(NpcType),
     PlayerDodges(NpcType),
     NpcDodges(NpcType),
+    NpcAttacksNpc(NpcType, NpcType),
+    NpcKillsNpc(NpcType, NpcType),
+    NpcDodgesNpc(NpcType, NpcType),
     PlayerEquips(ItemType),
+    PlayerUnequips(ItemType),
+    PlayerHasted,
+    PlayerIsNoLongerHasted,
+    PlayerDescends(u32),
+    PlayerOpensDoor,
+    PlayerUnlocksDoor(KeyColour),
+    DoorIsLocked(KeyColour),
+    CannotUseItem(ItemType),
+    TrapTriggers,
+    PlayerFindsTraps,
+    PlayerFindsPassage,
+    PlayerTalksTo(NpcType),
+    CannotSweep,
+    AimWouldHarmPlayer,
+    PlayerMapsLevel,
+    Earthquake,
+    PlayerRemovesCurse,
+    ItemIsCursed(ItemType),
+    PlayerMapsRoom,
+    PlayerDetectsLife,
+    PlayerDigsWall,
+    NpcDigsWall(NpcType),
+    CannotDigThere,
+    ItemBreaks(ItemType),
+    PlayerStopsResting(RestOutcome),
+    WandIsEmpty,
+    PlayerIdentifies(ItemType),
+    PlayerLowHealth,
+    // Some entities couldn't be placed while populating the level
+    LevelSpawnErrors(usize),
+}
+
+// How important a message is to the player, which determines how it's displayed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogSeverity {
+    Info,
+    Combat,
+    Warning,
+    Critical,
+}
+
+impl LogMessage {
+    pub fn severity(self) -> LogSeverity {
+        use LogMessage::*;
+        match self {
+            PlayerDies | PlayerDiesOfPoison | PlayerStarvesToDeath | NpcKillsPlayer(_)
+            | PlayerLowHealth => LogSeverity::Critical,
+            PlayerStarving
+            | PlayerTakesPoisonDamage
+            | PlayerIsBurned
+            | PlayerIsSplashedWithAcid
+            | TrapTriggers
+            | ItemIsCursed(_)
+            | LevelSpawnErrors(_) => LogSeverity::Warning,
+            PlayerAttacksNpc(_)
+            | PlayerCriticalHit(_)
+            | PlayerDrainsLife
+            | NpcAttacksPlayer(_)
+            | PlayerKillsNpc(_)
+            | PlayerIsHitByProjectile(_)
+            | ThrownItemHitsNpc(..)
+            | PlayerZapsNpc(_)
+            | NpcDies(_)
+            | PlayerDodges(_)
+            | NpcDodges(_)
+            | NpcAttacksNpc(..)
+            | NpcKillsNpc(..)
+            | NpcDodgesNpc(..) => LogSeverity::Combat,
+            _ => LogSeverity::Info,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
 pub enum ExamineCell {
-    Npc(NpcType),
+    Npc(NpcType, HitPoints),
     NpcCorpse(NpcType),
-    Item(ItemType),
+    Item(ItemType, bool),
     Player,
+    Stairs,
+    Wall,
+    Door,
+    Floor,
+}
+
+// What would happen if the player moved into a cell
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MovePreview {
+    Enter,
+    Attack,
+    Blocked,
+}
+
+// The reason the player stopped resting
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum RestOutcome {
+    FullHitPoints,
+    HostileInView,
+    MaxTurnsElapsed,
+    // Something else needs the player's attention, such as a projectile in flight
+    Interrupted,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -56,8 +200,24 @@ pub enum LevelUp {
     Health,
 }
 
+// The world is saved as part of the game state in the form produced by `World::save`
+mod world_save {
+    use crate::world::World;
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(world: &World, serializer: S) -> Result<S::Ok, S::Error> {
+        world.save().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<World, D::Error> {
+        let bytes = Vec::<u8>::deserialize(deserializer)?;
+        World::load(&bytes).map_err(D::Error::custom)
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct GameState {
+    #[serde(with = "world_save")]
     world: World,
     player_entity: Entity,
     shadowcast_context: shadowcast::Context<u8>,
@@ -68,6 +228,13 @@ pub struct GameState {
     rng: Isaac64Rng,
     screen_size: Size,
     dungeon_level: u32,
+    // The seed the current level was generated from. Each level's seed is drawn from `rng`.
+    level_seed: u64,
+    player_took_hasted_action: bool,
+    // When set, potions and gold are picked up by walking over them
+    auto_pickup: bool,
+    // Coordinates of characters revealed by a detect life scroll, shown until the next turn
+    detected_life: Vec<Coord>,
 }
 
 impl GameState {
@@ -80,10 +247,21 @@ impl GameState {
         let mut world = World::new(screen_size);
         let mut rng = Isaac64Rng::seed_from_u64(rng_seed);
         let dungeon_level = 1;
-        let Populate {
-            player_entity,
-            ai_state,
-        } = world.populate(dungeon_level, &mut rng);
+        let (
+            Populate {
+                player_entity,
+                ai_state,
+                spawn_errors,
+            },
+            level_seed,
+        ) = world.populate_seeded(
+            dungeon_level,
+            &PlayerConfig::default(),
+            &MonsterSpawnTable::default(),
+            &GenerationConfig::default(),
+            DifficultyScale::for_level(dungeon_level),
+            rng.gen(),
+        );
         let shadowcast_context = shadowcast::Context::default();
         let visibility_grid = VisibilityGrid::new(screen_size);
         let behaviour_context = BehaviourContext::new(screen_size);
@@ -98,10 +276,23 @@ impl GameState {
             rng,
             screen_size,
             dungeon_level,
+            level_seed,
+            player_took_hasted_action: false,
+            auto_pickup: false,
+            detected_life: Vec::new(),
         };
+        game_state.report_spawn_errors(&spawn_errors);
         game_state.update_visibility(initial_visibility_algorithm);
         game_state
     }
+    // A level with spawn errors is still playable, so the player is told something went wrong
+    // rather than the game stopping
+    fn report_spawn_errors(&mut self, spawn_errors: &[SpawnError]) {
+        if !spawn_errors.is_empty() {
+            self.message_log
+                .push(LogMessage::LevelSpawnErrors(spawn_errors.len()));
+        }
+    }
     pub fn player_level_up_and_descend(&mut self, level_up: LevelUp) {
         assert!(self.is_player_on_stairs());
         self.world.level_up_character(self.player_entity, level_up);
@@ -109,13 +300,30 @@ impl GameState {
         self.world.clear();
         self.visibility_grid.clear();
         self.dungeon_level += 1;
-        let Populate {
-            player_entity,
-            ai_state,
-        } = self.world.populate(self.dungeon_level, &mut self.rng);
+        let (
+            Populate {
+                player_entity,
+                ai_state,
+                spawn_errors,
+            },
+            level_seed,
+        ) = self.world.populate_seeded(
+            self.dungeon_level,
+            // The player spawned here is immediately replaced by the player from the previous
+            // level, so there's no need for any starting items.
+            &PlayerConfig::default(),
+            &MonsterSpawnTable::default(),
+            &GenerationConfig::default(),
+            DifficultyScale::for_level(self.dungeon_level),
+            self.rng.gen(),
+        );
+        self.level_seed = level_seed;
         self.world.replace_character(player_entity, player_data);
         self.player_entity = player_entity;
         self.ai_state = ai_state;
+        self.message_log
+            .push(LogMessage::PlayerDescends(self.dungeon_level));
+        self.report_spawn_errors(&spawn_errors);
     }
     pub fn is_player_on_stairs(&self) -> bool {
         self.world.coord_contains_stairs(self.player_coord())
@@ -124,20 +332,90 @@ impl GameState {
         if self.has_animations() {
             return;
         }
+        // Waiting searches the cells around the player for hidden traps and secret passages
+        if self.world.detect_traps_near(self.player_coord(), 1) > 0 {
+            self.message_log.push(LogMessage::PlayerFindsTraps);
+        }
+        if self.world.search(self.player_coord(), &mut self.rng) > 0 {
+            self.message_log.push(LogMessage::PlayerFindsPassage);
+        }
         self.ai_turn();
     }
-    pub fn maybe_move_player(&mut self, direction: CardinalDirection) {
+    // Waits a turn at a time until resting is interrupted (see `World::rest`)
+    pub fn player_rest(&mut self, max_turns: u32) -> RestOutcome {
+        // Each turn of rest is a regular wait, which needs the whole game state rather than just
+        // the world. The world is moved out of the game state while resting, and swapped back in
+        // for the duration of each turn.
+        let player_entity = self.player_entity;
+        let mut world = mem::replace(&mut self.world, World::new(Size::new(0, 0)));
+        let outcome = world.rest(player_entity, max_turns, |world| {
+            mem::swap(world, &mut self.world);
+            self.wait_player();
+            mem::swap(world, &mut self.world);
+        });
+        self.world = world;
+        self.message_log
+            .push(LogMessage::PlayerStopsResting(outcome));
+        outcome
+    }
+    pub fn maybe_move_player(&mut self, direction: Direction) {
         if self.has_animations() {
             return;
         }
-        self.world.maybe_move_character(
+        let move_result = self.world.maybe_move_character(
             self.player_entity,
             direction,
             &mut self.message_log,
             &mut self.rng,
         );
+        match move_result {
+            MoveResult::Attacked { .. } => {
+                self.alert_npcs_near(self.player_coord() + direction.coord());
+            }
+            MoveResult::Moved if self.auto_pickup => {
+                // Picking up is part of the move, so doesn't take an extra turn
+                let _ = self.world.auto_pickup(
+                    self.player_entity,
+                    ItemType::is_potion,
+                    &mut self.message_log,
+                );
+            }
+            _ => (),
+        }
         self.ai_turn();
     }
+    pub fn toggle_auto_pickup(&mut self) {
+        self.auto_pickup = !self.auto_pickup;
+        self.message_log
+            .push(LogMessage::AutoPickup(self.auto_pickup));
+    }
+    pub fn maybe_player_sweep_attack(&mut self) {
+        if self.has_animations() {
+            return;
+        }
+        if let Ok(num_attacked) =
+            self.world
+                .sweep_attack(self.player_entity, &mut self.message_log, &mut self.rng)
+        {
+            if num_attacked > 0 {
+                self.alert_npcs_near(self.player_coord());
+            }
+            self.ai_turn();
+        }
+    }
+    // The sound of combat draws nearby npcs towards the player
+    fn alert_npcs_near(&mut self, origin: Coord) {
+        const ALERT_RADIUS: u32 = 8;
+        for entity in self.world.propagate_alert(origin, ALERT_RADIUS) {
+            if let Some(agent) = self.ai_state.get_mut(entity) {
+                agent.alert();
+            }
+        }
+    }
+    // Sorting the inventory doesn't take a turn
+    pub fn sort_player_inventory(&mut self) {
+        self.world.sort_player_inventory(self.player_entity);
+    }
     pub fn maybe_player_get_item(&mut self) {
         if self.has_animations() {
             return;
@@ -154,15 +432,29 @@ impl GameState {
         if self.has_animations() {
             return Err(());
         }
-        let result =
-            self.world
-                .maybe_use_item(self.player_entity, inventory_index, &mut self.message_log);
+        let item_type = self
+            .world
+            .inventory_item_type(self.player_entity, inventory_index);
+        let result = self.world.maybe_use_item(
+            self.player_entity,
+            inventory_index,
+            &mut self.message_log,
+            &mut self.rng,
+        );
+        if result.is_ok() && item_type == Some(ItemType::MappingScroll) {
+            self.visibility_grid
+                .mark_discovered(self.world.all_floor_coords());
+        }
         if let Ok(usage) = result {
             match usage {
                 ItemUsage::Immediate => self.ai_turn(),
-                ItemUsage::Aim => (),
+                ItemUsage::Aim | ItemUsage::ChooseItem => (),
             }
         }
+        // Characters are detected after they've taken their turn so their positions are current
+        if result.is_ok() && item_type == Some(ItemType::DetectLifeScroll) {
+            self.detected_life = self.world.living_character_coords();
+        }
         result
     }
     pub fn maybe_player_use_item_aim(
@@ -170,12 +462,75 @@ impl GameState {
         inventory_index: usize,
         target: Coord,
     ) -> Result<(), ()> {
-        self.world.maybe_use_item_aim(
+        if self.has_animations() {
+            return Err(());
+        }
+        let item_type = self
+            .world
+            .inventory_item_type(self.player_entity, inventory_index);
+        let summoned = self.world.maybe_use_item_aim(
             self.player_entity,
             inventory_index,
             target,
             &mut self.message_log,
-        )
+            &mut self.rng,
+        )?;
+        if let Some(entity) = summoned {
+            // Unlike a launched projectile, a summoning has nothing left to animate, so the npcs
+            // take their turn straight away
+            self.ai_state.insert(entity, Agent::new());
+            self.ai_turn();
+        }
+        if item_type == Some(ItemType::ClairvoyanceScroll) {
+            // Reveal the room around the target along with the walls surrounding it
+            let room = self.world.room_coords_containing(target, true);
+            let walls = room.iter().flat_map(|&coord| {
+                Direction::all().map(move |direction| coord + direction.coord())
+            });
+            self.visibility_grid
+                .mark_discovered(room.iter().cloned().chain(walls));
+        }
+        Ok(())
+    }
+    pub fn maybe_player_use_item_choose(
+        &mut self,
+        inventory_index: usize,
+        target_index: usize,
+    ) -> Result<(), ()> {
+        let result = self.world.maybe_use_item_choose(
+            self.player_entity,
+            inventory_index,
+            target_index,
+            &mut self.message_log,
+        );
+        if result.is_ok() {
+            self.ai_turn();
+        }
+        result
+    }
+    pub fn check_player_can_throw_item(&mut self, inventory_index: usize) -> Result<(), ()> {
+        self.world
+            .check_can_throw_item(self.player_entity, inventory_index, &mut self.message_log)
+    }
+    pub fn maybe_player_throw_item(
+        &mut self,
+        inventory_index: usize,
+        target: Coord,
+    ) -> Result<(), ()> {
+        if self.has_animations() {
+            return Err(());
+        }
+        let result = self.world.maybe_throw_item(
+            self.player_entity,
+            inventory_index,
+            target,
+            &mut self.message_log,
+            &mut self.rng,
+        );
+        if result.is_ok() {
+            self.ai_turn();
+        }
+        result
     }
     pub fn maybe_player_drop_item(&mut self, inventory_index: usize) -> Result<(), ()> {
         let result =
@@ -186,25 +541,104 @@ impl GameState {
         }
         result
     }
+    pub fn maybe_player_swap_item(&mut self, inventory_index: usize) -> Result<(), ()> {
+        if self.has_animations() {
+            return Err(());
+        }
+        let result =
+            self.world
+                .maybe_swap_item(self.player_entity, inventory_index, &mut self.message_log);
+        if result.is_ok() {
+            self.ai_turn();
+        }
+        result
+    }
+    pub fn maybe_player_unequip_item(&mut self, slot: EquipSlot) -> Result<(), ()> {
+        if self.has_animations() {
+            return Err(());
+        }
+        let result = self
+            .world
+            .maybe_unequip_item(self.player_entity, slot, &mut self.message_log);
+        if result.is_ok() {
+            self.ai_turn();
+        }
+        result
+    }
     pub fn tick_animations(&mut self) {
-        self.world.move_projectiles(&mut self.message_log)
+        self.world
+            .move_projectiles(&mut self.message_log, &mut self.rng)
     }
     fn has_animations(&self) -> bool {
         self.world.has_projectiles()
     }
     pub fn entities_to_render<'a>(&'a self) -> impl 'a + Iterator<Item = EntityToRender> {
-        let tile_component = &self.world.components.tile;
-        let spatial_table = &self.world.spatial_table;
         let visibility_grid = &self.visibility_grid;
-        tile_component.iter().filter_map(move |(entity, &tile)| {
-            let &location = spatial_table.location_of(entity)?;
-            let visibility = visibility_grid.cell_visibility(location.coord);
-            Some(EntityToRender {
+        self.world
+            .entities_with_tiles()
+            .map(move |(_, coord, tile, layer)| EntityToRender {
                 tile,
-                location,
-                visibility,
+                location: Location {
+                    coord,
+                    layer: Some(layer),
+                },
+                visibility: visibility_grid.cell_visibility(coord),
+            })
+    }
+    // The positions of characters detected by a detect life scroll which are out of view
+    pub fn detected_life_coords(&self) -> Vec<Coord> {
+        self.detected_life
+            .iter()
+            .cloned()
+            .filter(|&coord| {
+                !matches!(
+                    self.visibility_grid.cell_visibility(coord),
+                    CellVisibility::Currently
+                )
+            })
+            .collect()
+    }
+    // The position of the next hostile character the player can currently see after `from`, used
+    // to cycle a cursor between visible monsters
+    pub fn next_visible_hostile_coord(&self, from: Coord) -> Option<Coord> {
+        let visible_coords = self
+            .screen_size
+            .coord_iter_row_major()
+            .filter(|&coord| {
+                matches!(
+                    self.visibility_grid.cell_visibility(coord),
+                    CellVisibility::Currently
+                )
+            })
+            .collect::<Vec<_>>();
+        self.world.next_visible_character(from, &visible_coords)
+    }
+    // Recent damage dealt in cells the player can currently see
+    pub fn visible_damage_events<'a>(&'a self) -> impl 'a + Iterator<Item = DamageEvent> {
+        self.world
+            .damage_events()
+            .iter()
+            .cloned()
+            .filter(move |event| {
+                matches!(
+                    self.visibility_grid.cell_visibility(event.coord),
+                    CellVisibility::Currently
+                )
             })
-        })
+    }
+    // The positions and types of in-flight projectiles which the player can currently see
+    pub fn visible_projectiles(&self) -> Vec<(Coord, ProjectileType)> {
+        self.world
+            .projectiles()
+            .into_iter()
+            .filter(|&(_, coord, _)| {
+                matches!(
+                    self.visibility_grid.cell_visibility(coord),
+                    CellVisibility::Currently
+                )
+            })
+            .map(|(_, coord, projectile_type)| (coord, projectile_type))
+            .collect()
     }
     pub fn update_visibility(&mut self, visibility_algorithm: VisibilityAlgorithm) {
         let player_coord = self
@@ -214,12 +648,35 @@ impl GameState {
             .unwrap();
         self.visibility_grid.update(
             player_coord,
+            self.world.entity_is_blind(self.player_entity),
             &self.world,
             &mut self.shadowcast_context,
             visibility_algorithm,
         );
     }
     fn ai_turn(&mut self) {
+        const PLAYER_REGENERATION_INTERVAL: u64 = 10;
+        self.detected_life.clear();
+        // A hasted player gets two actions for each turn taken by everything else
+        if self.world.entity_is_hasted(self.player_entity) && !self.player_took_hasted_action {
+            self.player_took_hasted_action = true;
+            return;
+        }
+        self.player_took_hasted_action = false;
+        self.world.tick_haste(&mut self.message_log);
+        self.world.tick_blindness(&mut self.message_log);
+        self.world.tick_rooted(&mut self.message_log);
+        self.world.advance_turn();
+        self.world
+            .regenerate(self.player_entity, PLAYER_REGENERATION_INTERVAL);
+        self.world.npc_regenerate();
+        self.world
+            .apply_status_effects(&mut self.message_log, &mut self.rng);
+        self.world
+            .apply_hunger(&mut self.message_log, &mut self.rng);
+        self.world
+            .tick_environmental_hazards(&mut self.message_log, &mut self.rng);
+        self.world.decay_corpses(&mut self.message_log);
         self.behaviour_context
             .update(self.player_entity, &self.world);
         let dead_entities = self
@@ -231,22 +688,54 @@ impl GameState {
             self.ai_state.remove(dead_entity);
         }
         for (entity, agent) in self.ai_state.iter_mut() {
-            let npc_action = agent.act(
-                entity,
-                self.player_entity,
-                &self.world,
-                &mut self.behaviour_context,
-            );
-            match npc_action {
-                NpcAction::Wait => (),
-                NpcAction::Move(direction) => self.world.maybe_move_character(
+            // Fast npcs act several times for each turn taken by the player
+            for _ in 0..self.world.npc_actions_per_turn(entity) {
+                if !self.world.is_living_character(entity)
+                    || !self.world.is_living_character(self.player_entity)
+                {
+                    break;
+                }
+                let was_asleep = agent.is_asleep();
+                let was_fleeing = agent.is_fleeing();
+                let npc_action = agent.act(
                     entity,
-                    direction,
-                    &mut self.message_log,
+                    self.player_entity,
+                    &self.world,
+                    &mut self.behaviour_context,
                     &mut self.rng,
-                ),
+                );
+                if was_asleep && !agent.is_asleep() {
+                    if let Some(npc_type) = self.world.npc_type(entity) {
+                        self.message_log.push(LogMessage::NpcWakesUp(npc_type));
+                    }
+                }
+                if agent.is_fleeing() && !was_fleeing {
+                    if let Some(npc_type) = self.world.npc_type(entity) {
+                        self.message_log.push(LogMessage::NpcFlees(npc_type));
+                    }
+                }
+                match npc_action {
+                    NpcAction::Wait => (),
+                    NpcAction::Move(direction) => {
+                        self.world.maybe_move_character(
+                            entity,
+                            direction.direction(),
+                            &mut self.message_log,
+                            &mut self.rng,
+                        );
+                    }
+                    NpcAction::FireProjectile(target) => {
+                        self.world
+                            .npc_fire_projectile(entity, target, &mut self.message_log)
+                    }
+                    NpcAction::Dig(coord) => {
+                        let _ = self.world.npc_dig(entity, coord, &mut self.message_log);
+                    }
+                }
             }
         }
+        self.world
+            .check_player_health_warnings(self.player_entity, &mut self.message_log);
     }
     pub fn is_player_alive(&self) -> bool {
         self.world.is_living_character(self.player_entity)
@@ -272,18 +761,26 @@ impl GameState {
     pub fn item_type(&self, entity: Entity) -> Option<ItemType> {
         self.world.item_type(entity)
     }
+    pub fn item_charges(&self, entity: Entity) -> Option<u32> {
+        self.world.item_charges(entity)
+    }
+    pub fn item_is_identified(&self, entity: Entity) -> bool {
+        self.world.item_is_identified(self.player_entity, entity)
+    }
     pub fn size(&self) -> Size {
         self.world.size()
     }
     pub fn examine_cell(&self, coord: Coord) -> Option<ExamineCell> {
         match self.visibility_grid.cell_visibility(coord) {
-            CellVisibility::Currently => self.world.examine_cell(coord),
-            _ => None,
+            CellVisibility::Currently => self.world.examine_cell(self.player_entity, coord),
+            // Only terrain is remembered, so characters and items out of view aren't revealed
+            CellVisibility::Previously => self.world.examine_terrain(coord),
+            CellVisibility::Never => None,
         }
     }
-    pub fn player_strength(&self) -> i32 {
+    pub fn player_strength(&self) -> u32 {
         self.world
-            .strength(self.player_entity)
+            .player_strength(self.player_entity)
             .expect("player missing strength")
     }
     pub fn player_dexterity(&self) -> i32 {
@@ -296,10 +793,302 @@ impl GameState {
             .intelligence(self.player_entity)
             .expect("player missing intelligence")
     }
+    // Previews the outcome of the player moving into `coord`, if it's adjacent to the player
+    pub fn player_move_preview(&self, coord: Coord) -> Option<MovePreview> {
+        let delta = coord - self.player_coord();
+        if delta.x.abs() > 1 || delta.y.abs() > 1 || delta == Coord::new(0, 0) {
+            return None;
+        }
+        let preview = if self
+            .world
+            .player_would_attack_at(self.player_entity, coord)
+            .is_some()
+        {
+            MovePreview::Attack
+        } else if self.world.can_player_enter(coord) {
+            MovePreview::Enter
+        } else {
+            MovePreview::Blocked
+        };
+        Some(preview)
+    }
+    // Warns the player if aiming the item in the given inventory slot at `target` would hurt them
+    pub fn warn_if_aim_would_harm_player(&mut self, inventory_index: usize, target: Coord) -> bool {
+        let would_harm = self
+            .world
+            .inventory_item_type(self.player_entity, inventory_index)
+            .map(|item_type| {
+                self.world
+                    .aim_would_harm_self(self.player_entity, item_type, target)
+            })
+            .unwrap_or(false);
+        if would_harm {
+            self.message_log.push(LogMessage::AimWouldHarmPlayer);
+        }
+        would_harm
+    }
+    pub fn aim_line(&self, target: Coord) -> Vec<Coord> {
+        self.world.aim_line(self.player_coord(), target)
+    }
+    pub fn player_gold(&self) -> u32 {
+        self.world.player_gold(self.player_entity)
+    }
+    pub fn player_carry_weight(&self) -> (u32, u32) {
+        self.world.player_carry_weight(self.player_entity)
+    }
+    pub fn player_status_effects(&self) -> Vec<StatusEffect> {
+        self.world.active_status_effects(self.player_entity)
+    }
+    pub fn player_nutrition(&self) -> u32 {
+        self.world
+            .player_nutrition(self.player_entity)
+            .expect("player missing nutrition")
+    }
     pub fn dungeon_level(&self) -> u32 {
         self.dungeon_level
     }
-    pub fn player_equipped_inventory_indices(&self) -> EquippedInventoryIndices {
-        self.world.equipped_inventory_indices(self.player_entity)
+    // The seed the current level was generated from, which can be used to generate it again
+    pub fn level_seed(&self) -> u64 {
+        self.level_seed
+    }
+    pub fn player_equipped_item_type(&self, slot: EquipSlot) -> Option<ItemType> {
+        self.world.equipped_in_slot(self.player_entity, slot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_game_state(rng_seed: u64) -> GameState {
+        GameState::new(Size::new(40, 30), rng_seed, VisibilityAlgorithm::Shadowcast)
+    }
+
+    #[test]
+    fn examine_does_not_reveal_npcs_out_of_view() {
+        let game_state = new_game_state(0);
+        let hidden_character_coords = game_state
+            .world
+            .living_character_coords()
+            .into_iter()
+            .filter(|&coord| {
+                !matches!(
+                    game_state.visibility_grid.cell_visibility(coord),
+                    CellVisibility::Currently
+                )
+            })
+            .collect::<Vec<_>>();
+        assert!(!hidden_character_coords.is_empty());
+        for coord in hidden_character_coords {
+            assert!(matches!(
+                game_state
+                    .world
+                    .examine_cell(game_state.player_entity, coord),
+                Some(ExamineCell::Npc(..))
+            ));
+            assert!(!matches!(
+                game_state.examine_cell(coord),
+                Some(ExamineCell::Npc(..))
+            ));
+        }
+    }
+
+    // Moves the player directly to `coord`, without taking a turn
+    fn place_player(game_state: &mut GameState, coord: Coord) {
+        game_state
+            .world
+            .spatial_table
+            .update_coord(game_state.player_entity, coord)
+            .unwrap();
+    }
+
+    fn npc_coords(game_state: &GameState) -> Vec<Coord> {
+        let mut coords = game_state
+            .world
+            .components
+            .npc_type
+            .entities()
+            .filter_map(|entity| game_state.world.entity_coord(entity))
+            .collect::<Vec<_>>();
+        coords.sort_by_key(|coord| (coord.y, coord.x));
+        coords
+    }
+
+    #[test]
+    fn descending_keeps_player_but_replaces_npcs() {
+        let mut game_state = new_game_state(0);
+        // Pick up an item from somewhere on the level
+        let item = game_state
+            .world
+            .components
+            .item
+            .entities()
+            .find(|&entity| game_state.world.entity_coord(entity).is_some())
+            .expect("no items on level");
+        let item_coord = game_state.world.entity_coord(item).unwrap();
+        let item_type = game_state.item_type(item).unwrap();
+        place_player(&mut game_state, item_coord);
+        game_state
+            .world
+            .maybe_get_item(game_state.player_entity, &mut game_state.message_log)
+            .unwrap();
+        game_state
+            .world
+            .components
+            .hit_points
+            .get_mut(game_state.player_entity)
+            .unwrap()
+            .current -= 3;
+        let stairs_coord = game_state
+            .size()
+            .coord_iter_row_major()
+            .find(|&coord| game_state.world.coord_contains_stairs(coord))
+            .expect("no stairs on level");
+        place_player(&mut game_state, stairs_coord);
+        let hit_points = game_state.player_hit_points();
+        let npc_coords_before = npc_coords(&game_state);
+        game_state.player_level_up_and_descend(LevelUp::Strength);
+        assert_eq!(game_state.dungeon_level(), 2);
+        assert_eq!(game_state.player_hit_points().current, hit_points.current);
+        assert_eq!(game_state.player_hit_points().max, hit_points.max);
+        let inventory = game_state.player_inventory();
+        assert_eq!(
+            game_state.item_type(inventory.get(0).unwrap()),
+            Some(item_type)
+        );
+        assert_ne!(npc_coords(&game_state), npc_coords_before);
+        for (entity, _) in game_state.ai_state.iter() {
+            assert!(game_state.world.npc_type(entity).is_some());
+        }
+    }
+
+    #[test]
+    fn summoned_ally_appears_at_target_with_an_agent() {
+        use direction::CardinalDirection;
+        let mut game_state = new_game_state(0);
+        // Clear out the level's npcs so nothing draws the ally away from where it was summoned
+        let npcs = game_state
+            .world
+            .components
+            .npc_type
+            .entities()
+            .collect::<Vec<_>>();
+        for npc in npcs {
+            game_state.world.remove_entity(npc);
+            game_state.ai_state.remove(npc);
+        }
+        let item = game_state
+            .world
+            .components
+            .item
+            .entities()
+            .find(|&entity| game_state.world.entity_coord(entity).is_some())
+            .expect("no items on level");
+        game_state
+            .world
+            .components
+            .item
+            .insert(item, ItemType::SummonScroll);
+        let player_coord = game_state.world.entity_coord(item).unwrap();
+        place_player(&mut game_state, player_coord);
+        game_state
+            .world
+            .maybe_get_item(game_state.player_entity, &mut game_state.message_log)
+            .unwrap();
+        let target = CardinalDirection::all()
+            .map(|direction| player_coord + direction.coord())
+            .find(|&coord| {
+                game_state
+                    .world
+                    .spatial_table
+                    .layers_at(coord)
+                    .map(|layers| {
+                        layers.floor.is_some()
+                            && layers.feature.is_none()
+                            && layers.character.is_none()
+                    })
+                    .unwrap_or(false)
+            })
+            .expect("no room to summon beside the player");
+        let nutrition = game_state
+            .world
+            .player_nutrition(game_state.player_entity)
+            .unwrap();
+        game_state.maybe_player_use_item_aim(0, target).unwrap();
+        let ally = game_state
+            .world
+            .spatial_table
+            .layers_at_checked(target)
+            .character
+            .expect("no character at summon target");
+        assert_eq!(
+            game_state.world.faction(ally),
+            Some(crate::world::Faction::Player)
+        );
+        assert!(game_state.ai_state.get(ally).is_some());
+        assert!(game_state.player_inventory().get(0).is_err());
+        // Summoning takes a turn
+        assert_eq!(
+            game_state.world.player_nutrition(game_state.player_entity),
+            Some(nutrition - 1)
+        );
+    }
+
+    #[test]
+    fn log_messages_have_expected_severities() {
+        assert_eq!(
+            LogMessage::PlayerKillsNpc(NpcType::Orc).severity(),
+            LogSeverity::Combat
+        );
+        assert_eq!(
+            LogMessage::PlayerLowHealth.severity(),
+            LogSeverity::Critical
+        );
+        assert_eq!(
+            LogMessage::ItemIsCursed(ItemType::Sword).severity(),
+            LogSeverity::Warning
+        );
+        assert_eq!(LogMessage::PlayerOpensDoor.severity(), LogSeverity::Info);
+    }
+
+    #[test]
+    fn saved_game_state_restores_world() {
+        let game_state = new_game_state(0);
+        let bytes = serde_json::to_vec(&game_state).unwrap();
+        let loaded: GameState = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(loaded.player_coord(), game_state.player_coord());
+        assert_eq!(
+            loaded.player_hit_points().current,
+            game_state.player_hit_points().current
+        );
+        assert_eq!(
+            loaded.world.living_character_coords(),
+            game_state.world.living_character_coords()
+        );
+    }
+
+    #[test]
+    fn spawn_errors_are_reported_in_message_log() {
+        let mut game_state = new_game_state(0);
+        game_state.message_log.clear();
+        game_state.report_spawn_errors(&[]);
+        assert!(game_state.message_log.is_empty());
+        let spawn_errors = [
+            SpawnError::OutOfBounds {
+                coord: Coord::new(-1, 0),
+            },
+            SpawnError::OutOfBounds {
+                coord: Coord::new(0, -1),
+            },
+        ];
+        game_state.report_spawn_errors(&spawn_errors);
+        assert!(matches!(
+            game_state.message_log.as_slice(),
+            [LogMessage::LevelSpawnErrors(2)]
+        ));
+        assert_eq!(
+            LogMessage::LevelSpawnErrors(2).severity(),
+            LogSeverity::Warning
+        );
     }
 }
@@ -1,17 +1,97 @@
-use crate::world::{ItemType, NpcType};
+use crate::world::{ItemType, KeyColour, NpcType};
+use direction::CardinalDirection;
 use grid_2d::{Coord, Grid, Size};
 use rand::{seq::IteratorRandom, seq::SliceRandom, Rng};
+use std::collections::VecDeque;
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum TerrainTile {
     Player,
     Floor,
     Wall,
+    SecretWall,
     Npc(NpcType),
+    ArmouredNpc(NpcType),
     Item(ItemType),
+    CursedItem(ItemType),
     Stairs,
+    Door,
+    LockedDoor(KeyColour),
+    Trap,
+    Gold(u32),
 }
 
+// The weight with which an npc type is chosen when spawning npcs. Weights increase linearly
+// with the dungeon level.
+#[derive(Clone, Copy, Debug)]
+pub struct MonsterSpawnWeight {
+    pub npc_type: NpcType,
+    pub base: u32,
+    pub per_level: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct MonsterSpawnTable {
+    pub weights: Vec<MonsterSpawnWeight>,
+}
+
+impl MonsterSpawnTable {
+    fn probability_distribution(&self, level: u32) -> Vec<(NpcType, u32)> {
+        self.weights
+            .iter()
+            .map(|weight| (weight.npc_type, weight.base + weight.per_level * level))
+            .collect()
+    }
+}
+
+impl Default for MonsterSpawnTable {
+    fn default() -> Self {
+        use NpcType::*;
+        let weights = vec![
+            MonsterSpawnWeight {
+                npc_type: Orc,
+                base: 20,
+                per_level: 0,
+            },
+            MonsterSpawnWeight {
+                npc_type: Troll,
+                base: 0,
+                per_level: 1,
+            },
+            MonsterSpawnWeight {
+                npc_type: Archer,
+                base: 0,
+                per_level: 1,
+            },
+            MonsterSpawnWeight {
+                npc_type: Digger,
+                base: 0,
+                per_level: 1,
+            },
+        ];
+        Self { weights }
+    }
+}
+
+// Options which control the shape and contents of generated levels
+#[derive(Clone, Copy, Debug)]
+pub struct GenerationConfig {
+    // Percentage chance that one room of the level is a monster house, packed with npcs guarding
+    // extra loot
+    pub monster_house_percent: u32,
+}
+
+impl Default for GenerationConfig {
+    fn default() -> Self {
+        Self {
+            monster_house_percent: 20,
+        }
+    }
+}
+
+// The percentage of randomly placed equipment which is cursed
+const CURSED_ITEM_PERCENT: u32 = 15;
+
 // A rectangular area of the map
 struct Room {
     top_left: Coord,
@@ -62,25 +142,98 @@ impl Room {
         }
     }
 
-    // Place `n` randomly chosen NPCs at random positions within the room
+    // Returns true if and only if `coord` is inside the room and not on its top or left wall
+    fn is_on_floor(&self, coord: Coord) -> bool {
+        let bottom_right = self.top_left + self.size.to_coord().unwrap();
+        coord.x > self.top_left.x
+            && coord.y > self.top_left.y
+            && coord.x < bottom_right.x
+            && coord.y < bottom_right.y
+    }
+
+    // Returns the open cells next to the floor of the room which aren't part of its floor. Every
+    // path into the room passes through one of these cells. Corridors can enter through the
+    // room's own top and left walls, or through the cells just beyond its bottom and right sides.
+    fn entrances<'a>(
+        &'a self,
+        grid: &'a Grid<Option<TerrainTile>>,
+    ) -> impl 'a + Iterator<Item = Coord> {
+        (self.size + Size::new(1, 1))
+            .coord_iter_row_major()
+            .map(move |coord| self.top_left + coord)
+            .filter(move |&coord| {
+                !self.is_on_floor(coord)
+                    && CardinalDirection::all()
+                        .any(|direction| self.is_on_floor(coord + direction.coord()))
+                    && matches!(
+                        grid.get(coord),
+                        Some(Some(TerrainTile::Floor)) | Some(Some(TerrainTile::Door))
+                    )
+            })
+    }
+
+    // Place `n` randomly chosen NPCs at random positions within the room. Each NPC has an
+    // `armoured_percent` chance of wearing armour.
     fn place_npcs<R: Rng>(
         &self,
         n: usize,
         probability_distribution: &[(NpcType, u32)],
+        armoured_percent: u32,
         grid: &mut Grid<Option<TerrainTile>>,
         rng: &mut R,
     ) {
+        if probability_distribution
+            .iter()
+            .all(|&(_, weight)| weight == 0)
+        {
+            return;
+        }
         for coord in self
             .coords()
             .filter(|&coord| grid.get_checked(coord).unwrap() == TerrainTile::Floor)
             .choose_multiple(rng, n)
         {
             let &npc_type = choose_from_probability_distribution(probability_distribution, rng);
-            *grid.get_checked_mut(coord) = Some(TerrainTile::Npc(npc_type));
+            let terrain_tile = if rng.gen_range(0..100) < armoured_percent {
+                TerrainTile::ArmouredNpc(npc_type)
+            } else {
+                TerrainTile::Npc(npc_type)
+            };
+            *grid.get_checked_mut(coord) = Some(terrain_tile);
+        }
+    }
+
+    // Place `n` traps at random positions within the room
+    fn place_traps<R: Rng>(&self, n: usize, grid: &mut Grid<Option<TerrainTile>>, rng: &mut R) {
+        for coord in self
+            .coords()
+            .filter(|&coord| grid.get_checked(coord).unwrap() == TerrainTile::Floor)
+            .choose_multiple(rng, n)
+        {
+            *grid.get_checked_mut(coord) = Some(TerrainTile::Trap);
+        }
+    }
+
+    // Place `n` piles of gold at random positions within the room, each containing an amount of
+    // gold which increases with the dungeon level
+    fn place_gold<R: Rng>(
+        &self,
+        n: usize,
+        level: u32,
+        grid: &mut Grid<Option<TerrainTile>>,
+        rng: &mut R,
+    ) {
+        for coord in self
+            .coords()
+            .filter(|&coord| grid.get_checked(coord).unwrap() == TerrainTile::Floor)
+            .choose_multiple(rng, n)
+        {
+            let amount = rng.gen_range(1..=(level.max(1) * 10));
+            *grid.get_checked_mut(coord) = Some(TerrainTile::Gold(amount));
         }
     }
 
-    // Place `n` items at random positions within the room
+    // Place `n` items at random positions within the room. Some equipment is cursed.
     fn place_items<R: Rng>(
         &self,
         n: usize,
@@ -94,25 +247,212 @@ impl Room {
             .choose_multiple(rng, n)
         {
             let &item = choose_from_probability_distribution(probability_distribution, rng);
-            *grid.get_checked_mut(coord) = Some(TerrainTile::Item(item));
+            let terrain_tile =
+                if item.equip_slot().is_some() && rng.gen_range(0..100) < CURSED_ITEM_PERCENT {
+                    TerrainTile::CursedItem(item)
+                } else {
+                    TerrainTile::Item(item)
+                };
+            *grid.get_checked_mut(coord) = Some(terrain_tile);
         }
     }
 }
 
-// carve out an L-shaped corridor between a pair of coordinates
-fn carve_corridor(start: Coord, end: Coord, grid: &mut Grid<Option<TerrainTile>>) {
+// carve out an L-shaped corridor between a pair of coordinates, returning the coordinates of
+// any walls which were carved through
+fn carve_corridor(start: Coord, end: Coord, grid: &mut Grid<Option<TerrainTile>>) -> Vec<Coord> {
+    let mut carved_walls = Vec::new();
     for i in start.x.min(end.x)..=start.x.max(end.x) {
-        let cell = grid.get_checked_mut(Coord { x: i, ..start });
+        let coord = Coord { x: i, ..start };
+        let cell = grid.get_checked_mut(coord);
+        if *cell == Some(TerrainTile::Wall) {
+            carved_walls.push(coord);
+        }
         if *cell == None || *cell == Some(TerrainTile::Wall) {
             *cell = Some(TerrainTile::Floor);
         }
     }
     for i in start.y.min(end.y)..start.y.max(end.y) {
-        let cell = grid.get_checked_mut(Coord { y: i, ..end });
+        let coord = Coord { y: i, ..end };
+        let cell = grid.get_checked_mut(coord);
+        if *cell == Some(TerrainTile::Wall) {
+            carved_walls.push(coord);
+        }
         if *cell == None || *cell == Some(TerrainTile::Wall) {
             *cell = Some(TerrainTile::Floor);
         }
     }
+    carved_walls
+}
+
+// Returns true if and only if the cell at `coord` is a floor with walls on opposite sides and
+// open space on the other two sides, such that a door placed there would fit snugly
+fn is_doorway(coord: Coord, grid: &Grid<Option<TerrainTile>>) -> bool {
+    let is_wall = |offset: Coord| match grid.get(coord + offset) {
+        None | Some(None) | Some(Some(TerrainTile::Wall)) => true,
+        Some(Some(_)) => false,
+    };
+    let walls_west_east = is_wall(Coord::new(-1, 0)) && is_wall(Coord::new(1, 0));
+    let walls_north_south = is_wall(Coord::new(0, -1)) && is_wall(Coord::new(0, 1));
+    grid.get_checked(coord) == &Some(TerrainTile::Floor) && (walls_west_east != walls_north_south)
+}
+
+// Walls (including secret walls) and locked doors are the only cells which can't be walked through
+fn is_passable(coord: Coord, grid: &Grid<Option<TerrainTile>>) -> bool {
+    match grid.get(coord) {
+        None
+        | Some(None)
+        | Some(Some(TerrainTile::Wall))
+        | Some(Some(TerrainTile::SecretWall))
+        | Some(Some(TerrainTile::LockedDoor(_))) => false,
+        Some(Some(_)) => true,
+    }
+}
+
+// Returns a grid marking every cell which can be reached from `start`. Locked doors are treated as
+// open, since the key to each locked door is placed somewhere on the level.
+fn connected_cells(start: Coord, grid: &Grid<Option<TerrainTile>>) -> Grid<bool> {
+    let is_open = |coord: Coord| {
+        is_passable(coord, grid)
+            || matches!(grid.get(coord), Some(Some(TerrainTile::LockedDoor(_))))
+    };
+    let mut seen = Grid::new_copy(grid.size(), false);
+    let mut queue = VecDeque::new();
+    *seen.get_checked_mut(start) = true;
+    queue.push_back(start);
+    while let Some(coord) = queue.pop_front() {
+        for direction in CardinalDirection::all() {
+            let neighbour_coord = coord + direction.coord();
+            if is_open(neighbour_coord) {
+                let seen_cell = seen.get_checked_mut(neighbour_coord);
+                if !*seen_cell {
+                    *seen_cell = true;
+                    queue.push_back(neighbour_coord);
+                }
+            }
+        }
+    }
+    seen
+}
+
+// Carves corridors until every open cell can be reached from `start`. Each unreachable cell is
+// joined to the nearest reachable cell, which in turn makes the rest of its region reachable.
+// Corridors are never aimed into `locked_room`, so they don't become a way around its doors.
+fn connect_all(start: Coord, locked_room: Option<&Room>, grid: &mut Grid<Option<TerrainTile>>) {
+    loop {
+        let connected = connected_cells(start, grid);
+        let unreachable = grid.coord_iter().find(|&coord| {
+            let is_open = match grid.get_checked(coord) {
+                None | Some(TerrainTile::Wall) | Some(TerrainTile::SecretWall) => false,
+                Some(_) => true,
+            };
+            is_open && !*connected.get_checked(coord)
+        });
+        let unreachable = match unreachable {
+            Some(coord) => coord,
+            None => break,
+        };
+        let nearest = connected
+            .enumerate()
+            .filter(|&(_, &is_connected)| is_connected)
+            .map(|(coord, _)| coord)
+            .filter(|&coord| {
+                let is_locked = match locked_room {
+                    Some(room) => {
+                        room.is_on_floor(coord)
+                            || matches!(grid.get_checked(coord), Some(TerrainTile::LockedDoor(_)))
+                    }
+                    None => false,
+                };
+                !is_locked
+            })
+            .min_by_key(|&coord| {
+                let delta = coord - unreachable;
+                (delta.x.abs() + delta.y.abs(), coord.y, coord.x)
+            })
+            .expect("start is always connected");
+        carve_corridor(unreachable, nearest, grid);
+    }
+}
+
+// Returns the number of steps it takes to walk from `start` to `end`, or `None` if `end` can't be
+// reached
+fn walking_distance(start: Coord, end: Coord, grid: &Grid<Option<TerrainTile>>) -> Option<usize> {
+    let mut seen = Grid::new_copy(grid.size(), false);
+    let mut queue = VecDeque::new();
+    *seen.get_checked_mut(start) = true;
+    queue.push_back((start, 0));
+    while let Some((coord, distance)) = queue.pop_front() {
+        if coord == end {
+            return Some(distance);
+        }
+        for direction in CardinalDirection::all() {
+            let neighbour_coord = coord + direction.coord();
+            if is_passable(neighbour_coord, grid) {
+                let seen_cell = seen.get_checked_mut(neighbour_coord);
+                if !*seen_cell {
+                    *seen_cell = true;
+                    queue.push_back((neighbour_coord, distance + 1));
+                }
+            }
+        }
+    }
+    None
+}
+
+// Returns the pair of open cells on opposite sides of the wall at `coord`, if the wall is a single
+// cell thick and has walls on its other two sides, such that knocking it out would join the two
+// open cells with a short passage
+fn wall_between(coord: Coord, grid: &Grid<Option<TerrainTile>>) -> Option<(Coord, Coord)> {
+    match grid.get(coord) {
+        Some(None) | Some(Some(TerrainTile::Wall)) => (),
+        _ => return None,
+    }
+    let size = grid.size();
+    if coord.x <= 0
+        || coord.y <= 0
+        || coord.x >= size.width() as i32 - 1
+        || coord.y >= size.height() as i32 - 1
+    {
+        return None;
+    }
+    let west_east = (coord + Coord::new(-1, 0), coord + Coord::new(1, 0));
+    let north_south = (coord + Coord::new(0, -1), coord + Coord::new(0, 1));
+    let is_open = |(a, b): (Coord, Coord)| is_passable(a, grid) && is_passable(b, grid);
+    let is_closed = |(a, b): (Coord, Coord)| !is_passable(a, grid) && !is_passable(b, grid);
+    if is_open(west_east) && is_closed(north_south) {
+        Some(west_east)
+    } else if is_open(north_south) && is_closed(west_east) {
+        Some(north_south)
+    } else {
+        None
+    }
+}
+
+// Returns the coordinates of all floor cells which can be reached from `start` without passing
+// through walls or locked doors
+fn reachable_floor_coords(start: Coord, grid: &Grid<Option<TerrainTile>>) -> Vec<Coord> {
+    let mut seen = Grid::new_copy(grid.size(), false);
+    let mut queue = VecDeque::new();
+    let mut floor_coords = Vec::new();
+    *seen.get_checked_mut(start) = true;
+    queue.push_back(start);
+    while let Some(coord) = queue.pop_front() {
+        if grid.get_checked(coord) == &Some(TerrainTile::Floor) {
+            floor_coords.push(coord);
+        }
+        for direction in CardinalDirection::all() {
+            let neighbour_coord = coord + direction.coord();
+            if is_passable(neighbour_coord, grid) {
+                let seen_cell = seen.get_checked_mut(neighbour_coord);
+                if !*seen_cell {
+                    *seen_cell = true;
+                    queue.push_back(neighbour_coord);
+                }
+            }
+        }
+    }
+    floor_coords
 }
 
 fn choose_from_probability_distribution<'a, T, R: Rng>(
@@ -131,9 +471,12 @@ fn choose_from_probability_distribution<'a, T, R: Rng>(
     unreachable!()
 }
 
-fn make_npc_probability_distribution(level: u32) -> Vec<(NpcType, u32)> {
-    use NpcType::*;
-    vec![(Orc, 20), (Troll, level)]
+fn armoured_npc_percent(level: u32) -> u32 {
+    match level {
+        0..=1 => 5,
+        2..=3 => 10,
+        _ => 20,
+    }
 }
 
 fn make_item_probability_distribution(level: u32) -> Vec<(ItemType, u32)> {
@@ -145,6 +488,8 @@ fn make_item_probability_distribution(level: u32) -> Vec<(ItemType, u32)> {
     };
     vec![
         (HealthPotion, 200),
+        (AcidPotion, 20),
+        (StrengthPotion, 5),
         (
             FireballScroll,
             match level {
@@ -161,22 +506,73 @@ fn make_item_probability_distribution(level: u32) -> Vec<(ItemType, u32)> {
                 _ => 50,
             },
         ),
+        (
+            LightningScroll,
+            match level {
+                0..=1 => 10,
+                2..=4 => 30,
+                _ => 50,
+            },
+        ),
+        (TeleportScroll, 20),
+        (SummonScroll, 10),
+        (MappingScroll, 10),
+        (EarthquakeScroll, 5),
+        (HasteScroll, 10),
+        (BlindnessScroll, 10),
+        (PolymorphScroll, 10),
+        (TeleportOtherScroll, 10),
+        (
+            ChainLightningScroll,
+            match level {
+                0..=1 => 0,
+                2..=4 => 10,
+                _ => 20,
+            },
+        ),
+        (ClairvoyanceScroll, 10),
+        (DetectLifeScroll, 10),
+        (IdentifyScroll, 10),
+        (RemoveCurseScroll, 10),
+        (Ration, 30),
+        (Backpack, 5),
+        (Net, 10),
+        (Pickaxe, item_chance / 2),
+        (FireballWand, item_chance / 2),
         (Sword, item_chance),
+        (Dagger, item_chance),
+        (PoisonDagger, item_chance / 2),
+        (VampiricDagger, item_chance / 2),
         (Staff, item_chance),
+        (Shield, item_chance),
+        (ReflectShield, item_chance / 4),
         (Armour, item_chance),
+        (LeatherArmour, item_chance),
         (Robe, item_chance),
     ]
 }
 
-pub fn generate_dungeon<R: Rng>(size: Size, level: u32, rng: &mut R) -> Grid<TerrainTile> {
+// Generation must only draw on `rng` for its randomness (and not, say, iterate over a hash map), as
+// levels are reproduced by generating them again from the same seed
+pub fn generate_dungeon<R: Rng>(
+    size: Size,
+    level: u32,
+    monster_spawn_table: &MonsterSpawnTable,
+    generation_config: &GenerationConfig,
+    rng: &mut R,
+) -> Grid<TerrainTile> {
     let mut grid = Grid::new_copy(size, None);
     let mut room_centres = Vec::new();
+    let mut rooms = Vec::new();
 
     const NPCS_PER_ROOM_DISTRIBUTION: &[usize] = &[0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 3, 3, 4];
     const ITEMS_PER_ROOM_DISTRIBUTION: &[usize] = &[0, 0, 1, 1, 1, 1, 1, 2, 2];
+    const TRAPS_PER_ROOM_DISTRIBUTION: &[usize] = &[0, 0, 0, 0, 0, 0, 1];
+    const GOLD_PER_ROOM_DISTRIBUTION: &[usize] = &[0, 0, 0, 1, 1, 2];
 
-    let npc_probability_distribution = make_npc_probability_distribution(level);
+    let npc_probability_distribution = monster_spawn_table.probability_distribution(level);
     let item_probability_distribution = make_item_probability_distribution(level);
+    let armoured_percent = armoured_npc_percent(level);
 
     // Attempt to add a room a constant number of times
     const NUM_ATTEMPTS: usize = 100;
@@ -200,17 +596,124 @@ pub fn generate_dungeon<R: Rng>(size: Size, level: u32, rng: &mut R) -> Grid<Ter
 
             // Add npcs to the room
             let &num_npcs = NPCS_PER_ROOM_DISTRIBUTION.choose(rng).unwrap();
-            room.place_npcs(num_npcs, &npc_probability_distribution, &mut grid, rng);
+            room.place_npcs(
+                num_npcs,
+                &npc_probability_distribution,
+                armoured_percent,
+                &mut grid,
+                rng,
+            );
 
             // Add items to the room
             let &num_items = ITEMS_PER_ROOM_DISTRIBUTION.choose(rng).unwrap();
             room.place_items(num_items, &item_probability_distribution, &mut grid, rng);
+
+            // Add gold to the room
+            let &num_gold = GOLD_PER_ROOM_DISTRIBUTION.choose(rng).unwrap();
+            room.place_gold(num_gold, level, &mut grid, rng);
+
+            // Add traps to the room
+            let &num_traps = TRAPS_PER_ROOM_DISTRIBUTION.choose(rng).unwrap();
+            room.place_traps(num_traps, &mut grid, rng);
+
+            rooms.push(room);
         }
     }
 
-    // Add corridors connecting every adjacent pair of room centres
+    // Add corridors connecting every adjacent pair of room centres, keeping track of where they
+    // pass through walls
+    let mut carved_walls = Vec::new();
     for window in room_centres.windows(2) {
-        carve_corridor(window[0], window[1], &mut grid);
+        carved_walls.extend(carve_corridor(window[0], window[1], &mut grid));
+    }
+
+    // Sometimes the doors into the room containing the stairs are locked
+    const LOCKED_STAIRS_ROOM_PERCENT: u32 = 30;
+    let lock_colour = if rng.gen_range(0..100) < LOCKED_STAIRS_ROOM_PERCENT {
+        KeyColour::ALL.choose(rng).cloned()
+    } else {
+        None
+    };
+
+    // Add doors where corridors enter rooms
+    for coord in carved_walls {
+        if is_doorway(coord, &grid) {
+            *grid.get_checked_mut(coord) = Some(TerrainTile::Door);
+        }
+    }
+
+    // Lock every way into the room containing the stairs, whether or not it's shaped like a
+    // doorway, so the only way to reach the stairs is with the key
+    let locked_room = lock_colour.and(rooms.last());
+    let lock_entrances = |grid: &mut Grid<Option<TerrainTile>>| {
+        if let (Some(colour), Some(room)) = (lock_colour, locked_room) {
+            for coord in room.entrances(grid).collect::<Vec<_>>() {
+                *grid.get_checked_mut(coord) = Some(TerrainTile::LockedDoor(colour));
+            }
+        }
+    };
+    lock_entrances(&mut grid);
+
+    // Corridors between rooms should already connect the whole level, but make sure nothing is
+    // left unreachable from the player's starting position. A new corridor may still pass through
+    // the locked room on its way elsewhere, so any entrance it opens up is locked too.
+    connect_all(room_centres[0], locked_room, &mut grid);
+    lock_entrances(&mut grid);
+
+    // Sometimes one room is a monster house. The rooms containing the player and the stairs are
+    // never chosen, and since the house only fills empty floor, every room stays reachable.
+    const MONSTER_HOUSE_NPCS: usize = 10;
+    const MONSTER_HOUSE_ITEMS: usize = 4;
+    if rooms.len() > 2 && rng.gen_range(0..100) < generation_config.monster_house_percent {
+        let monster_house = &rooms[rng.gen_range(1..(rooms.len() - 1))];
+        monster_house.place_npcs(
+            MONSTER_HOUSE_NPCS,
+            &npc_probability_distribution,
+            armoured_percent,
+            &mut grid,
+            rng,
+        );
+        monster_house.place_items(
+            MONSTER_HOUSE_ITEMS,
+            &item_probability_distribution,
+            &mut grid,
+            rng,
+        );
+    }
+
+    // Add a few secret passages through walls separating open cells which are otherwise a long
+    // walk apart. Passages never lead into a locked room, as they'd let the player skip the key.
+    const NUM_SECRET_PASSAGES: usize = 3;
+    const SECRET_PASSAGE_MIN_SHORTCUT: usize = 20;
+    let mut candidates = grid
+        .coord_iter()
+        .filter_map(|coord| wall_between(coord, &grid).map(|sides| (coord, sides)))
+        .filter(|&(_, (a, b))| match (lock_colour, rooms.last()) {
+            (Some(_), Some(room)) => !room.coords().any(|coord| coord == a || coord == b),
+            _ => true,
+        })
+        .collect::<Vec<_>>();
+    candidates.shuffle(rng);
+    let mut num_secret_passages = 0;
+    for (coord, (a, b)) in candidates {
+        if num_secret_passages >= NUM_SECRET_PASSAGES {
+            break;
+        }
+        let is_shortcut = walking_distance(a, b, &grid)
+            .map(|distance| distance >= SECRET_PASSAGE_MIN_SHORTCUT)
+            .unwrap_or(false);
+        if is_shortcut {
+            *grid.get_checked_mut(coord) = Some(TerrainTile::SecretWall);
+            num_secret_passages += 1;
+        }
+    }
+
+    // Place the key somewhere the player can reach without passing through a locked door
+    if let Some(colour) = lock_colour {
+        let reachable = reachable_floor_coords(room_centres[0], &grid);
+        if let Some(&coord) = reachable.choose(rng) {
+            *grid.get_checked_mut(coord) = Some(TerrainTile::Item(ItemType::Key(colour)));
+        }
     }
 
     // Add stairs to the centre of the last room placed
@@ -218,3 +721,146 @@ pub fn generate_dungeon<R: Rng>(size: Size, level: u32, rng: &mut R) -> Grid<Ter
 
     grid.map(|t| t.unwrap_or(TerrainTile::Wall))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_isaac::Isaac64Rng;
+
+    #[test]
+    fn zero_weight_orcs_are_never_spawned() {
+        let monster_spawn_table = MonsterSpawnTable {
+            weights: vec![
+                MonsterSpawnWeight {
+                    npc_type: NpcType::Orc,
+                    base: 0,
+                    per_level: 0,
+                },
+                MonsterSpawnWeight {
+                    npc_type: NpcType::Troll,
+                    base: 1,
+                    per_level: 1,
+                },
+            ],
+        };
+        let mut num_npcs = 0;
+        for seed in 0..20 {
+            let mut rng = Isaac64Rng::seed_from_u64(seed);
+            let grid = generate_dungeon(
+                Size::new(40, 30),
+                1,
+                &monster_spawn_table,
+                &GenerationConfig::default(),
+                &mut rng,
+            );
+            for tile in grid.iter() {
+                match *tile {
+                    TerrainTile::Npc(npc_type) | TerrainTile::ArmouredNpc(npc_type) => {
+                        assert_eq!(npc_type, NpcType::Troll);
+                        num_npcs += 1;
+                    }
+                    _ => (),
+                }
+            }
+        }
+        assert!(num_npcs > 0);
+    }
+
+    #[test]
+    fn locked_stairs_room_can_only_be_reached_with_key() {
+        let mut num_locked_levels = 0;
+        for seed in 0..100 {
+            let mut rng = Isaac64Rng::seed_from_u64(seed);
+            let grid = generate_dungeon(
+                Size::new(40, 30),
+                1,
+                &MonsterSpawnTable::default(),
+                &GenerationConfig::default(),
+                &mut rng,
+            )
+            .map(Some);
+            let find = |tile: TerrainTile| {
+                grid.enumerate()
+                    .find(|&(_, &cell)| cell == Some(tile))
+                    .map(|(coord, _)| coord)
+            };
+            let colour = match grid.iter().find_map(|&cell| match cell {
+                Some(TerrainTile::LockedDoor(colour)) => Some(colour),
+                _ => None,
+            }) {
+                Some(colour) => colour,
+                None => continue,
+            };
+            num_locked_levels += 1;
+            let start = find(TerrainTile::Player).unwrap();
+            let stairs = find(TerrainTile::Stairs).unwrap();
+            let key = find(TerrainTile::Item(ItemType::Key(colour))).unwrap();
+            assert_eq!(walking_distance(start, stairs, &grid), None);
+            assert!(walking_distance(start, key, &grid).is_some());
+            assert!(*connected_cells(start, &grid).get_checked(stairs));
+        }
+        assert!(num_locked_levels > 0);
+    }
+
+    #[test]
+    fn monster_house_packs_extra_npcs_into_level() {
+        const NUM_SEEDS: u64 = 20;
+        let count_npcs = |seed: u64, monster_house_percent: u32| {
+            let mut rng = Isaac64Rng::seed_from_u64(seed);
+            generate_dungeon(
+                Size::new(40, 30),
+                1,
+                &MonsterSpawnTable::default(),
+                &GenerationConfig {
+                    monster_house_percent,
+                },
+                &mut rng,
+            )
+            .iter()
+            .filter(|tile| matches!(tile, TerrainTile::Npc(_) | TerrainTile::ArmouredNpc(_)))
+            .count()
+        };
+        let mut extra_npcs = 0;
+        for seed in 0..NUM_SEEDS {
+            let normal = count_npcs(seed, 0);
+            let with_monster_house = count_npcs(seed, 100);
+            assert!(with_monster_house >= normal);
+            extra_npcs += with_monster_house - normal;
+        }
+        // A normal room has at most 4 npcs, and a monster house adds up to 10 more to a single room
+        assert!(extra_npcs as u64 > NUM_SEEDS * 5);
+    }
+
+    #[test]
+    fn player_start_can_reach_every_open_cell() {
+        for seed in 0..200 {
+            let mut rng = Isaac64Rng::seed_from_u64(seed);
+            let grid = generate_dungeon(
+                Size::new(40, 30),
+                1,
+                &MonsterSpawnTable::default(),
+                &GenerationConfig::default(),
+                &mut rng,
+            )
+            .map(Some);
+            let start = grid
+                .enumerate()
+                .find(|&(_, &cell)| cell == Some(TerrainTile::Player))
+                .map(|(coord, _)| coord)
+                .unwrap();
+            // The key to any locked door is on the level, so cells behind it count as reachable
+            let connected = connected_cells(start, &grid);
+            for (coord, &cell) in grid.enumerate() {
+                if matches!(cell, Some(TerrainTile::Floor) | Some(TerrainTile::Stairs)) {
+                    assert!(
+                        *connected.get_checked(coord),
+                        "seed {}: cell at {:?} is unreachable",
+                        seed,
+                        coord
+                    );
+                }
+            }
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use crate::app::colours;
-use crate::game::{ExamineCell, LogMessage};
-use crate::world::HitPoints;
+use crate::game::{ExamineCell, LogMessage, LogSeverity, RestOutcome};
+use crate::world::{HitPoints, ItemType, StatusEffect};
 use chargrid::{
     decorator::{AlignView, Alignment, AlignmentX, AlignmentY, BoundView},
     render::{ColModify, Frame, Style, View, ViewCell, ViewContext},
@@ -67,7 +67,13 @@ impl Default for MessagesView {
     fn default() -> Self {
         let common = RichTextPartOwned::new(String::new(), Style::new());
         Self {
-            buf: vec![common.clone(), common.clone(), common],
+            buf: vec![
+                common.clone(),
+                common.clone(),
+                common.clone(),
+                common.clone(),
+                common,
+            ],
         }
     }
 }
@@ -85,10 +91,29 @@ impl<'a> View<&'a [LogMessage]> for MessagesView {
             buf[0].text.clear();
             buf[1].text.clear();
             buf[2].text.clear();
+            buf[3].text.clear();
+            buf[4].text.clear();
             buf[0].style.foreground = Some(Rgb24::new_grey(255));
             buf[1].style.bold = Some(true);
             buf[2].style.foreground = Some(Rgb24::new_grey(255));
+            buf[3].style.bold = Some(true);
+            buf[4].style.foreground = Some(Rgb24::new_grey(255));
             match message {
+                PlayerDrainsLife => {
+                    write!(&mut buf[0].text, "You drain some of your foe's ").unwrap();
+                    write!(&mut buf[1].text, "life").unwrap();
+                    buf[1].style.foreground = Some(Rgb24::new(187, 0, 0));
+                    write!(&mut buf[2].text, ".").unwrap();
+                }
+                PlayerCriticalHit(npc_type) => {
+                    write!(&mut buf[0].text, "You land a ").unwrap();
+                    write!(&mut buf[1].text, "critical hit").unwrap();
+                    buf[1].style.foreground = Some(Rgb24::new(255, 255, 0));
+                    write!(&mut buf[2].text, " on the ").unwrap();
+                    write!(&mut buf[3].text, "{}", npc_type.name()).unwrap();
+                    buf[3].style.foreground = Some(colours::npc_colour(npc_type));
+                    write!(&mut buf[4].text, "!").unwrap();
+                }
                 PlayerAttacksNpc(npc_type) => {
                     write!(&mut buf[0].text, "You attack the ").unwrap();
                     write!(&mut buf[1].text, "{}", npc_type.name()).unwrap();
@@ -116,46 +141,379 @@ impl<'a> View<&'a [LogMessage]> for MessagesView {
                     write!(&mut buf[2].text, " KILLS YOU!").unwrap();
                     buf[2].style.foreground = Some(Rgb24::new(255, 0, 0));
                 }
-                PlayerGets(item_type) => {
-                    write!(&mut buf[0].text, "You get the ").unwrap();
+                PlayerDies => {
+                    write!(&mut buf[0].text, "YOU DIE!").unwrap();
+                    buf[0].style.foreground = Some(Rgb24::new(255, 0, 0));
+                }
+                PlayerDiesOfPoison => {
+                    write!(&mut buf[0].text, "You succumb to the ").unwrap();
+                    write!(&mut buf[1].text, "poison").unwrap();
+                    buf[1].style.foreground = Some(Rgb24::new(0, 187, 0));
+                    write!(&mut buf[2].text, "! YOU DIE!").unwrap();
+                    buf[2].style.foreground = Some(Rgb24::new(255, 0, 0));
+                }
+                PlayerStarvesToDeath => {
+                    write!(&mut buf[0].text, "You starve to death! ").unwrap();
+                    write!(&mut buf[1].text, "YOU DIE!").unwrap();
+                    buf[1].style.foreground = Some(Rgb24::new(255, 0, 0));
+                }
+                PlayerStarving => {
+                    write!(&mut buf[0].text, "You are ").unwrap();
+                    write!(&mut buf[1].text, "starving").unwrap();
+                    buf[1].style.foreground = Some(Rgb24::new(255, 0, 0));
+                    write!(&mut buf[2].text, "!").unwrap();
+                }
+                Earthquake => {
+                    write!(&mut buf[0].text, "The ground ").unwrap();
+                    write!(&mut buf[1].text, "shakes").unwrap();
+                    buf[1].style.foreground = Some(colours::EARTHQUAKE_SCROLL);
+                    write!(&mut buf[2].text, " violently!").unwrap();
+                }
+                PlayerMapsLevel => {
+                    write!(
+                        &mut buf[0].text,
+                        "The layout of this level is revealed to you."
+                    )
+                    .unwrap();
+                }
+                TrapTriggers => {
+                    write!(&mut buf[0].text, "A ").unwrap();
+                    write!(&mut buf[1].text, "trap").unwrap();
+                    buf[1].style.foreground = Some(colours::TRAP);
+                    write!(&mut buf[2].text, " is triggered!").unwrap();
+                }
+                PlayerFindsTraps => {
+                    write!(&mut buf[0].text, "You find a hidden ").unwrap();
+                    write!(&mut buf[1].text, "trap").unwrap();
+                    buf[1].style.foreground = Some(colours::TRAP);
+                    write!(&mut buf[2].text, ".").unwrap();
+                }
+                AimWouldHarmPlayer => {
+                    write!(&mut buf[0].text, "You would be caught in the ").unwrap();
+                    write!(&mut buf[1].text, "blast").unwrap();
+                    buf[1].style.foreground = Some(Rgb24::new(255, 0, 0));
+                    write!(&mut buf[2].text, "! Confirm again to fire anyway.").unwrap();
+                }
+                CannotSweep => {
+                    write!(
+                        &mut buf[0].text,
+                        "You need a two-handed weapon to make a sweeping attack!"
+                    )
+                    .unwrap();
+                }
+                PlayerTalksTo(npc_type) => {
+                    write!(&mut buf[0].text, "You talk to the ").unwrap();
+                    write!(&mut buf[1].text, "{}", npc_type.name()).unwrap();
+                    buf[1].style.foreground = Some(colours::npc_colour(npc_type));
+                    write!(&mut buf[2].text, ".").unwrap();
+                }
+                PlayerFindsPassage => {
+                    write!(&mut buf[0].text, "You find a ").unwrap();
+                    write!(&mut buf[1].text, "secret passage").unwrap();
+                    write!(&mut buf[2].text, "!").unwrap();
+                }
+                PlayerMapsRoom => {
+                    write!(&mut buf[0].text, "A distant room is revealed to you.").unwrap();
+                }
+                NpcDigsWall(npc_type) => {
+                    write!(&mut buf[0].text, "The ").unwrap();
+                    write!(&mut buf[1].text, "{}", npc_type.name()).unwrap();
+                    buf[1].style.foreground = Some(colours::npc_colour(npc_type));
+                    write!(&mut buf[2].text, " tunnels through the wall.").unwrap();
+                }
+                PlayerDetectsLife => {
+                    write!(&mut buf[0].text, "You sense the presence of ").unwrap();
+                    write!(&mut buf[1].text, "living creatures").unwrap();
+                    buf[1].style.foreground = Some(colours::DETECT_LIFE_SCROLL);
+                    write!(&mut buf[2].text, ".").unwrap();
+                }
+                PlayerDigsWall => {
+                    write!(&mut buf[0].text, "You dig through the wall.").unwrap();
+                }
+                CannotDigThere => {
+                    write!(&mut buf[0].text, "There's no wall there to dig!").unwrap();
+                }
+                PlayerRemovesCurse => {
+                    write!(
+                        &mut buf[0].text,
+                        "You feel as if someone is watching over you."
+                    )
+                    .unwrap();
+                }
+                ItemIsCursed(item_type) => {
+                    write!(&mut buf[0].text, "The ").unwrap();
                     write!(&mut buf[1].text, "{}", item_type.name()).unwrap();
                     buf[1].style.foreground = Some(colours::item_colour(item_type));
+                    write!(&mut buf[2].text, " is cursed! You can't take it off.").unwrap();
+                }
+                PlayerIdentifies(item_type) => {
+                    write!(&mut buf[0].text, "You identify the ").unwrap();
+                    write!(&mut buf[1].text, "{}", item_type.name()).unwrap();
+                    buf[1].style.foreground = Some(colours::item_colour(item_type));
+                    write!(&mut buf[2].text, ".").unwrap();
+                }
+                PlayerStopsResting(outcome) => match outcome {
+                    RestOutcome::FullHitPoints => {
+                        write!(&mut buf[0].text, "You feel fully rested.").unwrap();
+                    }
+                    RestOutcome::HostileInView => {
+                        write!(&mut buf[0].text, "You stop resting: ").unwrap();
+                        write!(&mut buf[1].text, "an enemy is in view").unwrap();
+                        buf[1].style.foreground = Some(Rgb24::new(255, 0, 0));
+                        write!(&mut buf[2].text, "!").unwrap();
+                    }
+                    RestOutcome::MaxTurnsElapsed => {
+                        write!(&mut buf[0].text, "You finish resting.").unwrap();
+                    }
+                    RestOutcome::Interrupted => {
+                        write!(&mut buf[0].text, "Your rest is interrupted!").unwrap();
+                    }
+                },
+                WandIsEmpty => {
+                    write!(&mut buf[0].text, "The wand is empty!").unwrap();
+                }
+                ItemBreaks(item_type) => {
+                    write!(&mut buf[0].text, "Your ").unwrap();
+                    write!(&mut buf[1].text, "{}", item_type.name()).unwrap();
+                    buf[1].style.foreground = Some(colours::item_colour(item_type));
+                    write!(&mut buf[2].text, " breaks!").unwrap();
+                }
+                PlayerEats => {
+                    write!(&mut buf[0].text, "You eat the ").unwrap();
+                    write!(&mut buf[1].text, "ration").unwrap();
+                    write!(&mut buf[2].text, ".").unwrap();
+                }
+                PlayerTakesPoisonDamage => {
+                    write!(&mut buf[0].text, "The ").unwrap();
+                    write!(&mut buf[1].text, "poison").unwrap();
+                    buf[1].style.foreground = Some(Rgb24::new(0, 187, 0));
+                    write!(&mut buf[2].text, " hurts you!").unwrap();
+                }
+                NpcTakesPoisonDamage(npc_type) => {
+                    write!(&mut buf[0].text, "The ").unwrap();
+                    write!(&mut buf[1].text, "{}", npc_type.name()).unwrap();
+                    buf[1].style.foreground = Some(colours::npc_colour(npc_type));
+                    write!(&mut buf[2].text, " is hurt by poison.").unwrap();
+                }
+                NpcBleeds(npc_type) => {
+                    write!(&mut buf[0].text, "The ").unwrap();
+                    write!(&mut buf[1].text, "{}", npc_type.name()).unwrap();
+                    buf[1].style.foreground = Some(colours::npc_colour(npc_type));
+                    write!(&mut buf[2].text, " ").unwrap();
+                    write!(&mut buf[3].text, "bleeds").unwrap();
+                    buf[3].style.foreground = Some(Rgb24::new(187, 0, 0));
+                    write!(&mut buf[4].text, ".").unwrap();
+                }
+                PlayerGets(item_type, identified) => {
+                    write!(&mut buf[0].text, "You get the ").unwrap();
+                    write!(&mut buf[1].text, "{}", item_type.display_name(identified)).unwrap();
+                    buf[1].style.foreground =
+                        Some(colours::item_display_colour(item_type, identified));
+                    write!(&mut buf[2].text, ".").unwrap();
+                }
+                PlayerGetsGold(amount) => {
+                    write!(&mut buf[0].text, "You pick up ").unwrap();
+                    write!(&mut buf[1].text, "{} gold", amount).unwrap();
+                    buf[1].style.foreground = Some(colours::GOLD);
+                    write!(&mut buf[2].text, ".").unwrap();
+                }
+                AutoPickup(enabled) => {
+                    write!(&mut buf[0].text, "Auto-pickup of potions and gold ").unwrap();
+                    write!(
+                        &mut buf[1].text,
+                        "{}",
+                        if enabled { "enabled" } else { "disabled" }
+                    )
+                    .unwrap();
                     write!(&mut buf[2].text, ".").unwrap();
                 }
                 PlayerInventoryIsFull => {
                     write!(&mut buf[0].text, "Inventory is full!").unwrap();
                 }
+                TooHeavy(item_type, identified) => {
+                    write!(&mut buf[0].text, "The ").unwrap();
+                    write!(&mut buf[1].text, "{}", item_type.display_name(identified)).unwrap();
+                    buf[1].style.foreground =
+                        Some(colours::item_display_colour(item_type, identified));
+                    write!(&mut buf[2].text, " is too heavy to carry!").unwrap();
+                }
+                PlayerInventoryExpands => {
+                    write!(&mut buf[0].text, "You strap on the ").unwrap();
+                    write!(&mut buf[1].text, "backpack").unwrap();
+                    buf[1].style.foreground = Some(colours::BACKPACK);
+                    write!(&mut buf[2].text, ". Your inventory grows.").unwrap();
+                }
+                PlayerInventoryCannotExpand => {
+                    write!(&mut buf[0].text, "You can't carry any more!").unwrap();
+                }
                 NoItemUnderPlayer => {
                     write!(&mut buf[0].text, "Nothing to get!").unwrap();
                 }
                 NoItemInInventorySlot => {
                     write!(&mut buf[0].text, "No item in inventory slot!").unwrap();
                 }
+                PlayerFeelsStronger => {
+                    write!(&mut buf[0].text, "You feel ").unwrap();
+                    write!(&mut buf[1].text, "stronger").unwrap();
+                    buf[1].style.foreground = Some(colours::STRENGTH_POTION);
+                    write!(&mut buf[2].text, "!").unwrap();
+                }
                 PlayerHeals => {
                     write!(&mut buf[0].text, "You feel slightly better.").unwrap();
                     buf[0].style.foreground = Some(Rgb24::new(0, 187, 0));
                 }
-                PlayerDrops(item_type) => {
+                PlayerZapsNpc(npc_type) => {
+                    write!(&mut buf[0].text, "Lightning strikes the ").unwrap();
+                    write!(&mut buf[1].text, "{}", npc_type.name()).unwrap();
+                    buf[1].style.foreground = Some(colours::npc_colour(npc_type));
+                    write!(&mut buf[2].text, "!").unwrap();
+                }
+                NoTargetInRange => {
+                    write!(&mut buf[0].text, "No target in range!").unwrap();
+                }
+                PlayerSummons(npc_type) => {
+                    write!(&mut buf[0].text, "You summon an allied ").unwrap();
+                    write!(&mut buf[1].text, "{}", npc_type.name()).unwrap();
+                    buf[1].style.foreground = Some(colours::npc_colour(npc_type));
+                    write!(&mut buf[2].text, "!").unwrap();
+                }
+                CannotSummonThere => {
+                    write!(&mut buf[0].text, "Can't summon there!").unwrap();
+                }
+                CannotSeeTarget => {
+                    write!(&mut buf[0].text, "You can't see there!").unwrap();
+                }
+                PlayerTeleports => {
+                    write!(&mut buf[0].text, "You feel disoriented.").unwrap();
+                }
+                TeleportFails => {
+                    write!(&mut buf[0].text, "There's nowhere to teleport to!").unwrap();
+                }
+                NpcTeleports(npc_type) => {
+                    write!(&mut buf[0].text, "The ").unwrap();
+                    write!(&mut buf[1].text, "{}", npc_type.name()).unwrap();
+                    buf[1].style.foreground = Some(colours::npc_colour(npc_type));
+                    write!(&mut buf[2].text, " vanishes!").unwrap();
+                }
+                NothingHappens => {
+                    write!(&mut buf[0].text, "Nothing happens.").unwrap();
+                }
+                NpcHeals(npc_type) => {
+                    write!(&mut buf[0].text, "The ").unwrap();
+                    write!(&mut buf[1].text, "{}", npc_type.name()).unwrap();
+                    buf[1].style.foreground = Some(colours::npc_colour(npc_type));
+                    write!(&mut buf[2].text, " looks slightly better.").unwrap();
+                }
+                PotionShatters => {
+                    write!(&mut buf[0].text, "The potion shatters.").unwrap();
+                }
+                PlayerIsSplashedWithAcid => {
+                    write!(&mut buf[0].text, "The ").unwrap();
+                    write!(&mut buf[1].text, "acid").unwrap();
+                    buf[1].style.foreground = Some(colours::ACID_POTION);
+                    write!(&mut buf[2].text, " burns you!").unwrap();
+                }
+                NpcIsSplashedWithAcid(npc_type) => {
+                    write!(&mut buf[0].text, "The ").unwrap();
+                    write!(&mut buf[1].text, "{}", npc_type.name()).unwrap();
+                    buf[1].style.foreground = Some(colours::npc_colour(npc_type));
+                    write!(&mut buf[2].text, " is splashed with ").unwrap();
+                    write!(&mut buf[3].text, "acid").unwrap();
+                    buf[3].style.foreground = Some(colours::ACID_POTION);
+                    write!(&mut buf[4].text, ".").unwrap();
+                }
+                CannotThrowItem(item_type, identified) => {
+                    write!(&mut buf[0].text, "You can't throw the ").unwrap();
+                    write!(&mut buf[1].text, "{}", item_type.display_name(identified)).unwrap();
+                    buf[1].style.foreground =
+                        Some(colours::item_display_colour(item_type, identified));
+                    write!(&mut buf[2].text, ".").unwrap();
+                }
+                PlayerDrops(item_type, identified) => {
                     write!(&mut buf[0].text, "You drop the ").unwrap();
-                    write!(&mut buf[1].text, "{}", item_type.name()).unwrap();
-                    buf[1].style.foreground = Some(colours::item_colour(item_type));
+                    write!(&mut buf[1].text, "{}", item_type.display_name(identified)).unwrap();
+                    buf[1].style.foreground =
+                        Some(colours::item_display_colour(item_type, identified));
                     write!(&mut buf[2].text, ".").unwrap();
                 }
                 NoSpaceToDropItem => {
                     write!(&mut buf[0].text, "No space to drop item!").unwrap();
                 }
+                PlayerThrows(item_type) => {
+                    write!(&mut buf[0].text, "You throw the ").unwrap();
+                    write!(&mut buf[1].text, "{}", item_type.name()).unwrap();
+                    buf[1].style.foreground = Some(colours::item_colour(item_type));
+                    write!(&mut buf[2].text, ".").unwrap();
+                }
+                ThrownItemHitsNpc(item_type, npc_type) => {
+                    write!(&mut buf[0].text, "The ").unwrap();
+                    write!(&mut buf[1].text, "{}", item_type.name()).unwrap();
+                    buf[1].style.foreground = Some(colours::item_colour(item_type));
+                    write!(&mut buf[2].text, " hits the ").unwrap();
+                    write!(&mut buf[3].text, "{}", npc_type.name()).unwrap();
+                    buf[3].style.foreground = Some(colours::npc_colour(npc_type));
+                    write!(&mut buf[4].text, ".").unwrap();
+                }
                 PlayerLaunchesProjectile(projectile) => {
                     write!(&mut buf[0].text, "You launch a ").unwrap();
                     write!(&mut buf[1].text, "{}", projectile.name()).unwrap();
                     buf[1].style.foreground = Some(colours::projectile_colour(projectile));
                     write!(&mut buf[2].text, "!").unwrap();
                 }
+                NpcLaunchesProjectile(npc_type, projectile) => {
+                    write!(&mut buf[0].text, "The ").unwrap();
+                    write!(&mut buf[1].text, "{}", npc_type.name()).unwrap();
+                    buf[1].style.foreground = Some(colours::npc_colour(npc_type));
+                    write!(&mut buf[2].text, " fires an {}!", projectile.name()).unwrap();
+                }
+                PlayerIsBurned => {
+                    write!(&mut buf[0].text, "The ").unwrap();
+                    write!(&mut buf[1].text, "fire").unwrap();
+                    buf[1].style.foreground = Some(colours::FIRE);
+                    write!(&mut buf[2].text, " burns you!").unwrap();
+                }
+                NpcIsBurned(npc_type) => {
+                    write!(&mut buf[0].text, "The ").unwrap();
+                    write!(&mut buf[1].text, "{}", npc_type.name()).unwrap();
+                    buf[1].style.foreground = Some(colours::npc_colour(npc_type));
+                    write!(&mut buf[2].text, " is burned by the ").unwrap();
+                    write!(&mut buf[3].text, "fire").unwrap();
+                    buf[3].style.foreground = Some(colours::FIRE);
+                    write!(&mut buf[4].text, ".").unwrap();
+                }
+                ProjectileReflected(projectile) => {
+                    write!(&mut buf[0].text, "The ").unwrap();
+                    write!(&mut buf[1].text, "{}", projectile.name()).unwrap();
+                    buf[1].style.foreground = Some(colours::projectile_colour(projectile));
+                    write!(&mut buf[2].text, " is reflected!").unwrap();
+                }
+                PlayerIsHitByProjectile(projectile) => {
+                    write!(&mut buf[0].text, "You are hit by the ").unwrap();
+                    write!(&mut buf[1].text, "{}", projectile.name()).unwrap();
+                    buf[1].style.foreground = Some(colours::projectile_colour(projectile));
+                    write!(&mut buf[2].text, ".").unwrap();
+                }
                 NpcDies(npc_type) => {
                     write!(&mut buf[0].text, "The ").unwrap();
                     write!(&mut buf[1].text, "{}", npc_type.name()).unwrap();
                     buf[1].style.foreground = Some(colours::npc_colour(npc_type));
                     write!(&mut buf[2].text, " dies.").unwrap();
                 }
+                CorpseCrumbles(npc_type) => {
+                    write!(&mut buf[0].text, "The ").unwrap();
+                    write!(&mut buf[1].text, "{}", npc_type.name()).unwrap();
+                    buf[1].style.foreground = Some(colours::npc_colour(npc_type));
+                    write!(&mut buf[2].text, " corpse crumbles to dust.").unwrap();
+                }
+                NpcPolymorphs { from, to } => {
+                    write!(&mut buf[0].text, "The ").unwrap();
+                    write!(&mut buf[1].text, "{}", from.name()).unwrap();
+                    buf[1].style.foreground = Some(colours::npc_colour(from));
+                    write!(&mut buf[2].text, " turns into a ").unwrap();
+                    write!(&mut buf[3].text, "{}", to.name()).unwrap();
+                    buf[3].style.foreground = Some(colours::npc_colour(to));
+                    write!(&mut buf[4].text, "!").unwrap();
+                }
                 NpcBecomesConfused(npc_type) => {
                     write!(&mut buf[0].text, "The ").unwrap();
                     write!(&mut buf[1].text, "{}", npc_type.name()).unwrap();
@@ -168,6 +526,42 @@ impl<'a> View<&'a [LogMessage]> for MessagesView {
                     buf[1].style.foreground = Some(colours::npc_colour(npc_type));
                     write!(&mut buf[2].text, "'s confusion passes.").unwrap();
                 }
+                NpcIsBlinded(npc_type) => {
+                    write!(&mut buf[0].text, "The ").unwrap();
+                    write!(&mut buf[1].text, "{}", npc_type.name()).unwrap();
+                    buf[1].style.foreground = Some(colours::npc_colour(npc_type));
+                    write!(&mut buf[2].text, " is blinded.").unwrap();
+                }
+                NpcFlees(npc_type) => {
+                    write!(&mut buf[0].text, "The ").unwrap();
+                    write!(&mut buf[1].text, "{}", npc_type.name()).unwrap();
+                    buf[1].style.foreground = Some(colours::npc_colour(npc_type));
+                    write!(&mut buf[2].text, " turns to flee!").unwrap();
+                }
+                NpcWakesUp(npc_type) => {
+                    write!(&mut buf[0].text, "The ").unwrap();
+                    write!(&mut buf[1].text, "{}", npc_type.name()).unwrap();
+                    buf[1].style.foreground = Some(colours::npc_colour(npc_type));
+                    write!(&mut buf[2].text, " wakes up!").unwrap();
+                }
+                NpcIsRooted(npc_type) => {
+                    write!(&mut buf[0].text, "The ").unwrap();
+                    write!(&mut buf[1].text, "{}", npc_type.name()).unwrap();
+                    buf[1].style.foreground = Some(colours::npc_colour(npc_type));
+                    write!(&mut buf[2].text, " is caught in the net!").unwrap();
+                }
+                NpcIsNoLongerRooted(npc_type) => {
+                    write!(&mut buf[0].text, "The ").unwrap();
+                    write!(&mut buf[1].text, "{}", npc_type.name()).unwrap();
+                    buf[1].style.foreground = Some(colours::npc_colour(npc_type));
+                    write!(&mut buf[2].text, " breaks free of the net.").unwrap();
+                }
+                NpcIsNoLongerBlind(npc_type) => {
+                    write!(&mut buf[0].text, "The ").unwrap();
+                    write!(&mut buf[1].text, "{}", npc_type.name()).unwrap();
+                    buf[1].style.foreground = Some(colours::npc_colour(npc_type));
+                    write!(&mut buf[2].text, " can see again.").unwrap();
+                }
                 PlayerDodges(npc_type) => {
                     write!(&mut buf[0].text, "You dodge the ").unwrap();
                     write!(&mut buf[1].text, "{}'s", npc_type.name()).unwrap();
@@ -180,12 +574,104 @@ impl<'a> View<&'a [LogMessage]> for MessagesView {
                     buf[1].style.foreground = Some(colours::npc_colour(npc_type));
                     write!(&mut buf[2].text, " dodges your attack.").unwrap();
                 }
+                NpcAttacksNpc(attacker, victim) => {
+                    write!(&mut buf[0].text, "The ").unwrap();
+                    write!(&mut buf[1].text, "{}", attacker.name()).unwrap();
+                    buf[1].style.foreground = Some(colours::npc_colour(attacker));
+                    write!(&mut buf[2].text, " attacks the ").unwrap();
+                    write!(&mut buf[3].text, "{}", victim.name()).unwrap();
+                    buf[3].style.foreground = Some(colours::npc_colour(victim));
+                    write!(&mut buf[4].text, ".").unwrap();
+                }
+                NpcKillsNpc(attacker, victim) => {
+                    write!(&mut buf[0].text, "The ").unwrap();
+                    write!(&mut buf[1].text, "{}", attacker.name()).unwrap();
+                    buf[1].style.foreground = Some(colours::npc_colour(attacker));
+                    write!(&mut buf[2].text, " kills the ").unwrap();
+                    write!(&mut buf[3].text, "{}", victim.name()).unwrap();
+                    buf[3].style.foreground = Some(colours::npc_colour(victim));
+                    write!(&mut buf[4].text, "!").unwrap();
+                }
+                NpcDodgesNpc(attacker, victim) => {
+                    write!(&mut buf[0].text, "The ").unwrap();
+                    write!(&mut buf[1].text, "{}", victim.name()).unwrap();
+                    buf[1].style.foreground = Some(colours::npc_colour(victim));
+                    write!(&mut buf[2].text, " dodges the ").unwrap();
+                    write!(&mut buf[3].text, "{}'s", attacker.name()).unwrap();
+                    buf[3].style.foreground = Some(colours::npc_colour(attacker));
+                    write!(&mut buf[4].text, " attack.").unwrap();
+                }
+                PlayerOpensDoor => {
+                    write!(&mut buf[0].text, "You open the door.").unwrap();
+                }
+                PlayerUnlocksDoor(colour) => {
+                    let key = ItemType::Key(colour);
+                    write!(&mut buf[0].text, "You unlock the door with the ").unwrap();
+                    write!(&mut buf[1].text, "{}", key.name()).unwrap();
+                    buf[1].style.foreground = Some(colours::item_colour(key));
+                    write!(&mut buf[2].text, ".").unwrap();
+                }
+                DoorIsLocked(colour) => {
+                    let key = ItemType::Key(colour);
+                    write!(&mut buf[0].text, "The door is locked. You need a ").unwrap();
+                    write!(&mut buf[1].text, "{}", key.name()).unwrap();
+                    buf[1].style.foreground = Some(colours::item_colour(key));
+                    write!(&mut buf[2].text, ".").unwrap();
+                }
+                CannotUseItem(item_type) => {
+                    write!(&mut buf[0].text, "You can't use the ").unwrap();
+                    write!(&mut buf[1].text, "{}", item_type.name()).unwrap();
+                    buf[1].style.foreground = Some(colours::item_colour(item_type));
+                    write!(&mut buf[2].text, " like that.").unwrap();
+                }
+                PlayerHasted => {
+                    write!(&mut buf[0].text, "You feel ").unwrap();
+                    write!(&mut buf[1].text, "fast").unwrap();
+                    buf[1].style.foreground = Some(colours::HASTE_SCROLL);
+                    write!(&mut buf[2].text, "!").unwrap();
+                }
+                PlayerIsNoLongerHasted => {
+                    write!(&mut buf[0].text, "You slow down.").unwrap();
+                }
+                PlayerDescends(dungeon_level) => {
+                    write!(&mut buf[0].text, "You descend to level ").unwrap();
+                    write!(&mut buf[1].text, "{}", dungeon_level).unwrap();
+                    write!(&mut buf[2].text, ".").unwrap();
+                }
                 PlayerEquips(item_type) => {
                     write!(&mut buf[0].text, "You equip the ").unwrap();
                     write!(&mut buf[1].text, "{}", item_type.name()).unwrap();
                     buf[1].style.foreground = Some(colours::item_colour(item_type));
                     write!(&mut buf[2].text, ".").unwrap();
                 }
+                PlayerUnequips(item_type) => {
+                    write!(&mut buf[0].text, "You take off the ").unwrap();
+                    write!(&mut buf[1].text, "{}", item_type.name()).unwrap();
+                    buf[1].style.foreground = Some(colours::item_colour(item_type));
+                    write!(&mut buf[2].text, ".").unwrap();
+                }
+                PlayerLowHealth => {
+                    write!(&mut buf[0].text, "You are close to death!").unwrap();
+                }
+                LevelSpawnErrors(count) => {
+                    write!(
+                        &mut buf[0].text,
+                        "Something went wrong building this level ({} things couldn't be placed).",
+                        count
+                    )
+                    .unwrap();
+                }
+            }
+            // The plain parts of more important messages stand out from the rest of the log
+            let severity_colour = match message.severity() {
+                LogSeverity::Critical => Some(Rgb24::new(255, 63, 63)),
+                LogSeverity::Warning => Some(Rgb24::new(255, 187, 63)),
+                LogSeverity::Info | LogSeverity::Combat => None,
+            };
+            if let Some(colour) = severity_colour {
+                for &i in &[0, 2, 4] {
+                    buf[i].style.foreground = Some(colour);
+                }
             }
         }
         const NUM_MESSAGES: usize = 4;
@@ -202,11 +688,27 @@ impl<'a> View<&'a [LogMessage]> for MessagesView {
     }
 }
 
-fn examine_cell_str(examine_cell: ExamineCell) -> &'static str {
+fn write_examine_cell(buf: &mut String, examine_cell: ExamineCell) {
+    use std::fmt::Write;
+    buf.clear();
     match examine_cell {
-        ExamineCell::Npc(npc_type) | ExamineCell::NpcCorpse(npc_type) => npc_type.name(),
-        ExamineCell::Item(item_type) => item_type.name(),
-        ExamineCell::Player => "yourself",
+        ExamineCell::Npc(npc_type, hit_points) => write!(
+            buf,
+            "{} ({}/{})",
+            npc_type.name(),
+            hit_points.current,
+            hit_points.max
+        )
+        .unwrap(),
+        ExamineCell::NpcCorpse(npc_type) => buf.push_str(npc_type.name()),
+        ExamineCell::Item(item_type, identified) => {
+            buf.push_str(item_type.display_name(identified))
+        }
+        ExamineCell::Player => buf.push_str("yourself"),
+        ExamineCell::Stairs => buf.push_str("stairs"),
+        ExamineCell::Wall => buf.push_str("stone wall"),
+        ExamineCell::Door => buf.push_str("door"),
+        ExamineCell::Floor => buf.push_str("floor"),
     }
 }
 
@@ -216,9 +718,13 @@ struct StatsView {
 }
 
 pub struct StatsData {
-    pub strength: i32,
+    pub strength: u32,
     pub dexterity: i32,
     pub intelligence: i32,
+    pub nutrition: u32,
+    pub gold: u32,
+    pub carry_weight: (u32, u32),
+    pub status_effects: Vec<StatusEffect>,
 }
 
 impl<'a> View<&'a StatsData> for StatsView {
@@ -232,10 +738,25 @@ impl<'a> View<&'a StatsData> for StatsView {
         self.buf.clear();
         write!(
             &mut self.buf,
-            "str: {}, dex: {}, int: {}",
-            data.strength, data.dexterity, data.intelligence
+            "str: {}, dex: {}, int: {}, food: {}, gold: {}, weight: {}/{}",
+            data.strength,
+            data.dexterity,
+            data.intelligence,
+            data.nutrition,
+            data.gold,
+            data.carry_weight.0,
+            data.carry_weight.1
         )
         .unwrap();
+        for status_effect in &data.status_effects {
+            write!(
+                &mut self.buf,
+                ", {} ({})",
+                status_effect.name(),
+                status_effect.remaining_turns()
+            )
+            .unwrap();
+        }
         StringViewSingleLine::new(Style::new().with_foreground(Rgb24::new_grey(187)))
             .view(&self.buf, context, frame);
     }
@@ -276,6 +797,7 @@ pub struct UiView {
     messages_view: MessagesView,
     stats_view: StatsView,
     dungeon_level_view: DungeonLevelView,
+    examine_cell_buf: String,
 }
 
 fn centre_health_width<T: Clone>(view: impl View<T>, height: u32) -> impl View<T> {
@@ -326,6 +848,7 @@ impl<'a> View<UiData<'a>> for UiView {
             .view(name, context.add_offset(Coord::new(0, 2)), frame);
         }
         if let Some(examine_cell) = data.examine_cell {
+            write_examine_cell(&mut self.examine_cell_buf, examine_cell);
             centre_health_width(
                 StringView::new(
                     Style::new().with_foreground(Rgb24::new_grey(187)),
@@ -334,7 +857,7 @@ impl<'a> View<UiData<'a>> for UiView {
                 2,
             )
             .view(
-                examine_cell_str(examine_cell),
+                self.examine_cell_buf.as_str(),
                 context.add_offset(Coord::new(0, 3)),
                 frame,
             );
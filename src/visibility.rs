@@ -13,6 +13,11 @@ const VISION_DISTANCE_SQUARED: u32 = 100;
 const VISION_DISTANCE: shadowcast::vision_distance::Circle =
     shadowcast::vision_distance::Circle::new_squared(VISION_DISTANCE_SQUARED);
 
+// A blind player can only see the cells immediately around them
+const BLIND_VISION_DISTANCE_SQUARED: u32 = 2;
+const BLIND_VISION_DISTANCE: shadowcast::vision_distance::Circle =
+    shadowcast::vision_distance::Circle::new_squared(BLIND_VISION_DISTANCE_SQUARED);
+
 struct Visibility;
 
 impl shadowcast::InputGrid for Visibility {
@@ -69,6 +74,17 @@ impl VisibilityGrid {
             CellVisibility::Never
         }
     }
+    // Marks cells as previously seen without making them currently visible. Cells which have
+    // already been seen are unaffected.
+    pub fn mark_discovered(&mut self, coords: impl IntoIterator<Item = Coord>) {
+        for coord in coords {
+            if let Some(cell) = self.grid.get_mut(coord) {
+                if cell.last_seen == 0 {
+                    cell.last_seen = 1;
+                }
+            }
+        }
+    }
     pub fn clear(&mut self) {
         self.count = 1;
         for cell in self.grid.iter_mut() {
@@ -78,6 +94,7 @@ impl VisibilityGrid {
     pub fn update(
         &mut self,
         player_coord: Coord,
+        player_is_blind: bool,
         world: &World,
         shadowcast_context: &mut shadowcast::Context<u8>,
         algorithm: VisibilityAlgorithm,
@@ -92,11 +109,16 @@ impl VisibilityGrid {
             VisibilityAlgorithm::Shadowcast => {
                 let count = self.count;
                 let grid = &mut self.grid;
+                let vision_distance = if player_is_blind {
+                    BLIND_VISION_DISTANCE
+                } else {
+                    VISION_DISTANCE
+                };
                 shadowcast_context.for_each_visible(
                     player_coord,
                     &Visibility,
                     world,
-                    VISION_DISTANCE,
+                    vision_distance,
                     255,
                     |coord, _visible_directions, _visibility| {
                         let cell = grid.get_checked_mut(coord);
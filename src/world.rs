@@ -1,33 +1,123 @@
 use crate::behaviour::Agent;
-use crate::game::{ExamineCell, LevelUp, LogMessage};
-use crate::terrain::{self, TerrainTile};
+use crate::game::{ExamineCell, LevelUp, LogMessage, RestOutcome};
+use crate::terrain::{self, GenerationConfig, MonsterSpawnTable, TerrainTile};
 use coord_2d::{Coord, Size};
-use direction::CardinalDirection;
+use direction::{CardinalDirection, Direction};
 use entity_table::{ComponentTable, Entity, EntityAllocator};
-use line_2d::CardinalStepIter;
-use rand::Rng;
+use grid_2d::Grid;
+use line_2d::LineSegment;
+use rand::{seq::IteratorRandom, Rng, SeedableRng};
+use rand_isaac::Isaac64Rng;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, VecDeque};
+use std::fmt;
 
-pub struct EquippedInventoryIndices {
-    pub worn: Option<usize>,
-    pub held: Option<usize>,
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EquipSlot {
+    Weapon,
+    Offhand,
+    Armour,
+}
+
+impl EquipSlot {
+    pub const ALL: [Self; 3] = [Self::Weapon, Self::Offhand, Self::Armour];
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Weapon => "weapon",
+            Self::Offhand => "offhand",
+            Self::Armour => "armour",
+        }
+    }
+}
+
+// The items a character has equipped. Equipped items are removed from the character's inventory,
+// and are returned to it when they are replaced by another item.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct EquipmentSlots {
+    pub weapon: Option<Entity>,
+    pub offhand: Option<Entity>,
+    pub armour: Option<Entity>,
+}
+
+impl EquipmentSlots {
+    pub fn get(&self, slot: EquipSlot) -> Option<Entity> {
+        match slot {
+            EquipSlot::Weapon => self.weapon,
+            EquipSlot::Offhand => self.offhand,
+            EquipSlot::Armour => self.armour,
+        }
+    }
+    fn slot_mut(&mut self, slot: EquipSlot) -> &mut Option<Entity> {
+        match slot {
+            EquipSlot::Weapon => &mut self.weapon,
+            EquipSlot::Offhand => &mut self.offhand,
+            EquipSlot::Armour => &mut self.armour,
+        }
+    }
+    pub fn iter<'a>(&'a self) -> impl 'a + Iterator<Item = (EquipSlot, Entity)> {
+        EquipSlot::ALL
+            .iter()
+            .filter_map(move |&slot| self.get(slot).map(|entity| (slot, entity)))
+    }
 }
 
 pub struct CharacterData {
     entity_data: EntityData,
-    inventory_entity_data: Vec<Option<EntityData>>,
+    inventory_entity_data: Vec<Option<Vec<EntityData>>>,
+    equipment_entity_data: Vec<(EquipSlot, EntityData)>,
 }
 
 #[derive(Clone, Copy)]
 pub enum ItemUsage {
     Immediate,
     Aim,
+    ChooseItem,
+}
+
+// A temporary effect on a character, with the number of turns it has left to run
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatusEffect {
+    Poisoned(u32),
+    Bleeding(u32),
+    Confused(u32),
+    Hasted(u32),
+    Blind(u32),
+    Rooted(u32),
+}
+
+impl StatusEffect {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Poisoned(_) => "poisoned",
+            Self::Bleeding(_) => "bleeding",
+            Self::Confused(_) => "confused",
+            Self::Hasted(_) => "hasted",
+            Self::Blind(_) => "blind",
+            Self::Rooted(_) => "rooted",
+        }
+    }
+    pub fn remaining_turns(self) -> u32 {
+        match self {
+            Self::Poisoned(turns)
+            | Self::Bleeding(turns)
+            | Self::Confused(turns)
+            | Self::Hasted(turns)
+            | Self::Blind(turns)
+            | Self::Rooted(turns) => turns,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum ProjectileType {
     Fireball { damage: u32 },
     Confusion { duration: u32 },
+    Blindness { duration: u32 },
+    Polymorph,
+    ChainLightning { damage: u32 },
+    Net { duration: u32 },
+    Arrow { damage: u32 },
+    Thrown { item_type: ItemType, damage: u32 },
 }
 
 impl ProjectileType {
@@ -35,13 +125,49 @@ impl ProjectileType {
         match self {
             Self::Fireball { .. } => "fireball",
             Self::Confusion { .. } => "confusion spell",
+            Self::Blindness { .. } => "blindness spell",
+            Self::Polymorph => "polymorph spell",
+            Self::ChainLightning { .. } => "chain lightning",
+            Self::Net { .. } => "net",
+            Self::Arrow { .. } => "arrow",
+            Self::Thrown { item_type, .. } => item_type.name(),
+        }
+    }
+    // The chebyshev radius of the area affected when the projectile stops
+    pub fn blast_radius(self) -> u32 {
+        match self {
+            Self::Fireball { .. } => 1,
+            Self::Confusion { .. } => 0,
+            Self::Blindness { .. } => 0,
+            Self::Polymorph => 0,
+            Self::ChainLightning { .. } => 0,
+            Self::Net { .. } => 0,
+            Self::Arrow { .. } => 0,
+            Self::Thrown { .. } => 0,
         }
     }
 }
 
+// A stack of one or more items of the same type occupying a single inventory slot
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InventorySlot {
+    item_type: ItemType,
+    entities: Vec<Entity>,
+}
+
+impl InventorySlot {
+    // Returns the item which will be removed next from this slot
+    pub fn entity(&self) -> Entity {
+        *self.entities.last().expect("empty inventory slot")
+    }
+    pub fn count(&self) -> usize {
+        self.entities.len()
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Inventory {
-    slots: Vec<Option<Entity>>,
+    slots: Vec<Option<InventorySlot>>,
 }
 
 pub struct InventoryIsFull;
@@ -54,55 +180,479 @@ impl Inventory {
         let slots = vec![None; capacity];
         Self { slots }
     }
-    pub fn slots(&self) -> &[Option<Entity>] {
+    pub fn slots(&self) -> &[Option<InventorySlot>] {
         &self.slots
     }
-    pub fn insert(&mut self, item: Entity) -> Result<(), InventoryIsFull> {
+    // Stackable items are added to an existing slot containing items of the same type if one
+    // exists. Otherwise the item is placed in the first empty slot.
+    pub fn insert(&mut self, item: Entity, item_type: ItemType) -> Result<(), InventoryIsFull> {
+        if item_type.is_stackable() {
+            if let Some(slot) = self
+                .slots
+                .iter_mut()
+                .flatten()
+                .find(|slot| slot.item_type == item_type)
+            {
+                slot.entities.push(item);
+                return Ok(());
+            }
+        }
         if let Some(slot) = self.slots.iter_mut().find(|s| s.is_none()) {
-            *slot = Some(item);
+            *slot = Some(InventorySlot {
+                item_type,
+                entities: vec![item],
+            });
             Ok(())
         } else {
             Err(InventoryIsFull)
         }
     }
+    // Removes a single item from the slot at `index`. The slot is only freed once its last item
+    // is removed.
     pub fn remove(&mut self, index: usize) -> Result<Entity, InventorySlotIsEmpty> {
-        if let Some(slot) = self.slots.get_mut(index) {
-            slot.take().ok_or(InventorySlotIsEmpty)
-        } else {
-            Err(InventorySlotIsEmpty)
+        let slot = self.slots.get_mut(index).ok_or(InventorySlotIsEmpty)?;
+        let stack = slot.as_mut().ok_or(InventorySlotIsEmpty)?;
+        let entity = stack.entities.pop().expect("empty inventory slot");
+        if stack.entities.is_empty() {
+            *slot = None;
         }
+        Ok(entity)
+    }
+    // Adds up to `amount` empty slots without exceeding `max_capacity` slots in total, returning
+    // the number of slots added
+    pub fn grow(&mut self, amount: usize, max_capacity: usize) -> usize {
+        let new_capacity = (self.slots.len() + amount)
+            .min(max_capacity)
+            .max(self.slots.len());
+        let added = new_capacity - self.slots.len();
+        self.slots.resize(new_capacity, None);
+        added
+    }
+    // The combined weight of every item in the inventory
+    pub fn total_weight(&self) -> u32 {
+        self.slots
+            .iter()
+            .flatten()
+            .map(|slot| slot.item_type.weight() * slot.count() as u32)
+            .sum()
+    }
+    // Removes a single item of the given type from the first slot which holds that type. Each
+    // slot records the type of its items, so this doesn't need to consult the item components.
+    pub fn remove_first_of_type(&mut self, item_type: ItemType) -> Option<Entity> {
+        let index = self.slots.iter().position(|slot| {
+            slot.as_ref()
+                .map(|slot| slot.item_type == item_type)
+                .unwrap_or(false)
+        })?;
+        self.remove(index).ok()
+    }
+    // Groups items by type in the order the types are declared, moving all the empty slots to the
+    // end. Items of the same type keep their relative order.
+    pub fn sort(&mut self) {
+        self.slots
+            .sort_by_key(|slot| (slot.is_none(), slot.as_ref().map(|slot| slot.item_type)));
     }
     pub fn get(&self, index: usize) -> Result<Entity, InventorySlotIsEmpty> {
         self.slots
             .get(index)
-            .cloned()
-            .flatten()
+            .and_then(|slot| slot.as_ref())
+            .map(|slot| slot.entity())
             .ok_or(InventorySlotIsEmpty)
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum ItemType {
     HealthPotion,
+    AcidPotion,
+    StrengthPotion,
     FireballScroll,
     ConfusionScroll,
+    LightningScroll,
+    ChainLightningScroll,
+    TeleportScroll,
+    SummonScroll,
+    MappingScroll,
+    EarthquakeScroll,
+    HasteScroll,
+    BlindnessScroll,
+    PolymorphScroll,
+    TeleportOtherScroll,
+    ClairvoyanceScroll,
+    DetectLifeScroll,
+    IdentifyScroll,
+    RemoveCurseScroll,
+    Ration,
+    Backpack,
+    Net,
+    Pickaxe,
+    FireballWand,
     Sword,
+    Dagger,
+    PoisonDagger,
+    VampiricDagger,
     Staff,
+    Shield,
+    ReflectShield,
     Armour,
+    LeatherArmour,
     Robe,
+    Key(KeyColour),
+}
+
+// Each locked door can only be opened with a key of the same colour
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum KeyColour {
+    Red,
+    Green,
+    Blue,
+}
+
+impl KeyColour {
+    pub const ALL: [Self; 3] = [Self::Red, Self::Green, Self::Blue];
+}
+
+// Labels given to specific entities so they can be found again later without hardcoding which
+// entity they are
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EntityTag {
+    Boss,
+}
+
+// What happens to the characters caught in the splash of a thrown potion
+#[derive(Clone, Copy, Debug)]
+pub enum SplashEffect {
+    Heal(u32),
+    Damage(u32),
 }
 
 impl ItemType {
+    pub fn splash_effect(self) -> Option<SplashEffect> {
+        match self {
+            Self::HealthPotion => Some(SplashEffect::Heal(HEALTH_TO_HEAL)),
+            Self::AcidPotion => Some(SplashEffect::Damage(ACID_DAMAGE)),
+            _ => None,
+        }
+    }
+    pub fn is_potion(self) -> bool {
+        matches!(
+            self,
+            Self::HealthPotion | Self::AcidPotion | Self::StrengthPotion
+        )
+    }
+    // Items which take effect at the cell they're aimed at, rather than launching a projectile
+    // towards it, can only be aimed at cells the character can see
+    pub fn is_aimed_directly(self) -> bool {
+        matches!(
+            self,
+            Self::HealthPotion | Self::AcidPotion | Self::SummonScroll | Self::TeleportOtherScroll
+        )
+    }
+    pub fn is_throwable(self) -> bool {
+        match self {
+            Self::HealthPotion
+            | Self::AcidPotion
+            | Self::Sword
+            | Self::Dagger
+            | Self::PoisonDagger
+            | Self::VampiricDagger
+            | Self::Staff => true,
+            Self::Ration
+            | Self::Backpack
+            | Self::StrengthPotion
+            | Self::Net
+            | Self::Pickaxe
+            | Self::FireballWand
+            | Self::FireballScroll
+            | Self::ConfusionScroll
+            | Self::LightningScroll
+            | Self::ChainLightningScroll
+            | Self::TeleportScroll
+            | Self::SummonScroll
+            | Self::MappingScroll
+            | Self::EarthquakeScroll
+            | Self::HasteScroll
+            | Self::BlindnessScroll
+            | Self::PolymorphScroll
+            | Self::TeleportOtherScroll
+            | Self::ClairvoyanceScroll
+            | Self::DetectLifeScroll
+            | Self::IdentifyScroll
+            | Self::RemoveCurseScroll
+            | Self::Shield
+            | Self::ReflectShield
+            | Self::Armour
+            | Self::LeatherArmour
+            | Self::Robe
+            | Self::Key(_) => false,
+        }
+    }
+    pub fn is_stackable(self) -> bool {
+        match self {
+            Self::HealthPotion
+            | Self::AcidPotion
+            | Self::StrengthPotion
+            | Self::FireballScroll
+            | Self::ConfusionScroll
+            | Self::LightningScroll
+            | Self::ChainLightningScroll
+            | Self::TeleportScroll
+            | Self::SummonScroll
+            | Self::MappingScroll
+            | Self::EarthquakeScroll
+            | Self::HasteScroll
+            | Self::BlindnessScroll
+            | Self::PolymorphScroll
+            | Self::TeleportOtherScroll
+            | Self::ClairvoyanceScroll
+            | Self::DetectLifeScroll
+            | Self::IdentifyScroll
+            | Self::RemoveCurseScroll
+            | Self::Ration
+            | Self::Backpack
+            | Self::Net
+            | Self::Key(_) => true,
+            Self::Pickaxe
+            | Self::FireballWand
+            | Self::Sword
+            | Self::Dagger
+            | Self::PoisonDagger
+            | Self::VampiricDagger
+            | Self::Staff
+            | Self::Shield
+            | Self::ReflectShield
+            | Self::Armour
+            | Self::LeatherArmour
+            | Self::Robe => false,
+        }
+    }
+    pub fn equip_slot(self) -> Option<EquipSlot> {
+        match self {
+            Self::Sword
+            | Self::Dagger
+            | Self::PoisonDagger
+            | Self::VampiricDagger
+            | Self::Staff => Some(EquipSlot::Weapon),
+            Self::Shield | Self::ReflectShield => Some(EquipSlot::Offhand),
+            Self::Armour | Self::LeatherArmour | Self::Robe => Some(EquipSlot::Armour),
+            Self::HealthPotion
+            | Self::AcidPotion
+            | Self::StrengthPotion
+            | Self::FireballScroll
+            | Self::ConfusionScroll
+            | Self::LightningScroll
+            | Self::ChainLightningScroll
+            | Self::TeleportScroll
+            | Self::SummonScroll
+            | Self::MappingScroll
+            | Self::EarthquakeScroll
+            | Self::HasteScroll
+            | Self::BlindnessScroll
+            | Self::PolymorphScroll
+            | Self::TeleportOtherScroll
+            | Self::ClairvoyanceScroll
+            | Self::DetectLifeScroll
+            | Self::IdentifyScroll
+            | Self::RemoveCurseScroll
+            | Self::Ration
+            | Self::Backpack
+            | Self::Net
+            | Self::Pickaxe
+            | Self::FireballWand
+            | Self::Key(_) => None,
+        }
+    }
+    // Potions and scrolls start out unidentified, and are only known by a generic name until the
+    // player uses or identifies them
+    fn starts_unidentified(self) -> bool {
+        match self {
+            Self::HealthPotion
+            | Self::AcidPotion
+            | Self::StrengthPotion
+            | Self::FireballScroll
+            | Self::ConfusionScroll
+            | Self::LightningScroll
+            | Self::ChainLightningScroll
+            | Self::TeleportScroll
+            | Self::SummonScroll
+            | Self::MappingScroll
+            | Self::EarthquakeScroll
+            | Self::HasteScroll
+            | Self::BlindnessScroll
+            | Self::PolymorphScroll
+            | Self::TeleportOtherScroll
+            | Self::ClairvoyanceScroll
+            | Self::DetectLifeScroll
+            | Self::IdentifyScroll
+            | Self::RemoveCurseScroll => true,
+            Self::Ration
+            | Self::Backpack
+            | Self::Net
+            | Self::Pickaxe
+            | Self::FireballWand
+            | Self::Sword
+            | Self::Dagger
+            | Self::PoisonDagger
+            | Self::VampiricDagger
+            | Self::Staff
+            | Self::Shield
+            | Self::ReflectShield
+            | Self::Armour
+            | Self::LeatherArmour
+            | Self::Robe
+            | Self::Key(_) => false,
+        }
+    }
+    // Two-handed weapons can't be used alongside an offhand item
+    pub fn is_two_handed(self) -> bool {
+        matches!(self, Self::Staff)
+    }
     pub fn name(self) -> &'static str {
         match self {
             Self::HealthPotion => "health potion",
+            Self::AcidPotion => "acid potion",
+            Self::StrengthPotion => "strength potion",
             Self::FireballScroll => "fireball scroll",
             Self::ConfusionScroll => "confusion scroll",
+            Self::LightningScroll => "lightning scroll",
+            Self::ChainLightningScroll => "chain lightning scroll",
+            Self::TeleportScroll => "teleport scroll",
+            Self::SummonScroll => "summon scroll",
+            Self::MappingScroll => "mapping scroll",
+            Self::EarthquakeScroll => "earthquake scroll",
+            Self::HasteScroll => "haste scroll",
+            Self::BlindnessScroll => "blindness scroll",
+            Self::PolymorphScroll => "polymorph scroll",
+            Self::TeleportOtherScroll => "teleport other scroll",
+            Self::ClairvoyanceScroll => "clairvoyance scroll",
+            Self::DetectLifeScroll => "detect life scroll",
+            Self::IdentifyScroll => "identify scroll",
+            Self::RemoveCurseScroll => "remove curse scroll",
+            Self::Ration => "ration",
+            Self::Backpack => "backpack",
+            Self::Net => "net",
+            Self::Pickaxe => "pickaxe",
+            Self::FireballWand => "fireball wand",
             Self::Sword => "sword",
+            Self::Dagger => "dagger",
+            Self::PoisonDagger => "poison dagger",
+            Self::VampiricDagger => "vampiric dagger",
             Self::Staff => "staff",
+            Self::Shield => "shield",
+            Self::ReflectShield => "reflecting shield",
             Self::Armour => "armour",
+            Self::LeatherArmour => "leather armour",
             Self::Robe => "robe",
+            Self::Key(KeyColour::Red) => "red key",
+            Self::Key(KeyColour::Green) => "green key",
+            Self::Key(KeyColour::Blue) => "blue key",
+        }
+    }
+    // How much an item of this type counts towards the weight a character can carry
+    pub fn weight(self) -> u32 {
+        match self {
+            Self::FireballScroll
+            | Self::ConfusionScroll
+            | Self::LightningScroll
+            | Self::ChainLightningScroll
+            | Self::TeleportScroll
+            | Self::SummonScroll
+            | Self::MappingScroll
+            | Self::EarthquakeScroll
+            | Self::HasteScroll
+            | Self::BlindnessScroll
+            | Self::PolymorphScroll
+            | Self::TeleportOtherScroll
+            | Self::ClairvoyanceScroll
+            | Self::DetectLifeScroll
+            | Self::IdentifyScroll
+            | Self::RemoveCurseScroll
+            | Self::FireballWand
+            | Self::Ration
+            | Self::Key(_) => 1,
+            Self::HealthPotion
+            | Self::AcidPotion
+            | Self::StrengthPotion
+            | Self::Backpack
+            | Self::Net
+            | Self::Dagger
+            | Self::PoisonDagger
+            | Self::VampiricDagger
+            | Self::Robe => 2,
+            Self::Staff => 4,
+            Self::Sword | Self::Shield | Self::ReflectShield | Self::LeatherArmour => 6,
+            Self::Pickaxe => 8,
+            Self::Armour => 12,
+        }
+    }
+    pub fn display_name(self, identified: bool) -> &'static str {
+        if identified {
+            return self.name();
+        }
+        match self {
+            Self::HealthPotion | Self::AcidPotion | Self::StrengthPotion => "unknown potion",
+            _ if self.starts_unidentified() => "unknown scroll",
+            _ => self.name(),
+        }
+    }
+}
+
+// A possible outcome of an npc dropping loot, which is only available on dungeon levels within the
+// given range. An `item` of `None` means nothing is dropped.
+#[derive(Clone, Copy, Debug)]
+pub struct LootEntry {
+    item: Option<ItemType>,
+    weight: u32,
+    min_level: u32,
+    max_level: u32,
+}
+
+impl LootEntry {
+    const fn new(item: Option<ItemType>, weight: u32) -> Self {
+        Self {
+            item,
+            weight,
+            min_level: 0,
+            max_level: u32::MAX,
+        }
+    }
+    const fn with_min_level(self, min_level: u32) -> Self {
+        Self { min_level, ..self }
+    }
+    const fn with_max_level(self, max_level: u32) -> Self {
+        Self { max_level, ..self }
+    }
+}
+
+// The weighted outcomes of an npc dropping loot
+#[derive(Clone, Copy, Debug)]
+pub struct LootTable {
+    entries: &'static [LootEntry],
+}
+
+impl LootTable {
+    // Chooses an item to drop among the entries available on `level`, or `None` if nothing
+    // should be dropped
+    fn choose<R: Rng>(self, level: u32, rng: &mut R) -> Option<ItemType> {
+        let mut available = self
+            .entries
+            .iter()
+            .filter(|entry| entry.min_level <= level && level <= entry.max_level);
+        let sum = available.clone().map(|entry| entry.weight).sum::<u32>();
+        if sum == 0 {
+            return None;
         }
+        let mut choice = rng.gen_range(0..sum);
+        available
+            .find(|entry| {
+                if choice < entry.weight {
+                    true
+                } else {
+                    choice -= entry.weight;
+                    false
+                }
+            })
+            .and_then(|entry| entry.item)
     }
 }
 
@@ -118,57 +668,262 @@ impl HitPoints {
     }
 }
 
+// A character's attack and defense ratings, before equipment and random rolls are taken into
+// account
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Stats {
+    pub attack: u32,
+    pub defense: u32,
+}
+
+impl Stats {
+    // An attack deals its full strength less the defense against it, but always at least 1 damage
+    // so no character can become invulnerable
+    fn damage(attack: u32, defense: u32) -> u32 {
+        attack.saturating_sub(defense).max(1)
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum NpcType {
     Orc,
     Troll,
+    Archer,
+    Digger,
 }
 
 impl NpcType {
+    pub const ALL: [Self; 4] = [Self::Orc, Self::Troll, Self::Archer, Self::Digger];
     pub fn name(self) -> &'static str {
         match self {
             Self::Orc => "orc",
             Self::Troll => "troll",
+            Self::Archer => "archer",
+            Self::Digger => "digger",
+        }
+    }
+    /// Items which may be dropped when an npc of this type dies
+    pub fn loot_table(self) -> LootTable {
+        use ItemType::*;
+        const ORC_LOOT: &[LootEntry] = &[
+            LootEntry::new(None, 90),
+            LootEntry::new(Some(HealthPotion), 10),
+            LootEntry::new(Some(FireballScroll), 5).with_min_level(3),
+            LootEntry::new(Some(Sword), 3).with_min_level(4),
+        ];
+        const TROLL_LOOT: &[LootEntry] = &[
+            LootEntry::new(None, 60),
+            LootEntry::new(Some(HealthPotion), 30),
+            LootEntry::new(Some(FireballScroll), 10),
+            LootEntry::new(Some(FireballWand), 5).with_min_level(3),
+            LootEntry::new(Some(VampiricDagger), 5).with_min_level(5),
+        ];
+        const ARCHER_LOOT: &[LootEntry] = &[
+            LootEntry::new(None, 90),
+            LootEntry::new(Some(Dagger), 10).with_max_level(3),
+            LootEntry::new(Some(PoisonDagger), 10).with_min_level(4),
+        ];
+        const DIGGER_LOOT: &[LootEntry] =
+            &[LootEntry::new(None, 80), LootEntry::new(Some(Pickaxe), 20)];
+        let entries = match self {
+            Self::Orc => ORC_LOOT,
+            Self::Troll => TROLL_LOOT,
+            Self::Archer => ARCHER_LOOT,
+            Self::Digger => DIGGER_LOOT,
+        };
+        LootTable { entries }
+    }
+    /// Hit points of an npc of this type before scaling for difficulty
+    fn base_hit_points(self) -> u32 {
+        match self {
+            Self::Orc => 2,
+            Self::Troll => 6,
+            Self::Archer => 2,
+            Self::Digger => 4,
+        }
+    }
+    /// Attack and defense ratings of an npc of this type
+    fn stats(self) -> Stats {
+        match self {
+            Self::Orc => Stats {
+                attack: 1,
+                defense: 0,
+            },
+            Self::Troll => Stats {
+                attack: 1,
+                defense: 0,
+            },
+            Self::Archer => Stats {
+                attack: 1,
+                defense: 0,
+            },
+            Self::Digger => Stats {
+                attack: 1,
+                defense: 0,
+            },
+        }
+    }
+    /// Strength and dexterity of an npc of this type
+    fn strength_and_dexterity(self) -> (i32, i32) {
+        match self {
+            Self::Orc => (1, 1),
+            Self::Troll => (2, 0),
+            Self::Archer => (0, 1),
+            Self::Digger => (1, 0),
+        }
+    }
+    /// Number of turns before the corpse of an npc of this type crumbles away
+    pub fn corpse_decay_duration(self) -> u32 {
+        match self {
+            Self::Orc => 50,
+            Self::Troll => 100,
+            Self::Archer => 50,
+            Self::Digger => 50,
+        }
+    }
+    /// Npcs of this type flee from the player once their hit points drop to this value, which is
+    /// always below their full hit points. A threshold of 0 means they never flee.
+    pub fn flee_threshold(self) -> u32 {
+        match self {
+            Self::Orc => 1,
+            Self::Troll => 0,
+            Self::Archer => 1,
+            Self::Digger => 0,
+        }
+    }
+    /// Hit points restored to npcs of this type at the end of every turn
+    pub fn regeneration(self) -> u32 {
+        match self {
+            Self::Troll => 1,
+            Self::Orc | Self::Archer | Self::Digger => 0,
+        }
+    }
+    /// The number of actions npcs of this type take for each turn taken by the player
+    pub fn speed(self) -> u32 {
+        match self {
+            Self::Orc | Self::Troll | Self::Archer => 1,
+            Self::Digger => 2,
         }
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Faction {
+    Player,
+    Hostile,
+    Neutral,
+}
+
+impl Faction {
+    pub fn is_hostile_to(self, other: Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::Player, Self::Hostile) | (Self::Hostile, Self::Player)
+        )
+    }
+}
+
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum Tile {
     Player,
     PlayerCorpse,
     Floor,
     Wall,
+    SecretWall,
     Npc(NpcType),
     NpcCorpse(NpcType),
     Item(ItemType),
     Projectile(ProjectileType),
     Stairs,
+    DoorClosed,
+    DoorOpen,
+    LockedDoor(KeyColour),
+    Trap,
+    Gold,
+    Fire,
 }
 
 entity_table::declare_entity_module! {
     components {
         tile: Tile,
         npc_type: NpcType,
+        faction: Faction,
         hit_points: HitPoints,
         item: ItemType,
         inventory: Inventory,
-        trajectory: CardinalStepIter,
+        trajectory: Trajectory,
+        carried_item: Entity,
+        piercing: u32,
         projectile: ProjectileType,
         confusion_countdown: u32,
+        haste: u32,
+        blind: u32,
+        rooted: u32,
+        speed: u32,
+        poison: u32,
+        bleed: u32,
+        knockback: (),
+        reflect: (),
+        crit_chance: u32,
+        identified_item_types: BTreeSet<ItemType>,
+        nutrition: u32,
+        durability: u32,
+        charges: u32,
+        decay: u32,
         stairs: (),
-        base_damage: i32,
+        door: bool,
+        trap: u32,
+        hidden: bool,
+        lock: KeyColour,
+        cursed: bool,
+        lifesteal: u32,
+        fire: u32,
+        gold_pile: u32,
+        gold: u32,
+        stats: Stats,
+        weapon_damage: i32,
+        weapon_poison: u32,
+        weapon_bleed: u32,
+        armour_defense: i32,
         strength: i32,
         dexterity: i32,
         intelligence: i32,
-        equipment_worn_inventory_index: usize,
-        equipment_held_inventory_index: usize,
+        equipment: EquipmentSlots,
+        tag: EntityTag,
+        low_health_warned: (),
     }
 }
 
 use components::Components;
 pub use components::EntityData;
 
+const HEALTH_TO_HEAL: u32 = 5;
+const ACID_DAMAGE: u32 = 4;
+const SPLASH_RADIUS: u32 = 1;
+const LIGHTNING_RANGE: u32 = 8;
+const DIRECT_AIM_RANGE_SQUARED: u32 = 100;
+const CHAIN_LIGHTNING_RADIUS: u32 = 3;
+const CHAIN_LIGHTNING_JUMPS: u32 = 3;
+const MAX_NUTRITION: u32 = 1000;
+const RATION_NUTRITION: u32 = 600;
+const PICKAXE_DURABILITY: u32 = 5;
+const FIREBALL_WAND_CHARGES: u32 = 3;
+const BACKPACK_CAPACITY: usize = 5;
+const BASE_CARRY_CAPACITY: u32 = 40;
+const CARRY_CAPACITY_PER_STRENGTH: u32 = 10;
+const MAX_INVENTORY_CAPACITY: usize = 20;
+const VAMPIRIC_DAGGER_LIFESTEAL: u32 = 50;
+const TRAP_DAMAGE: u32 = 2;
+const SEARCH_PERCENT: u32 = 30;
+const EARTHQUAKE_RADIUS: u32 = 4;
+const EARTHQUAKE_COLLAPSE_PERCENT: u32 = 50;
+const EARTHQUAKE_RAISE_PERCENT: u32 = 10;
+const HASTE_DURATION: u32 = 20;
+const NET_DURATION: u32 = 5;
+const FIRE_DURATION: u32 = 5;
+const FIRE_DAMAGE: u32 = 1;
+const FIRE_SPREAD_PERCENT: u32 = 10;
+
 spatial_table::declare_layers_module! {
     layers {
         floor: Floor,
@@ -188,11 +943,191 @@ pub struct World {
     pub entity_allocator: EntityAllocator,
     pub components: Components,
     pub spatial_table: SpatialTable,
+    turn: u64,
+    // The dungeon level most recently populated, which determines the loot dropped by npcs
+    level: u32,
+    // Damage dealt during the current and previous turns, for the frontend to display
+    #[serde(skip)]
+    damage_events: Vec<DamageEvent>,
+}
+
+// The cause of some damage dealt to a character
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DamageKind {
+    Melee,
+    Projectile,
+    Magic,
+    Fire,
+    Poison,
+    Bleed,
+    Acid,
+    Trap,
+    Collision,
+    Starvation,
+}
+
+// A record of a character at `coord` losing `amount` hit points on the given turn
+#[derive(Clone, Copy, Debug)]
+pub struct DamageEvent {
+    pub coord: Coord,
+    pub amount: u32,
+    pub kind: DamageKind,
+    pub turn: u64,
+}
+
+pub struct PlayerConfig {
+    pub max_hit_points: u32,
+    pub strength: i32,
+    pub dexterity: i32,
+    pub intelligence: i32,
+    pub inventory_capacity: usize,
+    // Starting items are added to the inventory in order. Any items which don't fit in the
+    // inventory are silently discarded.
+    pub starting_items: Vec<ItemType>,
+}
+
+impl Default for PlayerConfig {
+    fn default() -> Self {
+        Self {
+            max_hit_points: 20,
+            strength: 1,
+            dexterity: 1,
+            intelligence: 1,
+            inventory_capacity: 10,
+            starting_items: Vec::new(),
+        }
+    }
 }
 
 pub struct Populate {
     pub player_entity: Entity,
     pub ai_state: ComponentTable<Agent>,
+    pub spawn_errors: Vec<SpawnError>,
+}
+
+#[derive(Debug)]
+pub enum SpawnError {
+    Occupied {
+        coord: Coord,
+        layer: Layer,
+        occupied_by: Entity,
+    },
+    OutOfBounds {
+        coord: Coord,
+    },
+}
+
+// Scales the hit points of spawned npcs so that monsters on deeper levels are tougher. A scale of
+// 1.0 spawns npcs with their base hit points.
+#[derive(Clone, Copy, Debug)]
+pub struct DifficultyScale {
+    pub hit_points_multiplier: f32,
+}
+
+impl Default for DifficultyScale {
+    fn default() -> Self {
+        Self {
+            hit_points_multiplier: 1.0,
+        }
+    }
+}
+
+impl DifficultyScale {
+    // Each level past the first adds 10% to the hit points of npcs
+    pub fn for_level(level: u32) -> Self {
+        Self {
+            hit_points_multiplier: 1.0 + 0.1 * level.saturating_sub(1) as f32,
+        }
+    }
+    fn scale_hit_points(self, base: u32) -> u32 {
+        ((base as f32 * self.hit_points_multiplier).round() as u32).max(1)
+    }
+}
+
+#[derive(Debug)]
+pub struct LoadError(serde_json::Error);
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "can't load world: {}", self.0)
+    }
+}
+
+impl fmt::Display for SpawnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Occupied {
+                coord,
+                layer,
+                occupied_by,
+            } => write!(
+                f,
+                "can't spawn entity on {:?} layer at ({}, {}): already occupied by {:?}",
+                layer, coord.x, coord.y, occupied_by
+            ),
+            Self::OutOfBounds { coord } => write!(
+                f,
+                "can't spawn entity at ({}, {}): out of bounds",
+                coord.x, coord.y
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoveResult {
+    Moved,
+    Attacked { killed: bool },
+    OpenedDoor,
+    // The player bumped into a neutral npc and spoke to it instead of attacking
+    Talked,
+    // The character stayed where it was without attacking
+    Blocked,
+}
+
+// The cells a projectile has yet to pass through on a straight line towards its target. Unlike a
+// sequence of cardinal steps, this moves diagonally where necessary, so a projectile always ends up
+// on the exact cell it was aimed at.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Trajectory {
+    // Stored in reverse order so the next cell can be popped off the end
+    remaining: Vec<Coord>,
+    origin: Coord,
+    target: Coord,
+}
+
+impl Trajectory {
+    // A trajectory from a cell to itself is empty
+    fn new(from: Coord, to: Coord) -> Self {
+        let mut remaining = LineSegment::try_new(from, to)
+            .map(|line_segment| {
+                line_segment
+                    .iter()
+                    .filter(|&coord| coord != from)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        remaining.reverse();
+        Self {
+            remaining,
+            origin: from,
+            target: to,
+        }
+    }
+    // Sends the projectile from `current` back towards the cell it was launched from
+    fn reflect(&mut self, current: Coord) {
+        *self = Self {
+            origin: self.target,
+            ..Self::new(current, self.origin)
+        };
+    }
+}
+
+impl Iterator for Trajectory {
+    type Item = Coord;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.remaining.pop()
+    }
 }
 
 enum BumpAttackOutcome {
@@ -212,198 +1147,519 @@ impl World {
             entity_allocator,
             components,
             spatial_table,
+            turn: 0,
+            level: 0,
+            damage_events: Vec::new(),
         }
     }
+    pub fn advance_turn(&mut self) {
+        self.turn += 1;
+        // Damage is displayed for the turn it was dealt on and the following turn, so damage dealt
+        // by the player is still visible after the npcs have responded
+        let turn = self.turn;
+        self.damage_events.retain(|event| event.turn + 1 >= turn);
+    }
     pub fn clear(&mut self) {
         self.entity_allocator.clear();
         self.components.clear();
         self.spatial_table.clear();
+        self.damage_events.clear();
     }
-    fn spawn_wall(&mut self, coord: Coord) {
-        let entity = self.entity_allocator.alloc();
-        self.spatial_table
-            .update(
-                entity,
-                Location {
+    pub fn damage_events(&self) -> &[DamageEvent] {
+        &self.damage_events
+    }
+    // Serializes the entire world, including the positions and remaining trajectories of any
+    // projectiles in flight. Damage events are only for display and aren't saved.
+    pub fn save(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("failed to serialize world")
+    }
+    pub fn load(bytes: &[u8]) -> Result<Self, LoadError> {
+        serde_json::from_slice(bytes).map_err(LoadError)
+    }
+    // Places a newly created entity in the world. If the location is out of bounds or already
+    // occupied, the entity is removed from the world entirely.
+    fn place_new_entity(
+        &mut self,
+        entity: Entity,
+        coord: Coord,
+        layer: Layer,
+    ) -> Result<(), SpawnError> {
+        let error = if coord.is_valid(self.spatial_table.grid_size()) {
+            let location = Location {
+                coord,
+                layer: Some(layer),
+            };
+            match self.spatial_table.update(entity, location) {
+                Ok(_) => return Ok(()),
+                Err(e) => SpawnError::Occupied {
                     coord,
-                    layer: Some(Layer::Feature),
+                    layer,
+                    occupied_by: e.unwrap_occupied_by(),
                 },
-            )
-            .unwrap();
+            }
+        } else {
+            SpawnError::OutOfBounds { coord }
+        };
+        self.remove_entity(entity);
+        Err(error)
+    }
+    fn spawn_wall(&mut self, coord: Coord) -> Result<Entity, SpawnError> {
+        let entity = self.entity_allocator.alloc();
+        self.place_new_entity(entity, coord, Layer::Feature)?;
         self.components.tile.insert(entity, Tile::Wall);
+        Ok(entity)
     }
-    fn spawn_floor(&mut self, coord: Coord) {
+    // A secret wall behaves like a wall until it's found by searching
+    fn spawn_secret_wall(&mut self, coord: Coord) -> Result<Entity, SpawnError> {
         let entity = self.entity_allocator.alloc();
-        self.spatial_table
-            .update(
-                entity,
-                Location {
-                    coord,
-                    layer: Some(Layer::Floor),
-                },
-            )
-            .unwrap();
+        self.place_new_entity(entity, coord, Layer::Feature)?;
+        self.components.tile.insert(entity, Tile::SecretWall);
+        Ok(entity)
+    }
+    fn spawn_door(&mut self, coord: Coord) -> Result<Entity, SpawnError> {
+        let entity = self.entity_allocator.alloc();
+        self.place_new_entity(entity, coord, Layer::Feature)?;
+        self.components.tile.insert(entity, Tile::DoorClosed);
+        self.components.door.insert(entity, false);
+        Ok(entity)
+    }
+    // A locked door is a closed door which can't be opened until it's unlocked
+    fn spawn_locked_door(&mut self, coord: Coord, colour: KeyColour) -> Result<Entity, SpawnError> {
+        let entity = self.entity_allocator.alloc();
+        self.place_new_entity(entity, coord, Layer::Feature)?;
+        self.components
+            .tile
+            .insert(entity, Tile::LockedDoor(colour));
+        self.components.door.insert(entity, false);
+        self.components.lock.insert(entity, colour);
+        Ok(entity)
+    }
+    fn spawn_trap(&mut self, coord: Coord) -> Result<Entity, SpawnError> {
+        let entity = self.entity_allocator.alloc();
+        self.place_new_entity(entity, coord, Layer::Feature)?;
+        self.components.tile.insert(entity, Tile::Trap);
+        self.components.trap.insert(entity, TRAP_DAMAGE);
+        self.components.hidden.insert(entity, true);
+        Ok(entity)
+    }
+    // Sets a floor cell alight. Fails if there's no floor at `coord` or it's already occupied by a
+    // feature.
+    fn ignite(&mut self, coord: Coord, duration: u32) -> Result<(), ()> {
+        let layers = self.spatial_table.layers_at(coord).ok_or(())?;
+        if layers.floor.is_none() || layers.feature.is_some() {
+            return Err(());
+        }
+        let entity = self.entity_allocator.alloc();
+        self.place_new_entity(entity, coord, Layer::Feature)
+            .map_err(|_| ())?;
+        self.components.tile.insert(entity, Tile::Fire);
+        self.components.fire.insert(entity, duration);
+        Ok(())
+    }
+    fn spawn_floor(&mut self, coord: Coord) -> Result<Entity, SpawnError> {
+        let entity = self.entity_allocator.alloc();
+        self.place_new_entity(entity, coord, Layer::Floor)?;
         self.components.tile.insert(entity, Tile::Floor);
+        Ok(entity)
     }
-    fn spawn_player(&mut self, coord: Coord) -> Entity {
+    fn spawn_player(
+        &mut self,
+        coord: Coord,
+        player_config: &PlayerConfig,
+    ) -> Result<Entity, SpawnError> {
         let entity = self.entity_allocator.alloc();
-        self.spatial_table
-            .update(
-                entity,
-                Location {
-                    coord,
-                    layer: Some(Layer::Character),
-                },
-            )
-            .unwrap();
+        self.place_new_entity(entity, coord, Layer::Character)?;
         self.components.tile.insert(entity, Tile::Player);
+        self.components.faction.insert(entity, Faction::Player);
         self.components
             .hit_points
-            .insert(entity, HitPoints::new_full(20));
-        self.components.base_damage.insert(entity, 1);
-        self.components.strength.insert(entity, 1);
-        self.components.dexterity.insert(entity, 1);
-        self.components.intelligence.insert(entity, 1);
-        self.components.inventory.insert(entity, Inventory::new(10));
-        entity
+            .insert(entity, HitPoints::new_full(player_config.max_hit_points));
+        self.components.stats.insert(
+            entity,
+            Stats {
+                attack: 1,
+                defense: 0,
+            },
+        );
+        self.components
+            .strength
+            .insert(entity, player_config.strength);
+        self.components
+            .dexterity
+            .insert(entity, player_config.dexterity);
+        self.components
+            .intelligence
+            .insert(entity, player_config.intelligence);
+        self.components.nutrition.insert(entity, MAX_NUTRITION);
+        self.components.gold.insert(entity, 0);
+        self.components
+            .identified_item_types
+            .insert(entity, BTreeSet::new());
+        let mut inventory = Inventory::new(player_config.inventory_capacity);
+        for &item_type in player_config.starting_items.iter() {
+            let item = self.create_item(item_type);
+            if inventory.insert(item, item_type).is_err() {
+                self.remove_entity(item);
+            }
+        }
+        self.components.inventory.insert(entity, inventory);
+        Ok(entity)
     }
-    fn spawn_npc(&mut self, coord: Coord, npc_type: NpcType) -> Entity {
+    fn spawn_npc(
+        &mut self,
+        coord: Coord,
+        npc_type: NpcType,
+        difficulty_scale: DifficultyScale,
+    ) -> Result<Entity, SpawnError> {
         let entity = self.entity_allocator.alloc();
-        self.spatial_table
-            .update(
-                entity,
-                Location {
-                    coord,
-                    layer: Some(Layer::Character),
-                },
-            )
-            .unwrap();
+        self.place_new_entity(entity, coord, Layer::Character)?;
         self.components.tile.insert(entity, Tile::Npc(npc_type));
         self.components.npc_type.insert(entity, npc_type);
-        let hit_points = match npc_type {
-            NpcType::Orc => HitPoints::new_full(2),
-            NpcType::Troll => HitPoints::new_full(6),
-        };
-        self.components.hit_points.insert(entity, hit_points);
-        self.components.base_damage.insert(entity, 1);
-        let (strength, dexterity) = match npc_type {
-            NpcType::Orc => (1, 1),
-            NpcType::Troll => (2, 0),
-        };
+        self.components.faction.insert(entity, Faction::Hostile);
+        self.components.hit_points.insert(
+            entity,
+            HitPoints::new_full(difficulty_scale.scale_hit_points(npc_type.base_hit_points())),
+        );
+        self.components.stats.insert(entity, npc_type.stats());
+        let (strength, dexterity) = npc_type.strength_and_dexterity();
         self.components.strength.insert(entity, strength);
         self.components.dexterity.insert(entity, dexterity);
-        entity
+        if npc_type == NpcType::Troll {
+            self.components.knockback.insert(entity, ());
+        }
+        self.components.speed.insert(entity, npc_type.speed());
+        Ok(entity)
     }
-    fn spawn_item(&mut self, coord: Coord, item_type: ItemType) {
+    // Creates an item which is not yet located anywhere in the world
+    fn create_item(&mut self, item_type: ItemType) -> Entity {
         let entity = self.entity_allocator.alloc();
-        self.spatial_table
-            .update(
-                entity,
-                Location {
-                    coord,
-                    layer: Some(Layer::Object),
-                },
-            )
-            .unwrap();
         self.components.tile.insert(entity, Tile::Item(item_type));
         self.components.item.insert(entity, item_type);
+        match item_type {
+            ItemType::Sword => {
+                self.components.weapon_damage.insert(entity, 1);
+                self.components.weapon_bleed.insert(entity, 1);
+            }
+            ItemType::Dagger => {
+                self.components.weapon_damage.insert(entity, 1);
+                self.components.crit_chance.insert(entity, 10);
+            }
+            ItemType::PoisonDagger => {
+                self.components.weapon_damage.insert(entity, 1);
+                self.components.weapon_poison.insert(entity, 3);
+                self.components.crit_chance.insert(entity, 10);
+            }
+            ItemType::VampiricDagger => {
+                self.components.weapon_damage.insert(entity, 1);
+                self.components
+                    .lifesteal
+                    .insert(entity, VAMPIRIC_DAGGER_LIFESTEAL);
+            }
+            ItemType::Staff => {
+                self.components.knockback.insert(entity, ());
+            }
+            ItemType::Pickaxe => {
+                self.components
+                    .durability
+                    .insert(entity, PICKAXE_DURABILITY);
+            }
+            ItemType::FireballWand => {
+                self.components
+                    .charges
+                    .insert(entity, FIREBALL_WAND_CHARGES);
+            }
+            ItemType::Armour => {
+                self.components.armour_defense.insert(entity, 1);
+            }
+            ItemType::LeatherArmour => {
+                self.components.armour_defense.insert(entity, 1);
+            }
+            ItemType::Shield => {
+                self.components.armour_defense.insert(entity, 1);
+            }
+            ItemType::ReflectShield => {
+                self.components.armour_defense.insert(entity, 1);
+                self.components.reflect.insert(entity, ());
+            }
+            _ => (),
+        }
+        entity
     }
-    fn spawn_projectile(&mut self, from: Coord, to: Coord, projectile_type: ProjectileType) {
+    fn spawn_item(&mut self, coord: Coord, item_type: ItemType) -> Result<Entity, SpawnError> {
+        let entity = self.create_item(item_type);
+        self.place_new_entity(entity, coord, Layer::Object)?;
+        Ok(entity)
+    }
+    fn spawn_gold(&mut self, coord: Coord, amount: u32) -> Result<Entity, SpawnError> {
         let entity = self.entity_allocator.alloc();
-        self.spatial_table
-            .update(
-                entity,
-                Location {
-                    coord: from,
-                    layer: Some(Layer::Projectile),
-                },
-            )
-            .unwrap();
+        self.place_new_entity(entity, coord, Layer::Object)?;
+        self.components.tile.insert(entity, Tile::Gold);
+        self.components.gold_pile.insert(entity, amount);
+        Ok(entity)
+    }
+    // Gives an npc a single piece of armour, which it wears
+    fn equip_npc_armour(&mut self, entity: Entity, item_type: ItemType) {
+        let item_entity = self.create_item(item_type);
+        self.components.equipment.insert(
+            entity,
+            EquipmentSlots {
+                armour: Some(item_entity),
+                ..Default::default()
+            },
+        );
+    }
+    fn spawn_projectile(
+        &mut self,
+        from: Coord,
+        to: Coord,
+        projectile_type: ProjectileType,
+    ) -> Result<Entity, SpawnError> {
+        let entity = self.entity_allocator.alloc();
+        self.place_new_entity(entity, from, Layer::Projectile)?;
         self.components
             .tile
             .insert(entity, Tile::Projectile(projectile_type));
         self.components.projectile.insert(entity, projectile_type);
         self.components
             .trajectory
-            .insert(entity, CardinalStepIter::new(to - from));
+            .insert(entity, Trajectory::new(from, to));
+        Ok(entity)
     }
-    fn spawn_stairs(&mut self, coord: Coord) {
+    fn spawn_stairs(&mut self, coord: Coord) -> Result<Entity, SpawnError> {
         let entity = self.entity_allocator.alloc();
-        self.spatial_table
-            .update(
-                entity,
-                Location {
-                    coord,
-                    layer: Some(Layer::Floor),
-                },
-            )
-            .unwrap();
+        self.place_new_entity(entity, coord, Layer::Floor)?;
         self.components.tile.insert(entity, Tile::Stairs);
         self.components.stairs.insert(entity, ());
+        Ok(entity)
+    }
+    // Populates the level using a random number generator seeded with `seed`, so the same seed
+    // always produces the same level. The seed is returned alongside the result so it can be
+    // reported to the player. This relies on `generate_dungeon` and `populate` drawing from the
+    // rng in the same order every time.
+    pub fn populate_seeded(
+        &mut self,
+        level: u32,
+        player_config: &PlayerConfig,
+        monster_spawn_table: &MonsterSpawnTable,
+        generation_config: &GenerationConfig,
+        difficulty_scale: DifficultyScale,
+        seed: u64,
+    ) -> (Populate, u64) {
+        let mut rng = Isaac64Rng::seed_from_u64(seed);
+        let populate = self.populate(
+            level,
+            player_config,
+            monster_spawn_table,
+            generation_config,
+            difficulty_scale,
+            &mut rng,
+        );
+        (populate, seed)
     }
-    pub fn populate<R: Rng>(&mut self, level: u32, rng: &mut R) -> Populate {
-        let terrain = terrain::generate_dungeon(self.spatial_table.grid_size(), level, rng);
+    pub fn populate<R: Rng>(
+        &mut self,
+        level: u32,
+        player_config: &PlayerConfig,
+        monster_spawn_table: &MonsterSpawnTable,
+        generation_config: &GenerationConfig,
+        difficulty_scale: DifficultyScale,
+        rng: &mut R,
+    ) -> Populate {
+        self.level = level;
+        let terrain = terrain::generate_dungeon(
+            self.spatial_table.grid_size(),
+            level,
+            monster_spawn_table,
+            generation_config,
+            rng,
+        );
         let mut player_entity = None;
         let mut ai_state = ComponentTable::default();
+        let mut spawn_errors = Vec::new();
         for (coord, &terrain_tile) in terrain.enumerate() {
-            match terrain_tile {
-                TerrainTile::Player => {
-                    self.spawn_floor(coord);
-                    player_entity = Some(self.spawn_player(coord));
-                }
-                TerrainTile::Floor => self.spawn_floor(coord),
-                TerrainTile::Stairs => self.spawn_stairs(coord),
-                TerrainTile::Wall => {
-                    self.spawn_floor(coord);
-                    self.spawn_wall(coord);
-                }
-                TerrainTile::Npc(npc_type) => {
-                    let entity = self.spawn_npc(coord, npc_type);
-                    self.spawn_floor(coord);
-                    ai_state.insert(entity, Agent::new());
-                }
-                TerrainTile::Item(item_type) => {
-                    self.spawn_item(coord, item_type);
-                    self.spawn_floor(coord);
-                }
+            if let Err(spawn_error) = self.populate_cell(
+                coord,
+                terrain_tile,
+                player_config,
+                difficulty_scale,
+                &mut player_entity,
+                &mut ai_state,
+            ) {
+                spawn_errors.push(spawn_error);
             }
         }
         Populate {
-            player_entity: player_entity.unwrap(),
+            player_entity: player_entity.expect("failed to spawn player"),
             ai_state,
+            spawn_errors,
+        }
+    }
+    // Nothing in the game tags entities yet, so this is only exercised by tests until scripted
+    // content starts to use it
+    #[allow(dead_code)]
+    pub fn entities_with_tag(&self, tag: EntityTag) -> Vec<Entity> {
+        self.components
+            .tag
+            .iter()
+            .filter(|&(_, &entity_tag)| entity_tag == tag)
+            .map(|(entity, _)| entity)
+            .collect()
+    }
+    fn populate_cell(
+        &mut self,
+        coord: Coord,
+        terrain_tile: TerrainTile,
+        player_config: &PlayerConfig,
+        difficulty_scale: DifficultyScale,
+        player_entity: &mut Option<Entity>,
+        ai_state: &mut ComponentTable<Agent>,
+    ) -> Result<(), SpawnError> {
+        match terrain_tile {
+            TerrainTile::Player => {
+                self.spawn_floor(coord)?;
+                *player_entity = Some(self.spawn_player(coord, player_config)?);
+            }
+            TerrainTile::Floor => {
+                self.spawn_floor(coord)?;
+            }
+            TerrainTile::Stairs => {
+                self.spawn_stairs(coord)?;
+            }
+            TerrainTile::Door => {
+                self.spawn_floor(coord)?;
+                self.spawn_door(coord)?;
+            }
+            TerrainTile::LockedDoor(colour) => {
+                self.spawn_floor(coord)?;
+                self.spawn_locked_door(coord, colour)?;
+            }
+            TerrainTile::Trap => {
+                self.spawn_floor(coord)?;
+                self.spawn_trap(coord)?;
+            }
+            TerrainTile::Wall => {
+                self.spawn_floor(coord)?;
+                self.spawn_wall(coord)?;
+            }
+            TerrainTile::SecretWall => {
+                self.spawn_floor(coord)?;
+                self.spawn_secret_wall(coord)?;
+            }
+            TerrainTile::Npc(npc_type) => {
+                self.spawn_floor(coord)?;
+                let entity = self.spawn_npc(coord, npc_type, difficulty_scale)?;
+                ai_state.insert(entity, Agent::new_asleep());
+            }
+            TerrainTile::ArmouredNpc(npc_type) => {
+                self.spawn_floor(coord)?;
+                let entity = self.spawn_npc(coord, npc_type, difficulty_scale)?;
+                self.equip_npc_armour(entity, ItemType::LeatherArmour);
+                ai_state.insert(entity, Agent::new_asleep());
+            }
+            TerrainTile::Item(item_type) => {
+                self.spawn_floor(coord)?;
+                self.spawn_item(coord, item_type)?;
+            }
+            TerrainTile::CursedItem(item_type) => {
+                self.spawn_floor(coord)?;
+                let entity = self.spawn_item(coord, item_type)?;
+                self.components.cursed.insert(entity, true);
+            }
+            TerrainTile::Gold(amount) => {
+                self.spawn_floor(coord)?;
+                self.spawn_gold(coord, amount)?;
+            }
+        }
+        Ok(())
+    }
+    // Attacks every character hostile to the attacker in the eight cells around it as a single
+    // action. Only characters wielding a two-handed weapon can make a sweeping attack. Returns the
+    // number of characters attacked.
+    pub fn sweep_attack<R: Rng>(
+        &mut self,
+        attacker: Entity,
+        message_log: &mut Vec<LogMessage>,
+        rng: &mut R,
+    ) -> Result<usize, ()> {
+        let wields_two_handed = self
+            .equipped_in_slot(attacker, EquipSlot::Weapon)
+            .map(ItemType::is_two_handed)
+            .unwrap_or(false);
+        if !wields_two_handed {
+            message_log.push(LogMessage::CannotSweep);
+            return Err(());
+        }
+        let coord = self.spatial_table.coord_of(attacker).ok_or(())?;
+        // Victims are chosen up front so a character knocked back into another adjacent cell
+        // isn't attacked twice
+        let victims = Direction::all()
+            .filter_map(|direction| {
+                self.spatial_table
+                    .layers_at(coord + direction.coord())?
+                    .character
+                    .filter(|&character| self.is_hostile_to(attacker, character))
+                    .map(|character| (character, direction))
+            })
+            .collect::<Vec<_>>();
+        let mut num_attacked = 0;
+        for (victim, direction) in victims {
+            if !self.is_living_character(victim) {
+                continue;
+            }
+            let outcome = self.character_bump_attack(victim, attacker, direction, message_log, rng);
+            Self::write_combat_log_messages(
+                self.npc_type(attacker),
+                self.npc_type(victim),
+                outcome,
+                message_log,
+            );
+            num_attacked += 1;
         }
+        Ok(num_attacked)
     }
     fn write_combat_log_messages(
-        attacker_is_player: bool,
+        attacker_npc_type: Option<NpcType>,
+        victim_npc_type: Option<NpcType>,
         outcome: BumpAttackOutcome,
-        npc_type: NpcType,
         message_log: &mut Vec<LogMessage>,
     ) {
-        if attacker_is_player {
-            match outcome {
+        match (attacker_npc_type, victim_npc_type) {
+            (None, Some(npc_type)) => match outcome {
                 BumpAttackOutcome::Kill => message_log.push(LogMessage::PlayerKillsNpc(npc_type)),
                 BumpAttackOutcome::Hit => message_log.push(LogMessage::PlayerAttacksNpc(npc_type)),
                 BumpAttackOutcome::Dodge => message_log.push(LogMessage::NpcDodges(npc_type)),
-            }
-        } else {
-            match outcome {
+            },
+            (Some(npc_type), None) => match outcome {
                 BumpAttackOutcome::Kill => message_log.push(LogMessage::NpcKillsPlayer(npc_type)),
                 BumpAttackOutcome::Hit => message_log.push(LogMessage::NpcAttacksPlayer(npc_type)),
                 BumpAttackOutcome::Dodge => message_log.push(LogMessage::PlayerDodges(npc_type)),
-            }
+            },
+            (Some(attacker), Some(victim)) => match outcome {
+                BumpAttackOutcome::Kill => {
+                    message_log.push(LogMessage::NpcKillsNpc(attacker, victim))
+                }
+                BumpAttackOutcome::Hit => {
+                    message_log.push(LogMessage::NpcAttacksNpc(attacker, victim))
+                }
+                BumpAttackOutcome::Dodge => {
+                    message_log.push(LogMessage::NpcDodgesNpc(attacker, victim))
+                }
+            },
+            (None, None) => (),
         }
     }
     pub fn maybe_move_character<R: Rng>(
         &mut self,
         character_entity: Entity,
-        direction: CardinalDirection,
+        direction: Direction,
         message_log: &mut Vec<LogMessage>,
         rng: &mut R,
-    ) {
+    ) -> MoveResult {
         let character_coord = self
             .spatial_table
             .coord_of(character_entity)
             .expect("character has no coord");
+        let is_confused = self.is_confused(character_entity);
         let direction = if let Some(confusion_countdown) = self
             .components
             .confusion_countdown
@@ -417,596 +1673,6134 @@ impl World {
             } else {
                 *confusion_countdown -= 1;
             }
-            rng.gen()
+            rng.gen::<CardinalDirection>().direction()
         } else {
             direction
         };
         let new_character_coord = character_coord + direction.coord();
-        if new_character_coord.is_valid(self.spatial_table.grid_size()) {
-            let dest_layers = self.spatial_table.layers_at_checked(new_character_coord);
-            if let Some(dest_character_entity) = dest_layers.character {
-                let character_is_npc = self.components.npc_type.get(character_entity).cloned();
-                let dest_character_is_npc =
-                    self.components.npc_type.get(dest_character_entity).cloned();
-                if character_is_npc.is_some() != dest_character_is_npc.is_some() {
-                    let outcome =
-                        self.character_bump_attack(dest_character_entity, character_entity, rng);
-                    let npc_type = character_is_npc.or(dest_character_is_npc).unwrap();
-                    Self::write_combat_log_messages(
-                        character_is_npc.is_none(),
-                        outcome,
-                        npc_type,
-                        message_log,
-                    );
+        if !new_character_coord.is_valid(self.spatial_table.grid_size()) {
+            return MoveResult::Blocked;
+        }
+        let dest_layers = self.spatial_table.layers_at_checked(new_character_coord);
+        if let Some(dest_character_entity) = dest_layers.character {
+            // Confused characters lash out at whoever they stumble into, regardless of faction
+            if is_confused || self.is_hostile_to(character_entity, dest_character_entity) {
+                let outcome = self.character_bump_attack(
+                    dest_character_entity,
+                    character_entity,
+                    direction,
+                    message_log,
+                    rng,
+                );
+                let killed = match outcome {
+                    BumpAttackOutcome::Kill => true,
+                    BumpAttackOutcome::Hit | BumpAttackOutcome::Dodge => false,
+                };
+                Self::write_combat_log_messages(
+                    self.npc_type(character_entity),
+                    self.npc_type(dest_character_entity),
+                    outcome,
+                    message_log,
+                );
+                MoveResult::Attacked { killed }
+            } else if self.faction(character_entity) == Some(Faction::Player)
+                && self.faction(dest_character_entity) == Some(Faction::Neutral)
+            {
+                if let Some(npc_type) = self.npc_type(dest_character_entity) {
+                    message_log.push(LogMessage::PlayerTalksTo(npc_type));
+                }
+                MoveResult::Talked
+            } else if !self.is_rooted(character_entity)
+                && self.maybe_swap_places(character_entity, direction).is_ok()
+            {
+                self.aggravate_bleeding(character_entity);
+                MoveResult::Moved
+            } else {
+                MoveResult::Blocked
+            }
+        } else if self.is_rooted(character_entity) {
+            // A rooted character can still attack its neighbours, but can't go anywhere
+            MoveResult::Blocked
+        } else if let Some(feature) = dest_layers.feature {
+            if self.components.lock.contains(feature) {
+                // Only the player carries keys, so npcs are always stopped by locked doors
+                if !self.components.npc_type.contains(character_entity)
+                    && self
+                        .try_unlock_door(character_entity, new_character_coord, message_log)
+                        .is_ok()
+                {
+                    MoveResult::OpenedDoor
+                } else {
+                    MoveResult::Blocked
                 }
-            } else if dest_layers.feature.is_none() {
+            } else if self.components.door.get(feature) == Some(&false) {
+                // Walking into a closed door opens it
+                self.components.door.insert(feature, true);
+                self.components.tile.insert(feature, Tile::DoorOpen);
+                if !self.components.npc_type.contains(character_entity) {
+                    message_log.push(LogMessage::PlayerOpensDoor);
+                }
+                MoveResult::OpenedDoor
+            } else if self.is_feature_solid(feature) {
+                MoveResult::Blocked
+            } else {
                 self.spatial_table
                     .update_coord(character_entity, new_character_coord)
                     .unwrap();
+                self.aggravate_bleeding(character_entity);
+                if self.components.trap.contains(feature) {
+                    self.trigger_trap(character_entity, feature, message_log, rng);
+                }
+                MoveResult::Moved
             }
+        } else {
+            self.spatial_table
+                .update_coord(character_entity, new_character_coord)
+                .unwrap();
+            self.aggravate_bleeding(character_entity);
+            MoveResult::Moved
         }
     }
-    fn inventory_item_type(&self, entity: Entity, index: usize) -> Option<ItemType> {
-        self.components.inventory.get(entity).and_then(|inventory| {
-            inventory
-                .get(index)
-                .ok()
-                .and_then(|held_entity| self.components.item.get(held_entity).cloned())
-        })
+    // Opens the locked door at `coord` if the player holds a key of the matching colour. The key is
+    // used up.
+    pub fn try_unlock_door(
+        &mut self,
+        player: Entity,
+        coord: Coord,
+        message_log: &mut Vec<LogMessage>,
+    ) -> Result<(), ()> {
+        let door = self
+            .spatial_table
+            .layers_at(coord)
+            .and_then(|layers| layers.feature)
+            .ok_or(())?;
+        let &colour = self.components.lock.get(door).ok_or(())?;
+        let key = self
+            .components
+            .inventory
+            .get_mut(player)
+            .and_then(|inventory| inventory.remove_first_of_type(ItemType::Key(colour)));
+        match key {
+            Some(key) => {
+                self.remove_entity(key);
+                self.components.lock.remove(door);
+                self.components.door.insert(door, true);
+                self.components.tile.insert(door, Tile::DoorOpen);
+                message_log.push(LogMessage::PlayerUnlocksDoor(colour));
+                Ok(())
+            }
+            None => {
+                message_log.push(LogMessage::DoorIsLocked(colour));
+                Err(())
+            }
+        }
     }
-    fn damage_modifier(&self, entity: Entity) -> i32 {
+    // Swaps the character with the npc of the same faction in the given direction, so allies
+    // don't get in each other's way. Npcs never displace the player. Fails if either character
+    // would end up on a cell with a solid feature.
+    pub fn maybe_swap_places(&mut self, mover: Entity, direction: Direction) -> Result<(), ()> {
+        let mover_coord = self.spatial_table.coord_of(mover).ok_or(())?;
+        let other_coord = mover_coord + direction.coord();
+        let other = self
+            .spatial_table
+            .layers_at(other_coord)
+            .and_then(|layers| layers.character)
+            .ok_or(())?;
+        if !self.components.npc_type.contains(other) {
+            return Err(());
+        }
+        match (
+            self.components.faction.get(mover),
+            self.components.faction.get(other),
+        ) {
+            (Some(faction), Some(other_faction)) if faction == other_faction => (),
+            _ => return Err(()),
+        }
+        for &coord in &[mover_coord, other_coord] {
+            if let Some(feature) = self.spatial_table.layers_at_checked(coord).feature {
+                if self.is_feature_solid(feature) {
+                    return Err(());
+                }
+            }
+        }
+        // Only one character can occupy a cell, so take the other character out of the spatial
+        // table while the mover takes its place
+        self.spatial_table.remove(other);
+        self.spatial_table.update_coord(mover, other_coord).unwrap();
+        self.spatial_table
+            .update(
+                other,
+                Location {
+                    coord: mover_coord,
+                    layer: Some(Layer::Character),
+                },
+            )
+            .unwrap();
+        Ok(())
+    }
+    // Open doors, traps and fire are the only features which don't block movement, vision and
+    // projectiles
+    fn is_feature_solid(&self, feature: Entity) -> bool {
+        self.components.door.get(feature) != Some(&true)
+            && !self.components.trap.contains(feature)
+            && !self.components.fire.contains(feature)
+    }
+    // Reveals the trap and damages the character who stepped on it
+    fn trigger_trap<R: Rng>(
+        &mut self,
+        character: Entity,
+        trap: Entity,
+        message_log: &mut Vec<LogMessage>,
+        rng: &mut R,
+    ) {
+        self.components.hidden.insert(trap, false);
+        message_log.push(LogMessage::TrapTriggers);
+        let &damage = self.components.trap.get(trap).unwrap();
+        let maybe_npc = self.npc_type(character);
+        if let Some(VictimDies) = self.character_damage(character, damage, DamageKind::Trap, rng) {
+            if let Some(npc_type) = maybe_npc {
+                message_log.push(LogMessage::NpcDies(npc_type));
+            } else {
+                message_log.push(LogMessage::PlayerDies);
+            }
+        }
+    }
+    // Reveals any hidden traps within `radius` of `coord`, returning the number of traps revealed
+    pub fn detect_traps_near(&mut self, coord: Coord, radius: u32) -> usize {
+        let mut num_revealed = 0;
+        let top_left = coord - Coord::new(radius as i32, radius as i32);
+        let size = Size::new(radius * 2 + 1, radius * 2 + 1);
+        for entity in self.entities_in_rect(top_left, size) {
+            if let Some(hidden) = self.components.hidden.get_mut(entity) {
+                if *hidden {
+                    *hidden = false;
+                    num_revealed += 1;
+                }
+            }
+        }
+        num_revealed
+    }
+    // Each secret wall adjacent to `coord` has a chance of being found, opening up the passage
+    // behind it. Returns the number of passages found.
+    pub fn search<R: Rng>(&mut self, coord: Coord, rng: &mut R) -> usize {
+        let secret_walls = self
+            .entities_in_rect(coord - Coord::new(1, 1), Size::new(3, 3))
+            .into_iter()
+            .filter(|&entity| matches!(self.components.tile.get(entity), Some(Tile::SecretWall)))
+            .collect::<Vec<_>>();
+        let mut num_found = 0;
+        for secret_wall in secret_walls {
+            if rng.gen_range(0..100) < SEARCH_PERCENT {
+                self.remove_entity(secret_wall);
+                num_found += 1;
+            }
+        }
+        num_found
+    }
+    // Returns the npcs hostile to the player within `radius` of `origin`, which should be alerted
+    // to the player's presence by the noise of combat at `origin`
+    pub fn propagate_alert(&self, origin: Coord, radius: u32) -> Vec<Entity> {
+        let top_left = origin - Coord::new(radius as i32, radius as i32);
+        let size = Size::new(radius * 2 + 1, radius * 2 + 1);
+        self.entities_in_rect(top_left, size)
+            .into_iter()
+            .filter(|&entity| {
+                self.components.npc_type.contains(entity)
+                    && self.faction(entity) != Some(Faction::Player)
+            })
+            .collect()
+    }
+    fn inventory_item_entity(&self, entity: Entity, index: usize) -> Option<Entity> {
         self.components
-            .equipment_held_inventory_index
+            .inventory
             .get(entity)
-            .and_then(|&held_index| {
-                self.inventory_item_type(entity, held_index)
-                    .map(|item_type| match item_type {
-                        ItemType::Sword => 1,
-                        _ => 0,
-                    })
-            })
-            .unwrap_or(0)
+            .and_then(|inventory| inventory.get(index).ok())
     }
-    fn defense_modifier(&self, entity: Entity) -> i32 {
+    pub fn sort_player_inventory(&mut self, player: Entity) {
+        if let Some(inventory) = self.components.inventory.get_mut(player) {
+            inventory.sort();
+        }
+    }
+    pub fn inventory_item_type(&self, entity: Entity, index: usize) -> Option<ItemType> {
+        self.inventory_item_entity(entity, index)
+            .and_then(|held_entity| self.components.item.get(held_entity).cloned())
+    }
+    fn equipped_entity(&self, entity: Entity, slot: EquipSlot) -> Option<Entity> {
         self.components
-            .equipment_worn_inventory_index
+            .equipment
             .get(entity)
-            .and_then(|&held_index| {
-                self.inventory_item_type(entity, held_index)
-                    .map(|item_type| match item_type {
-                        ItemType::Armour => 1,
-                        _ => 0,
-                    })
-            })
+            .and_then(|equipment| equipment.get(slot))
+    }
+    pub fn equipped_in_slot(&self, entity: Entity, slot: EquipSlot) -> Option<ItemType> {
+        self.equipped_entity(entity, slot)
+            .and_then(|item| self.components.item.get(item).cloned())
+    }
+    fn damage_modifier(&self, entity: Entity) -> i32 {
+        self.equipped_entity(entity, EquipSlot::Weapon)
+            .and_then(|weapon| self.components.weapon_damage.get(weapon).cloned())
             .unwrap_or(0)
     }
-    fn magic_modifier(&self, entity: Entity) -> i32 {
-        let held = self
+    fn weapon_poison(&self, entity: Entity) -> u32 {
+        self.equipped_entity(entity, EquipSlot::Weapon)
+            .and_then(|weapon| self.components.weapon_poison.get(weapon).cloned())
+            .unwrap_or(0)
+    }
+    fn weapon_lifesteal(&self, entity: Entity) -> u32 {
+        self.equipped_entity(entity, EquipSlot::Weapon)
+            .and_then(|weapon| self.components.lifesteal.get(weapon).cloned())
+            .unwrap_or(0)
+    }
+    // Projectiles which would hit a character holding a reflecting item turn back the way they came
+    fn reflects_projectiles(&self, entity: Entity) -> bool {
+        self.equipped_entity(entity, EquipSlot::Offhand)
+            .map(|offhand| self.components.reflect.contains(offhand))
+            .unwrap_or(false)
+    }
+    fn weapon_bleed(&self, entity: Entity) -> u32 {
+        self.equipped_entity(entity, EquipSlot::Weapon)
+            .and_then(|weapon| self.components.weapon_bleed.get(weapon).cloned())
+            .unwrap_or(0)
+    }
+    // A character's attacks knock back their victims if either the character or their weapon has
+    // knockback
+    fn has_knockback(&self, entity: Entity) -> bool {
+        self.components.knockback.contains(entity)
+            || self
+                .equipped_entity(entity, EquipSlot::Weapon)
+                .map(|weapon| self.components.knockback.contains(weapon))
+                .unwrap_or(false)
+    }
+    // The percentage chance that an attack by the character is a critical hit, combining the
+    // character's own chance with that of their weapon
+    fn crit_chance(&self, entity: Entity) -> u32 {
+        let own = self
             .components
-            .equipment_held_inventory_index
+            .crit_chance
             .get(entity)
-            .and_then(|&held_index| {
-                self.inventory_item_type(entity, held_index)
-                    .map(|item_type| match item_type {
-                        ItemType::Staff => 1,
-                        _ => 0,
-                    })
-            })
+            .cloned()
             .unwrap_or(0);
-        let worn = self
-            .components
-            .equipment_worn_inventory_index
-            .get(entity)
-            .and_then(|&held_index| {
-                self.inventory_item_type(entity, held_index)
-                    .map(|item_type| match item_type {
-                        ItemType::Robe => 1,
-                        _ => 0,
-                    })
-            })
+        let weapon = self
+            .equipped_entity(entity, EquipSlot::Weapon)
+            .and_then(|weapon| self.components.crit_chance.get(weapon).cloned())
             .unwrap_or(0);
-        held + worn
-    }
-    fn character_bump_attack<R: Rng>(
-        &mut self,
-        victim: Entity,
-        attacker: Entity,
-        rng: &mut R,
-    ) -> BumpAttackOutcome {
-        let &attacker_base_damage = self.components.base_damage.get(attacker).unwrap();
-        let &attacker_strength = self.components.strength.get(attacker).unwrap();
-        let attacker_damage_modifier = self.damage_modifier(attacker);
-        let &victim_dexterity = self.components.dexterity.get(victim).unwrap();
-        let victim_defense_modifier = self.defense_modifier(victim);
-        let gross_damage = attacker_base_damage
-            + rng.gen_range(0..(attacker_strength + 1))
-            + attacker_damage_modifier;
-        let damage_reduction = rng.gen_range(0..(victim_dexterity + 1)) + victim_defense_modifier;
-        let net_damage = gross_damage.saturating_sub(damage_reduction).max(0) as u32;
-        if net_damage == 0 {
-            BumpAttackOutcome::Dodge
-        } else {
-            if self.character_damage(victim, net_damage).is_some() {
-                BumpAttackOutcome::Kill
-            } else {
-                BumpAttackOutcome::Hit
-            }
-        }
+        own + weapon
     }
-    fn character_damage(&mut self, victim: Entity, damage: u32) -> Option<VictimDies> {
-        if let Some(hit_points) = self.components.hit_points.get_mut(victim) {
-            hit_points.current = hit_points.current.saturating_sub(damage);
-            if hit_points.current == 0 {
-                self.character_die(victim);
-                return Some(VictimDies);
-            }
-        }
-        None
+    // Both worn armour and offhand items such as shields protect the character
+    fn defense_modifier(&self, entity: Entity) -> i32 {
+        [EquipSlot::Armour, EquipSlot::Offhand]
+            .iter()
+            .filter_map(|&slot| self.equipped_entity(entity, slot))
+            .filter_map(|item| self.components.armour_defense.get(item).cloned())
+            .sum()
     }
-    fn character_die(&mut self, entity: Entity) {
-        if let Some(occpied_by_entity) = self
-            .spatial_table
-            .update_layer(entity, Layer::Object)
-            .err()
-            .map(|e| e.unwrap_occupied_by())
-        {
-            // If a character dies on a cell which contains an object, remove the existing object
-            // from existence and replace it with the character's corpse.
-            self.remove_entity(occpied_by_entity);
-            self.spatial_table
-                .update_layer(entity, Layer::Object)
-                .unwrap();
-        }
-        let current_tile = self.components.tile.get(entity).unwrap();
-        let corpse_tile = match current_tile {
-            Tile::Player => Tile::PlayerCorpse,
-            Tile::Npc(npc_type) => Tile::NpcCorpse(*npc_type),
-            other => panic!("unexpected tile on character {:?}", other),
+    fn magic_modifier(&self, entity: Entity) -> i32 {
+        let weapon = match self.equipped_in_slot(entity, EquipSlot::Weapon) {
+            Some(ItemType::Staff) => 1,
+            _ => 0,
         };
-        self.components.tile.insert(entity, corpse_tile);
+        let armour = match self.equipped_in_slot(entity, EquipSlot::Armour) {
+            Some(ItemType::Robe) => 1,
+            _ => 0,
+        };
+        weapon + armour
     }
-    pub fn maybe_get_item(
+    // Moves an item from the character's inventory into the equipment slot for its type. Items
+    // displaced from the character's equipment are returned to their inventory.
+    fn equip_item(
         &mut self,
         character: Entity,
+        inventory_index: usize,
         message_log: &mut Vec<LogMessage>,
     ) -> Result<(), ()> {
-        let coord = self
-            .spatial_table
-            .coord_of(character)
-            .expect("character has no coord");
-        if let Some(object_entity) = self.spatial_table.layers_at_checked(coord).object {
-            if let Some(&item_type) = self.components.item.get(object_entity) {
-                // this assumes that the only character that can get items is the player
-                let inventory = self
-                    .components
-                    .inventory
-                    .get_mut(character)
-                    .expect("character has no inventory");
-                if inventory.insert(object_entity).is_ok() {
-                    self.spatial_table.remove(object_entity);
-                    message_log.push(LogMessage::PlayerGets(item_type));
-                    return Ok(());
-                } else {
-                    message_log.push(LogMessage::PlayerInventoryIsFull);
-                    return Err(());
-                }
+        let item_type = self
+            .inventory_item_type(character, inventory_index)
+            .expect("no item in inventory slot");
+        let slot = item_type.equip_slot().expect("item can't be equipped");
+        let mut equipment = self
+            .components
+            .equipment
+            .get(character)
+            .cloned()
+            .unwrap_or_default();
+        let mut displaced = Vec::new();
+        displaced.extend(equipment.slot_mut(slot).take());
+        if slot == EquipSlot::Weapon && item_type.is_two_handed() {
+            displaced.extend(equipment.offhand.take());
+        }
+        if slot == EquipSlot::Offhand
+            && self
+                .equipped_in_slot(character, EquipSlot::Weapon)
+                .map(ItemType::is_two_handed)
+                .unwrap_or(false)
+        {
+            displaced.extend(equipment.weapon.take());
+        }
+        // A cursed item can't be taken off, so nothing can replace it in its slot
+        if let Some(&cursed_item) = displaced.iter().find(|&&item| self.is_cursed(item)) {
+            let &cursed_item_type = self.components.item.get(cursed_item).unwrap();
+            message_log.push(LogMessage::ItemIsCursed(cursed_item_type));
+            return Err(());
+        }
+        // Swap items on a copy of the inventory, so if the unequipped items don't fit then neither
+        // the inventory nor the equipment is changed
+        let mut inventory = self
+            .components
+            .inventory
+            .get(character)
+            .cloned()
+            .expect("character has no inventory");
+        let item = inventory.remove(inventory_index).unwrap();
+        for old_item in displaced {
+            let &old_item_type = self.components.item.get(old_item).unwrap();
+            if inventory.insert(old_item, old_item_type).is_err() {
+                message_log.push(LogMessage::PlayerInventoryIsFull);
+                return Err(());
             }
         }
-        message_log.push(LogMessage::NoItemUnderPlayer);
-        Err(())
+        self.components.inventory.insert(character, inventory);
+        *equipment.slot_mut(slot) = Some(item);
+        self.components.equipment.insert(character, equipment);
+        message_log.push(LogMessage::PlayerEquips(item_type));
+        // Curses only reveal themselves once it's too late
+        if self.is_cursed(item) {
+            message_log.push(LogMessage::ItemIsCursed(item_type));
+        }
+        Ok(())
     }
-    pub fn maybe_use_item(
+    // Moves the item in the given equipment slot back into the character's inventory. Cursed items
+    // can't be taken off until the curse is removed.
+    pub fn maybe_unequip_item(
         &mut self,
         character: Entity,
-        inventory_index: usize,
+        slot: EquipSlot,
         message_log: &mut Vec<LogMessage>,
-    ) -> Result<ItemUsage, ()> {
+    ) -> Result<(), ()> {
+        let item = self.equipped_entity(character, slot).ok_or(())?;
+        let &item_type = self
+            .components
+            .item
+            .get(item)
+            .expect("non-item in equipment");
+        if self.is_cursed(item) {
+            message_log.push(LogMessage::ItemIsCursed(item_type));
+            return Err(());
+        }
         let inventory = self
             .components
             .inventory
             .get_mut(character)
             .expect("character has no inventory");
-        let item = match inventory.get(inventory_index) {
-            Ok(item) => item,
-            Err(InventorySlotIsEmpty) => {
-                message_log.push(LogMessage::NoItemInInventorySlot);
-                return Err(());
+        if inventory.insert(item, item_type).is_err() {
+            message_log.push(LogMessage::PlayerInventoryIsFull);
+            return Err(());
+        }
+        if let Some(equipment) = self.components.equipment.get_mut(character) {
+            *equipment.slot_mut(slot) = None;
+        }
+        message_log.push(LogMessage::PlayerUnequips(item_type));
+        Ok(())
+    }
+    fn is_cursed(&self, item: Entity) -> bool {
+        self.components.cursed.get(item).cloned().unwrap_or(false)
+    }
+    fn character_bump_attack<R: Rng>(
+        &mut self,
+        victim: Entity,
+        attacker: Entity,
+        direction: Direction,
+        message_log: &mut Vec<LogMessage>,
+        rng: &mut R,
+    ) -> BumpAttackOutcome {
+        let mut net_damage = self.resolve_attack(attacker, victim, rng);
+        // A critical hit doubles the damage of an attack which wasn't dodged
+        if net_damage > 0 && rng.gen_range(0..100) < self.crit_chance(attacker) {
+            net_damage *= 2;
+            if let (None, Some(npc_type)) = (self.npc_type(attacker), self.npc_type(victim)) {
+                message_log.push(LogMessage::PlayerCriticalHit(npc_type));
             }
-        };
-        let &item_type = self
-            .components
-            .item
-            .get(item)
-            .expect("non-item in inventory");
-        let usage = match item_type {
-            ItemType::HealthPotion => {
-                let mut hit_points = self
-                    .components
-                    .hit_points
-                    .get_mut(character)
-                    .expect("character has no hit points");
-                const HEALTH_TO_HEAL: u32 = 5;
-                hit_points.current = hit_points.max.min(hit_points.current + HEALTH_TO_HEAL);
-                inventory.remove(inventory_index).unwrap();
-                message_log.push(LogMessage::PlayerHeals);
-                ItemUsage::Immediate
+        }
+        if net_damage == 0 {
+            BumpAttackOutcome::Dodge
+        } else {
+            let victim_hit_points = self.components.hit_points.get(victim).unwrap().current;
+            self.apply_lifesteal(attacker, net_damage.min(victim_hit_points), message_log);
+            if self
+                .character_damage(victim, net_damage, DamageKind::Melee, rng)
+                .is_some()
+            {
+                BumpAttackOutcome::Kill
+            } else {
+                let poison = self.weapon_poison(attacker);
+                if poison > 0 {
+                    self.character_poison(victim, poison);
+                }
+                let bleed = self.weapon_bleed(attacker);
+                if bleed > 0 {
+                    self.character_bleed(victim, bleed);
+                }
+                if self.has_knockback(attacker)
+                    && self.apply_knockback(victim, direction, rng).is_some()
+                {
+                    return BumpAttackOutcome::Kill;
+                }
+                BumpAttackOutcome::Hit
             }
-            ItemType::FireballScroll | ItemType::ConfusionScroll => ItemUsage::Aim,
-            ItemType::Sword | ItemType::Staff => {
-                self.components
-                    .equipment_held_inventory_index
-                    .insert(character, inventory_index);
-                message_log.push(LogMessage::PlayerEquips(item_type));
-                ItemUsage::Immediate
+        }
+    }
+    // The damage dealt by a single melee attack before critical hits are taken into account. The
+    // attacker's attack stat, weapon and a random portion of their strength make up the attack, and
+    // the victim dodges a random portion of it up to their dexterity. An attack which is entirely
+    // dodged deals no damage. Otherwise the rest of the attack is reduced by the victim's defense
+    // stat and armour, as in `Stats::damage`, so an attack which gets past the dodge always deals at
+    // least 1 damage however heavy the armour.
+    fn resolve_attack<R: Rng>(&self, attacker: Entity, victim: Entity, rng: &mut R) -> u32 {
+        let &attacker_stats = self.components.stats.get(attacker).unwrap();
+        let &attacker_strength = self.components.strength.get(attacker).unwrap();
+        let attacker_damage_modifier = self.damage_modifier(attacker);
+        let &victim_stats = self.components.stats.get(victim).unwrap();
+        let &victim_dexterity = self.components.dexterity.get(victim).unwrap();
+        let victim_defense_modifier = self.defense_modifier(victim);
+        let attack = attacker_stats.attack as i32
+            + rng.gen_range(0..(attacker_strength + 1))
+            + attacker_damage_modifier;
+        let dodge = rng.gen_range(0..(victim_dexterity + 1));
+        if dodge >= attack {
+            return 0;
+        }
+        let defense = victim_stats.defense + victim_defense_modifier.max(0) as u32;
+        Stats::damage((attack - dodge) as u32, defense)
+    }
+    // Heals the attacker by a fraction of the damage they dealt, if their weapon has lifesteal
+    fn apply_lifesteal(
+        &mut self,
+        attacker: Entity,
+        damage_dealt: u32,
+        message_log: &mut Vec<LogMessage>,
+    ) {
+        let amount = damage_dealt * self.weapon_lifesteal(attacker) / 100;
+        let hit_points = match self.components.hit_points.get(attacker) {
+            Some(&hit_points) => hit_points,
+            None => return,
+        };
+        if amount == 0 || hit_points.current >= hit_points.max {
+            return;
+        }
+        self.character_heal(attacker, amount);
+        if !self.components.npc_type.contains(attacker) {
+            message_log.push(LogMessage::PlayerDrainsLife);
+        }
+    }
+    // Pushes the victim one cell in the given direction. If the victim can't be pushed into the
+    // destination cell, it's slammed against whatever is there and takes damage instead.
+    fn apply_knockback<R: Rng>(
+        &mut self,
+        victim: Entity,
+        direction: Direction,
+        rng: &mut R,
+    ) -> Option<VictimDies> {
+        const COLLISION_DAMAGE: u32 = 1;
+        let victim_coord = self
+            .spatial_table
+            .coord_of(victim)
+            .expect("victim has no coord");
+        let destination = victim_coord + direction.coord();
+        let can_enter = self.can_npc_enter(destination)
+            && self
+                .spatial_table
+                .layers_at(destination)
+                .map(|layers| {
+                    layers.character.is_none()
+                        && layers
+                            .feature
+                            .is_none_or(|feature| !self.is_feature_solid(feature))
+                })
+                .unwrap_or(false);
+        if can_enter {
+            self.spatial_table
+                .update_coord(victim, destination)
+                .unwrap();
+            None
+        } else {
+            self.character_damage(victim, COLLISION_DAMAGE, DamageKind::Collision, rng)
+        }
+    }
+    fn character_damage<R: Rng>(
+        &mut self,
+        victim: Entity,
+        damage: u32,
+        kind: DamageKind,
+        rng: &mut R,
+    ) -> Option<VictimDies> {
+        // Several projectiles may hit the same character in a single tick. Once the character has
+        // died, any further damage is ignored so it can't die (and drop its loot) a second time.
+        if !self.is_living_character(victim) {
+            return None;
+        }
+        if let Some(hit_points) = self.components.hit_points.get_mut(victim) {
+            if let Some(coord) = self.spatial_table.coord_of(victim) {
+                self.damage_events.push(DamageEvent {
+                    coord,
+                    amount: damage.min(hit_points.current),
+                    kind,
+                    turn: self.turn,
+                });
             }
-            ItemType::Armour | ItemType::Robe => {
-                self.components
-                    .equipment_worn_inventory_index
-                    .insert(character, inventory_index);
-                message_log.push(LogMessage::PlayerEquips(item_type));
-                ItemUsage::Immediate
+            hit_points.current = hit_points.current.saturating_sub(damage);
+            if hit_points.current == 0 {
+                self.character_die(victim, rng);
+                return Some(VictimDies);
             }
+        }
+        None
+    }
+    // Damage from projectile attacks is reduced by the victim's defense stat and armour, as with
+    // melee attacks. Other sources of damage such as poison and hunger ignore defense.
+    fn character_attack_damage<R: Rng>(
+        &mut self,
+        victim: Entity,
+        damage: u32,
+        kind: DamageKind,
+        rng: &mut R,
+    ) -> Option<VictimDies> {
+        let defense = self
+            .components
+            .stats
+            .get(victim)
+            .map_or(0, |stats| stats.defense)
+            + self.defense_modifier(victim).max(0) as u32;
+        let damage = Stats::damage(damage, defense);
+        self.character_damage(victim, damage, kind, rng)
+    }
+    fn character_die<R: Rng>(&mut self, entity: Entity, rng: &mut R) {
+        if let Some(occpied_by_entity) = self
+            .spatial_table
+            .update_layer(entity, Layer::Object)
+            .err()
+            .map(|e| e.unwrap_occupied_by())
+        {
+            // If a character dies on a cell which contains an object, remove the existing object
+            // from existence and replace it with the character's corpse.
+            self.remove_entity(occpied_by_entity);
+            self.spatial_table
+                .update_layer(entity, Layer::Object)
+                .unwrap();
+        }
+        let current_tile = self.components.tile.get(entity).unwrap();
+        let corpse_tile = match current_tile {
+            Tile::Player => Tile::PlayerCorpse,
+            Tile::Npc(npc_type) => Tile::NpcCorpse(*npc_type),
+            other => panic!("unexpected tile on character {:?}", other),
         };
-        Ok(usage)
+        self.components.tile.insert(entity, corpse_tile);
+        // Corpses don't suffer from lingering poison or wounds
+        self.components.poison.remove(entity);
+        self.components.bleed.remove(entity);
+        if let Some(&npc_type) = self.components.npc_type.get(entity) {
+            self.components
+                .decay
+                .insert(entity, npc_type.corpse_decay_duration());
+        }
+        self.drop_npc_loot(entity, rng);
     }
-    fn magic(&self, entity: Entity) -> i32 {
-        self.components
-            .intelligence
-            .get(entity)
-            .cloned()
-            .unwrap_or(0)
-            + self.magic_modifier(entity)
+    fn drop_npc_loot<R: Rng>(&mut self, entity: Entity, rng: &mut R) {
+        let npc_type = match self.components.npc_type.get(entity) {
+            Some(&npc_type) => npc_type,
+            None => return,
+        };
+        let item_type = match npc_type.loot_table().choose(self.level, rng) {
+            Some(item_type) => item_type,
+            None => return,
+        };
+        let coord = self.spatial_table.coord_of(entity).unwrap();
+        // The corpse usually occupies the object layer of the cell where the npc died, so the
+        // loot is dropped in the first free adjacent cell instead. Spawning the loot fails on
+        // cells whose object layer is occupied, in which case the next cell is tried. If there is
+        // no free cell nearby, the loot is lost.
+        let candidates = std::iter::once(coord)
+            .chain(Direction::all().map(|direction| coord + direction.coord()))
+            .collect::<Vec<_>>();
+        for drop_coord in candidates {
+            let is_open_floor = self
+                .spatial_table
+                .layers_at(drop_coord)
+                .map(|layers| layers.floor.is_some() && layers.feature.is_none())
+                .unwrap_or(false);
+            if is_open_floor && self.spawn_item(drop_coord, item_type).is_ok() {
+                return;
+            }
+        }
     }
-    pub fn maybe_use_item_aim(
+    pub fn maybe_get_item(
         &mut self,
         character: Entity,
-        inventory_index: usize,
-        target: Coord,
         message_log: &mut Vec<LogMessage>,
     ) -> Result<(), ()> {
-        let character_coord = self.spatial_table.coord_of(character).unwrap();
-        if character_coord == target {
-            return Err(());
-        }
-        let inventory = self
-            .components
-            .inventory
-            .get_mut(character)
-            .expect("character has no inventory");
-        let item_entity = inventory.remove(inventory_index).unwrap();
-        let &item_type = self.components.item.get(item_entity).unwrap();
-        match item_type {
-            ItemType::HealthPotion
-            | ItemType::Sword
-            | ItemType::Staff
-            | ItemType::Armour
-            | ItemType::Robe => panic!("invalid item for aim"),
-            ItemType::FireballScroll => {
-                let fireball = ProjectileType::Fireball {
-                    damage: self.magic(character).max(0) as u32,
-                };
-                message_log.push(LogMessage::PlayerLaunchesProjectile(fireball));
-                self.spawn_projectile(character_coord, target, fireball);
+        let coord = self
+            .spatial_table
+            .coord_of(character)
+            .expect("character has no coord");
+        if let Some(object_entity) = self.spatial_table.layers_at_checked(coord).object {
+            // Gold is added straight to the character's purse rather than taking up an inventory
+            // slot
+            if let Some(&amount) = self.components.gold_pile.get(object_entity) {
+                if let Some(gold) = self.components.gold.get_mut(character) {
+                    *gold += amount;
+                    self.remove_entity(object_entity);
+                    message_log.push(LogMessage::PlayerGetsGold(amount));
+                    return Ok(());
+                }
             }
-            ItemType::ConfusionScroll => {
-                let confusion = ProjectileType::Confusion {
-                    duration: self.magic(character).max(0) as u32 * 3,
-                };
-                message_log.push(LogMessage::PlayerLaunchesProjectile(confusion));
-                self.spawn_projectile(character_coord, target, confusion);
+            if let Some(&item_type) = self.components.item.get(object_entity) {
+                let (carried, capacity) = self.player_carry_weight(character);
+                if carried + item_type.weight() > capacity {
+                    message_log.push(LogMessage::TooHeavy(
+                        item_type,
+                        self.item_is_identified(character, object_entity),
+                    ));
+                    return Err(());
+                }
+                // this assumes that the only character that can get items is the player
+                let inventory = self
+                    .components
+                    .inventory
+                    .get_mut(character)
+                    .expect("character has no inventory");
+                if inventory.insert(object_entity, item_type).is_ok() {
+                    self.spatial_table.remove(object_entity);
+                    message_log.push(LogMessage::PlayerGets(
+                        item_type,
+                        self.item_is_identified(character, object_entity),
+                    ));
+                    return Ok(());
+                } else {
+                    message_log.push(LogMessage::PlayerInventoryIsFull);
+                    return Err(());
+                }
             }
         }
-        Ok(())
+        message_log.push(LogMessage::NoItemUnderPlayer);
+        Err(())
     }
-    pub fn maybe_drop_item(
+    // Picks up the item under the character only if its type satisfies `predicate`. Gold is always
+    // picked up since it can't be cursed and doesn't take up an inventory slot. Nothing is logged
+    // when there's no matching item, since this runs after every step the player takes.
+    pub fn auto_pickup<F: Fn(ItemType) -> bool>(
         &mut self,
         character: Entity,
-        inventory_index: usize,
+        predicate: F,
         message_log: &mut Vec<LogMessage>,
     ) -> Result<(), ()> {
         let coord = self
             .spatial_table
             .coord_of(character)
             .expect("character has no coord");
-        if self.spatial_table.layers_at_checked(coord).object.is_some() {
-            message_log.push(LogMessage::NoSpaceToDropItem);
+        let object_entity = self
+            .spatial_table
+            .layers_at_checked(coord)
+            .object
+            .ok_or(())?;
+        let matches = self.components.gold_pile.contains(object_entity)
+            || self
+                .components
+                .item
+                .get(object_entity)
+                .is_some_and(|&item_type| predicate(item_type));
+        if !matches {
             return Err(());
         }
+        self.maybe_get_item(character, message_log)
+    }
+    // Drops the item in the given inventory slot and picks up the item under the character as a
+    // single action, allowing items to be exchanged when the inventory is full
+    pub fn maybe_swap_item(
+        &mut self,
+        character: Entity,
+        inventory_index: usize,
+        message_log: &mut Vec<LogMessage>,
+    ) -> Result<(), ()> {
+        let coord = self
+            .spatial_table
+            .coord_of(character)
+            .expect("character has no coord");
+        let (floor_item, floor_item_type) = match self
+            .spatial_table
+            .layers_at_checked(coord)
+            .object
+            .and_then(|entity| {
+                self.components
+                    .item
+                    .get(entity)
+                    .map(|&item_type| (entity, item_type))
+            }) {
+            Some(floor_item) => floor_item,
+            None => {
+                message_log.push(LogMessage::NoItemUnderPlayer);
+                return Err(());
+            }
+        };
+        let (carried, capacity) = self.player_carry_weight(character);
         let inventory = self
             .components
             .inventory
             .get_mut(character)
             .expect("character has no inventory");
-        let item = match inventory.remove(inventory_index) {
+        let dropped_item = match inventory.remove(inventory_index) {
             Ok(item) => item,
             Err(InventorySlotIsEmpty) => {
                 message_log.push(LogMessage::NoItemInInventorySlot);
                 return Err(());
             }
         };
+        let &dropped_item_type = self
+            .components
+            .item
+            .get(dropped_item)
+            .expect("non-item in inventory");
+        if carried - dropped_item_type.weight() + floor_item_type.weight() > capacity {
+            // This can't fail as it returns the item to the slot it was just removed from
+            let _ = inventory.insert(dropped_item, dropped_item_type);
+            message_log.push(LogMessage::TooHeavy(
+                floor_item_type,
+                self.item_is_identified(character, floor_item),
+            ));
+            return Err(());
+        }
+        // Removing one item from a stack doesn't free its slot, so the floor item may still not fit
+        if inventory.insert(floor_item, floor_item_type).is_err() {
+            // This can't fail as it returns the item to the slot it was just removed from
+            let _ = inventory.insert(dropped_item, dropped_item_type);
+            message_log.push(LogMessage::PlayerInventoryIsFull);
+            return Err(());
+        }
+        self.spatial_table.remove(floor_item);
         self.spatial_table
             .update(
-                item,
+                dropped_item,
                 Location {
                     coord,
                     layer: Some(Layer::Object),
                 },
             )
             .unwrap();
-        let &item_type = self
-            .components
-            .item
-            .get(item)
-            .expect("non-item in inventory");
-        if self
-            .components
-            .equipment_held_inventory_index
-            .get(character)
-            .cloned()
-            == Some(inventory_index)
-        {
-            self.components
-                .equipment_held_inventory_index
-                .remove(character);
+        message_log.push(LogMessage::PlayerDrops(
+            dropped_item_type,
+            self.item_is_identified(character, dropped_item),
+        ));
+        message_log.push(LogMessage::PlayerGets(
+            floor_item_type,
+            self.item_is_identified(character, floor_item),
+        ));
+        Ok(())
+    }
+    pub fn maybe_use_item<R: Rng>(
+        &mut self,
+        character: Entity,
+        inventory_index: usize,
+        message_log: &mut Vec<LogMessage>,
+        rng: &mut R,
+    ) -> Result<ItemUsage, ()> {
+        let inventory = self
+            .components
+            .inventory
+            .get_mut(character)
+            .expect("character has no inventory");
+        let item = match inventory.get(inventory_index) {
+            Ok(item) => item,
+            Err(InventorySlotIsEmpty) => {
+                message_log.push(LogMessage::NoItemInInventorySlot);
+                return Err(());
+            }
+        };
+        let &item_type = self
+            .components
+            .item
+            .get(item)
+            .expect("non-item in inventory");
+        let usage = match item_type {
+            ItemType::HealthPotion => {
+                inventory.remove(inventory_index).unwrap();
+                self.character_heal(character, HEALTH_TO_HEAL);
+                message_log.push(LogMessage::PlayerHeals);
+                ItemUsage::Immediate
+            }
+            ItemType::Backpack => {
+                if inventory.grow(BACKPACK_CAPACITY, MAX_INVENTORY_CAPACITY) == 0 {
+                    message_log.push(LogMessage::PlayerInventoryCannotExpand);
+                    return Err(());
+                }
+                inventory.remove(inventory_index).unwrap();
+                message_log.push(LogMessage::PlayerInventoryExpands);
+                ItemUsage::Immediate
+            }
+            ItemType::StrengthPotion => {
+                inventory.remove(inventory_index).unwrap();
+                if let Some(strength) = self.components.strength.get_mut(character) {
+                    *strength += 1;
+                }
+                message_log.push(LogMessage::PlayerFeelsStronger);
+                ItemUsage::Immediate
+            }
+            ItemType::AcidPotion
+            | ItemType::FireballScroll
+            | ItemType::ConfusionScroll
+            | ItemType::BlindnessScroll
+            | ItemType::PolymorphScroll
+            | ItemType::TeleportOtherScroll
+            | ItemType::ChainLightningScroll
+            | ItemType::Net
+            | ItemType::ClairvoyanceScroll
+            | ItemType::SummonScroll
+            | ItemType::Pickaxe => ItemUsage::Aim,
+            ItemType::FireballWand => {
+                if self.components.charges.get(item) == Some(&0) {
+                    // Using an empty wand doesn't take up the character's turn
+                    message_log.push(LogMessage::WandIsEmpty);
+                    return Err(());
+                }
+                ItemUsage::Aim
+            }
+            // The scroll is consumed once the item to identify has been chosen
+            ItemType::IdentifyScroll => ItemUsage::ChooseItem,
+            ItemType::LightningScroll => {
+                let target = match self.nearest_visible_hostile(character, LIGHTNING_RANGE) {
+                    Some(target) => target,
+                    None => {
+                        // The scroll isn't consumed if there's nothing to zap
+                        message_log.push(LogMessage::NoTargetInRange);
+                        return Err(());
+                    }
+                };
+                self.components
+                    .inventory
+                    .get_mut(character)
+                    .expect("character has no inventory")
+                    .remove(inventory_index)
+                    .unwrap();
+                let maybe_npc = self.npc_type(target);
+                if let Some(npc_type) = maybe_npc {
+                    message_log.push(LogMessage::PlayerZapsNpc(npc_type));
+                }
+                let damage = self.magic(character).max(0) as u32 * 2;
+                if let Some(VictimDies) =
+                    self.character_damage(target, damage, DamageKind::Magic, rng)
+                {
+                    if let Some(npc_type) = maybe_npc {
+                        message_log.push(LogMessage::NpcDies(npc_type));
+                    }
+                }
+                ItemUsage::Immediate
+            }
+            ItemType::TeleportScroll => {
+                if !self.teleport_entity(character, rng) {
+                    // The scroll isn't consumed if there's nowhere to teleport to
+                    message_log.push(LogMessage::TeleportFails);
+                    return Err(());
+                }
+                self.components
+                    .inventory
+                    .get_mut(character)
+                    .expect("character has no inventory")
+                    .remove(inventory_index)
+                    .unwrap();
+                message_log.push(LogMessage::PlayerTeleports);
+                ItemUsage::Immediate
+            }
+            ItemType::MappingScroll => {
+                // Revealing the map is up to the caller, since the world doesn't track which
+                // cells the player has seen
+                inventory.remove(inventory_index).unwrap();
+                message_log.push(LogMessage::PlayerMapsLevel);
+                ItemUsage::Immediate
+            }
+            ItemType::EarthquakeScroll => {
+                inventory.remove(inventory_index).unwrap();
+                let coord = self
+                    .spatial_table
+                    .coord_of(character)
+                    .expect("character has no coord");
+                self.earthquake(coord, EARTHQUAKE_RADIUS, rng);
+                message_log.push(LogMessage::Earthquake);
+                ItemUsage::Immediate
+            }
+            ItemType::HasteScroll => {
+                inventory.remove(inventory_index).unwrap();
+                self.components.haste.insert(character, HASTE_DURATION);
+                message_log.push(LogMessage::PlayerHasted);
+                ItemUsage::Immediate
+            }
+            ItemType::DetectLifeScroll => {
+                // As with the mapping scroll, showing the detected characters is up to the caller
+                inventory.remove(inventory_index).unwrap();
+                message_log.push(LogMessage::PlayerDetectsLife);
+                ItemUsage::Immediate
+            }
+            ItemType::RemoveCurseScroll => {
+                inventory.remove(inventory_index).unwrap();
+                if let Some(equipment) = self.components.equipment.get(character) {
+                    for (_, equipped_item) in equipment.iter() {
+                        self.components.cursed.insert(equipped_item, false);
+                    }
+                }
+                message_log.push(LogMessage::PlayerRemovesCurse);
+                ItemUsage::Immediate
+            }
+            ItemType::Ration => {
+                inventory.remove(inventory_index).unwrap();
+                self.character_eat(character);
+                message_log.push(LogMessage::PlayerEats);
+                ItemUsage::Immediate
+            }
+            ItemType::Sword
+            | ItemType::Dagger
+            | ItemType::PoisonDagger
+            | ItemType::VampiricDagger
+            | ItemType::Staff
+            | ItemType::Shield
+            | ItemType::ReflectShield
+            | ItemType::Armour
+            | ItemType::LeatherArmour
+            | ItemType::Robe => {
+                self.equip_item(character, inventory_index, message_log)?;
+                ItemUsage::Immediate
+            }
+            ItemType::Key(_) => {
+                // Keys are used by walking into a locked door
+                message_log.push(LogMessage::CannotUseItem(item_type));
+                return Err(());
+            }
+        };
+        if let ItemUsage::Immediate = usage {
+            self.identify_item_type(character, item_type);
         }
-        if self
+        Ok(usage)
+    }
+    pub fn maybe_use_item_choose(
+        &mut self,
+        character: Entity,
+        inventory_index: usize,
+        target_index: usize,
+        message_log: &mut Vec<LogMessage>,
+    ) -> Result<(), ()> {
+        let target_type = match self.inventory_item_type(character, target_index) {
+            Some(target_type) => target_type,
+            None => {
+                message_log.push(LogMessage::NoItemInInventorySlot);
+                return Err(());
+            }
+        };
+        let item_entity = self
             .components
-            .equipment_worn_inventory_index
-            .get(character)
+            .inventory
+            .get_mut(character)
+            .expect("character has no inventory")
+            .remove(inventory_index)
+            .unwrap();
+        let &item_type = self.components.item.get(item_entity).unwrap();
+        match item_type {
+            ItemType::IdentifyScroll => {
+                self.identify_item_type(character, target_type);
+                message_log.push(LogMessage::PlayerIdentifies(target_type));
+            }
+            _ => panic!("invalid item for choose"),
+        }
+        self.remove_entity(item_entity);
+        self.identify_item_type(character, item_type);
+        Ok(())
+    }
+    // Whether `character` knows what items of the given type are. Only items which start out
+    // unidentified can be unknown.
+    pub fn item_type_is_identified(&self, character: Entity, item_type: ItemType) -> bool {
+        !item_type.starts_unidentified()
+            || self
+                .components
+                .identified_item_types
+                .get(character)
+                .map(|identified| identified.contains(&item_type))
+                .unwrap_or(false)
+    }
+    pub fn item_is_identified(&self, character: Entity, item: Entity) -> bool {
+        self.components
+            .item
+            .get(item)
+            .map(|&item_type| self.item_type_is_identified(character, item_type))
+            .unwrap_or(true)
+    }
+    // Identifies every item of the given type for the character, including items they don't hold
+    // yet and items of the type which have already been used up
+    fn identify_item_type(&mut self, character: Entity, item_type: ItemType) {
+        if let Some(identified) = self.components.identified_item_types.get_mut(character) {
+            identified.insert(item_type);
+        }
+    }
+    fn magic(&self, entity: Entity) -> i32 {
+        self.components
+            .intelligence
+            .get(entity)
             .cloned()
-            == Some(inventory_index)
+            .unwrap_or(0)
+            + self.magic_modifier(entity)
+    }
+    // On success, returns the entity of the npc summoned by the item, if any. It's up to the
+    // caller to give a summoned npc an agent.
+    pub fn maybe_use_item_aim<R: Rng>(
+        &mut self,
+        character: Entity,
+        inventory_index: usize,
+        target: Coord,
+        message_log: &mut Vec<LogMessage>,
+        rng: &mut R,
+    ) -> Result<Option<Entity>, ()> {
+        let character_coord = self.spatial_table.coord_of(character).unwrap();
+        if character_coord == target {
+            return Err(());
+        }
+        let aimed_directly = self
+            .inventory_item_type(character, inventory_index)
+            .map(ItemType::is_aimed_directly)
+            .unwrap_or(false);
+        if aimed_directly && !self.can_aim_directly_at(character_coord, target) {
+            message_log.push(LogMessage::CannotSeeTarget);
+            return Err(());
+        }
+        if self.inventory_item_type(character, inventory_index) == Some(ItemType::SummonScroll)
+            && !self.can_summon_at(target)
         {
+            // The scroll isn't consumed if the summoned npc has nowhere to go
+            message_log.push(LogMessage::CannotSummonThere);
+            return Err(());
+        }
+        let mut summoned = None;
+        let item_entity = self
+            .inventory_item_entity(character, inventory_index)
+            .expect("no item in inventory slot");
+        let &item_type = self.components.item.get(item_entity).unwrap();
+        // Each item takes effect before it's removed from the inventory, so if its projectile
+        // can't be launched the item isn't used up
+        match item_type {
+            ItemType::LightningScroll
+            | ItemType::StrengthPotion
+            | ItemType::DetectLifeScroll
+            | ItemType::TeleportScroll
+            | ItemType::MappingScroll
+            | ItemType::EarthquakeScroll
+            | ItemType::HasteScroll
+            | ItemType::IdentifyScroll
+            | ItemType::RemoveCurseScroll
+            | ItemType::Ration
+            | ItemType::Backpack
+            | ItemType::Shield
+            | ItemType::ReflectShield
+            | ItemType::Armour
+            | ItemType::LeatherArmour
+            | ItemType::Robe
+            | ItemType::Key(_) => panic!("invalid item for aim"),
+            ItemType::Sword
+            | ItemType::Dagger
+            | ItemType::PoisonDagger
+            | ItemType::VampiricDagger
+            | ItemType::Staff => {
+                // The weapon is carried by the projectile, and lands as an item wherever the
+                // projectile stops
+                let thrown = ProjectileType::Thrown {
+                    item_type,
+                    damage: self
+                        .components
+                        .weapon_damage
+                        .get(item_entity)
+                        .map(|&damage| damage.max(0) as u32)
+                        .unwrap_or(0),
+                };
+                let projectile = self
+                    .spawn_projectile(character_coord, target, thrown)
+                    .map_err(|_| ())?;
+                self.components.carried_item.insert(projectile, item_entity);
+                message_log.push(LogMessage::PlayerThrows(item_type));
+            }
+            // Pickaxes and wands stay in the inventory until they break or run out of charges
+            ItemType::Pickaxe => {
+                return self
+                    .use_pickaxe(character, inventory_index, target, message_log)
+                    .map(|()| None);
+            }
+            ItemType::FireballWand => {
+                return self
+                    .use_fireball_wand(character, inventory_index, target, message_log)
+                    .map(|()| None);
+            }
+            ItemType::FireballScroll => {
+                let fireball = ProjectileType::Fireball {
+                    damage: self.magic(character).max(0) as u32,
+                };
+                self.spawn_projectile(character_coord, target, fireball)
+                    .map_err(|_| ())?;
+                message_log.push(LogMessage::PlayerLaunchesProjectile(fireball));
+                self.remove_entity(item_entity);
+            }
+            ItemType::ConfusionScroll => {
+                let confusion = ProjectileType::Confusion {
+                    duration: self.magic(character).max(0) as u32 * 3,
+                };
+                self.spawn_projectile(character_coord, target, confusion)
+                    .map_err(|_| ())?;
+                message_log.push(LogMessage::PlayerLaunchesProjectile(confusion));
+                self.remove_entity(item_entity);
+            }
+            ItemType::BlindnessScroll => {
+                let blindness = ProjectileType::Blindness {
+                    duration: self.magic(character).max(0) as u32 * 3,
+                };
+                self.spawn_projectile(character_coord, target, blindness)
+                    .map_err(|_| ())?;
+                message_log.push(LogMessage::PlayerLaunchesProjectile(blindness));
+                self.remove_entity(item_entity);
+            }
+            ItemType::Net => {
+                let net = ProjectileType::Net {
+                    duration: NET_DURATION,
+                };
+                self.spawn_projectile(character_coord, target, net)
+                    .map_err(|_| ())?;
+                message_log.push(LogMessage::PlayerThrows(item_type));
+                self.remove_entity(item_entity);
+            }
+            ItemType::ChainLightningScroll => {
+                let chain_lightning = ProjectileType::ChainLightning {
+                    damage: self.magic(character).max(0) as u32 * 2,
+                };
+                self.spawn_projectile(character_coord, target, chain_lightning)
+                    .map_err(|_| ())?;
+                message_log.push(LogMessage::PlayerLaunchesProjectile(chain_lightning));
+                self.remove_entity(item_entity);
+            }
+            ItemType::PolymorphScroll => {
+                let polymorph = ProjectileType::Polymorph;
+                self.spawn_projectile(character_coord, target, polymorph)
+                    .map_err(|_| ())?;
+                message_log.push(LogMessage::PlayerLaunchesProjectile(polymorph));
+                self.remove_entity(item_entity);
+            }
+            ItemType::TeleportOtherScroll => {
+                // The scroll is used up even if there's nothing at the target to teleport
+                let npc = self.spatial_table.layers_at_checked(target).character;
+                let npc_type = npc.and_then(|npc| self.components.npc_type.get(npc).cloned());
+                match (npc, npc_type) {
+                    (Some(npc), Some(npc_type)) if self.teleport_entity(npc, rng) => {
+                        message_log.push(LogMessage::NpcTeleports(npc_type));
+                    }
+                    _ => message_log.push(LogMessage::NothingHappens),
+                }
+                self.remove_entity(item_entity);
+            }
+            ItemType::ClairvoyanceScroll => {
+                // As with the mapping scroll, revealing the room is up to the caller
+                message_log.push(LogMessage::PlayerMapsRoom);
+                self.remove_entity(item_entity);
+            }
+            ItemType::HealthPotion | ItemType::AcidPotion => {
+                let effect = item_type
+                    .splash_effect()
+                    .expect("potion has no splash effect");
+                message_log.push(LogMessage::PotionShatters);
+                self.create_cloud(target, SPLASH_RADIUS, effect, message_log, rng);
+                self.remove_entity(item_entity);
+            }
+            ItemType::SummonScroll => {
+                const SUMMONED_NPC_TYPE: NpcType = NpcType::Orc;
+                let entity = self
+                    .spawn_npc(target, SUMMONED_NPC_TYPE, DifficultyScale::default())
+                    .map_err(|_| ())?;
+                self.components.faction.insert(entity, Faction::Player);
+                message_log.push(LogMessage::PlayerSummons(SUMMONED_NPC_TYPE));
+                self.remove_entity(item_entity);
+                summoned = Some(entity);
+            }
+        }
+        self.components
+            .inventory
+            .get_mut(character)
+            .expect("character has no inventory")
+            .remove(inventory_index)
+            .unwrap();
+        self.identify_item_type(character, item_type);
+        Ok(summoned)
+    }
+    // Digs out the wall at `target`, which must be adjacent to the character. Each dig wears down
+    // the pickaxe, which breaks once its durability runs out.
+    fn use_pickaxe(
+        &mut self,
+        character: Entity,
+        inventory_index: usize,
+        target: Coord,
+        message_log: &mut Vec<LogMessage>,
+    ) -> Result<(), ()> {
+        let character_coord = self.spatial_table.coord_of(character).unwrap();
+        let delta = target - character_coord;
+        if delta.x.abs() > 1 || delta.y.abs() > 1 || self.dig_wall(target).is_err() {
+            message_log.push(LogMessage::CannotDigThere);
+            return Err(());
+        }
+        message_log.push(LogMessage::PlayerDigsWall);
+        let pickaxe = self
+            .inventory_item_entity(character, inventory_index)
+            .expect("pickaxe missing from inventory");
+        let durability = self
+            .components
+            .durability
+            .get_mut(pickaxe)
+            .expect("pickaxe has no durability");
+        *durability = durability.saturating_sub(1);
+        if *durability == 0 {
             self.components
-                .equipment_worn_inventory_index
-                .remove(character);
+                .inventory
+                .get_mut(character)
+                .expect("character has no inventory")
+                .remove(inventory_index)
+                .unwrap();
+            self.remove_entity(pickaxe);
+            message_log.push(LogMessage::ItemBreaks(ItemType::Pickaxe));
         }
-        message_log.push(LogMessage::PlayerDrops(item_type));
         Ok(())
     }
-    pub fn move_projectiles(&mut self, message_log: &mut Vec<LogMessage>) {
-        let mut entities_to_remove = Vec::new();
-        let mut fireball_hit = Vec::new();
-        let mut confusion_hit = Vec::new();
-        for (entity, trajectory) in self.components.trajectory.iter_mut() {
-            if let Some(direction) = trajectory.next() {
-                let current_coord = self.spatial_table.coord_of(entity).unwrap();
-                let new_coord = current_coord + direction.coord();
-                let dest_layers = self.spatial_table.layers_at_checked(new_coord);
-                if dest_layers.feature.is_some() {
-                    entities_to_remove.push(entity);
-                } else if let Some(character) = dest_layers.character {
-                    entities_to_remove.push(entity);
-                    if let Some(&projectile_type) = self.components.projectile.get(entity) {
-                        match projectile_type {
-                            ProjectileType::Fireball { damage } => {
-                                fireball_hit.push((character, damage));
-                            }
-                            ProjectileType::Confusion { duration } => {
-                                confusion_hit.push((character, duration));
-                            }
-                        }
-                    }
-                }
-
-                // ignore collisiosns of projectiles
-                let _ = self.spatial_table.update_coord(entity, new_coord);
-            } else {
-                entities_to_remove.push(entity);
+    // Fires a fireball at `target`, using up one of the wand's charges. Unlike a scroll, the wand
+    // stays in the inventory once it's empty.
+    fn use_fireball_wand(
+        &mut self,
+        character: Entity,
+        inventory_index: usize,
+        target: Coord,
+        message_log: &mut Vec<LogMessage>,
+    ) -> Result<(), ()> {
+        let wand = self
+            .inventory_item_entity(character, inventory_index)
+            .expect("wand missing from inventory");
+        if self.components.charges.get(wand) == Some(&0) {
+            message_log.push(LogMessage::WandIsEmpty);
+            return Err(());
+        }
+        let character_coord = self.spatial_table.coord_of(character).unwrap();
+        let fireball = ProjectileType::Fireball {
+            damage: self.magic(character).max(0) as u32,
+        };
+        // A charge is only used up if the fireball is launched
+        self.spawn_projectile(character_coord, target, fireball)
+            .map_err(|_| ())?;
+        *self
+            .components
+            .charges
+            .get_mut(wand)
+            .expect("wand has no charges") -= 1;
+        message_log.push(LogMessage::PlayerLaunchesProjectile(fireball));
+        Ok(())
+    }
+    pub fn item_charges(&self, entity: Entity) -> Option<u32> {
+        self.components.charges.get(entity).cloned()
+    }
+    // Removes the wall at `coord`, exposing the floor beneath it. Fails if there is no wall at
+    // `coord`.
+    pub fn dig_wall(&mut self, coord: Coord) -> Result<(), ()> {
+        let wall = self
+            .spatial_table
+            .layers_at(coord)
+            .and_then(|layers| layers.feature)
+            .filter(|&feature| {
+                matches!(
+                    self.components.tile.get(feature),
+                    Some(Tile::Wall) | Some(Tile::SecretWall)
+                )
+            })
+            .ok_or(())?;
+        self.remove_entity(wall);
+        Ok(())
+    }
+    // Npcs can dig through any wall except those on the edge of the map, so the map stays enclosed
+    pub fn can_npc_dig(&self, coord: Coord) -> bool {
+        let size = self.spatial_table.grid_size();
+        let on_edge = coord.x <= 0
+            || coord.y <= 0
+            || coord.x >= size.width() as i32 - 1
+            || coord.y >= size.height() as i32 - 1;
+        !on_edge
+            && self
+                .spatial_table
+                .layers_at(coord)
+                .and_then(|layers| layers.feature)
+                .map(|feature| {
+                    matches!(
+                        self.components.tile.get(feature),
+                        Some(Tile::Wall) | Some(Tile::SecretWall)
+                    )
+                })
+                .unwrap_or(false)
+    }
+    // Digs out the wall at `coord`, which must be adjacent to the npc. This takes the npc's turn,
+    // and it moves into the tunnel on a later turn.
+    pub fn npc_dig(
+        &mut self,
+        entity: Entity,
+        coord: Coord,
+        message_log: &mut Vec<LogMessage>,
+    ) -> Result<(), ()> {
+        let npc_coord = self.spatial_table.coord_of(entity).ok_or(())?;
+        let delta = coord - npc_coord;
+        if delta.x.abs() + delta.y.abs() != 1 || !self.can_npc_dig(coord) {
+            return Err(());
+        }
+        self.dig_wall(coord)?;
+        if let Some(&npc_type) = self.components.npc_type.get(entity) {
+            message_log.push(LogMessage::NpcDigsWall(npc_type));
+        }
+        Ok(())
+    }
+    // The target of an item which is aimed directly must be in range and in line of sight, so the
+    // player can't heal or summon through walls
+    fn can_aim_directly_at(&self, from: Coord, to: Coord) -> bool {
+        let delta = to - from;
+        (delta.x * delta.x + delta.y * delta.y) as u32 <= DIRECT_AIM_RANGE_SQUARED
+            && self.has_line_of_sight(from, to)
+    }
+    fn can_summon_at(&self, coord: Coord) -> bool {
+        self.spatial_table
+            .layers_at(coord)
+            .map(|layers| {
+                layers.floor.is_some() && layers.feature.is_none() && layers.character.is_none()
+            })
+            .unwrap_or(false)
+    }
+    pub fn check_can_throw_item(
+        &self,
+        character: Entity,
+        inventory_index: usize,
+        message_log: &mut Vec<LogMessage>,
+    ) -> Result<(), ()> {
+        match self.inventory_item_type(character, inventory_index) {
+            None => {
+                message_log.push(LogMessage::NoItemInInventorySlot);
+                Err(())
+            }
+            Some(item_type) if item_type.is_throwable() => Ok(()),
+            Some(item_type) => {
+                let identified = self
+                    .inventory_item_entity(character, inventory_index)
+                    .map(|item| self.item_is_identified(character, item))
+                    .unwrap_or(true);
+                message_log.push(LogMessage::CannotThrowItem(item_type, identified));
+                Err(())
+            }
+        }
+    }
+    pub fn maybe_throw_item<R: Rng>(
+        &mut self,
+        character: Entity,
+        inventory_index: usize,
+        target: Coord,
+        message_log: &mut Vec<LogMessage>,
+        rng: &mut R,
+    ) -> Result<(), ()> {
+        self.check_can_throw_item(character, inventory_index, message_log)?;
+        self.maybe_use_item_aim(character, inventory_index, target, message_log, rng)
+            .map(|_| ())
+    }
+    // Applies the effect to every character within `radius` of `centre`, as with the blast of a
+    // fireball
+    pub fn create_cloud<R: Rng>(
+        &mut self,
+        centre: Coord,
+        radius: u32,
+        effect: SplashEffect,
+        message_log: &mut Vec<LogMessage>,
+        rng: &mut R,
+    ) {
+        for coord in self.coords_within_radius(centre, radius) {
+            let character = match self.spatial_table.layers_at_checked(coord).character {
+                Some(character) => character,
+                None => continue,
+            };
+            let maybe_npc = self.components.npc_type.get(character).cloned();
+            match effect {
+                SplashEffect::Heal(amount) => {
+                    self.character_heal(character, amount);
+                    if let Some(npc_type) = maybe_npc {
+                        message_log.push(LogMessage::NpcHeals(npc_type));
+                    } else {
+                        message_log.push(LogMessage::PlayerHeals);
+                    }
+                }
+                SplashEffect::Damage(damage) => {
+                    if let Some(VictimDies) =
+                        self.character_damage(character, damage, DamageKind::Acid, rng)
+                    {
+                        if let Some(npc_type) = maybe_npc {
+                            message_log.push(LogMessage::NpcDies(npc_type));
+                        } else {
+                            message_log.push(LogMessage::PlayerDies);
+                        }
+                    } else if let Some(npc_type) = maybe_npc {
+                        message_log.push(LogMessage::NpcIsSplashedWithAcid(npc_type));
+                    } else {
+                        message_log.push(LogMessage::PlayerIsSplashedWithAcid);
+                    }
+                }
+            }
+        }
+    }
+    fn character_poison(&mut self, entity: Entity, duration: u32) {
+        let current = self.components.poison.get(entity).cloned().unwrap_or(0);
+        self.components.poison.insert(entity, current.max(duration));
+    }
+    // Bleeding stacks with each wound, unlike poison which only lasts as long as the worst dose
+    fn character_bleed(&mut self, entity: Entity, intensity: u32) {
+        let current = self.components.bleed.get(entity).cloned().unwrap_or(0);
+        self.components.bleed.insert(entity, current + intensity);
+    }
+    // Moving reopens a bleeding character's wounds, so fleeing makes bleeding worse
+    fn aggravate_bleeding(&mut self, entity: Entity) {
+        if let Some(bleed) = self.components.bleed.get_mut(entity) {
+            *bleed += 1;
+        }
+    }
+    pub fn apply_status_effects<R: Rng>(&mut self, message_log: &mut Vec<LogMessage>, rng: &mut R) {
+        let mut poisoned = self
+            .components
+            .poison
+            .entities()
+            .filter(|&entity| self.is_living_character(entity))
+            .collect::<Vec<_>>();
+        self.sort_by_coord(&mut poisoned);
+        for entity in poisoned {
+            let poison = self.components.poison.get_mut(entity).unwrap();
+            *poison -= 1;
+            if *poison == 0 {
+                self.components.poison.remove(entity);
+            }
+            let maybe_npc = self.components.npc_type.get(entity).cloned();
+            if let Some(VictimDies) = self.character_damage(entity, 1, DamageKind::Poison, rng) {
+                if let Some(npc) = maybe_npc {
+                    message_log.push(LogMessage::NpcDies(npc));
+                } else {
+                    message_log.push(LogMessage::PlayerDiesOfPoison);
+                }
+            } else if let Some(npc) = maybe_npc {
+                message_log.push(LogMessage::NpcTakesPoisonDamage(npc));
+            } else {
+                message_log.push(LogMessage::PlayerTakesPoisonDamage);
+            }
+        }
+        // Bleeding characters take damage equal to the intensity of their bleeding, which then
+        // eases by one. Characters who keep moving undo this by aggravating their wounds.
+        let mut bleeding = self
+            .components
+            .bleed
+            .entities()
+            .filter(|&entity| self.is_living_character(entity))
+            .collect::<Vec<_>>();
+        self.sort_by_coord(&mut bleeding);
+        for entity in bleeding {
+            let bleed = self.components.bleed.get_mut(entity).unwrap();
+            let damage = *bleed;
+            *bleed -= 1;
+            if *bleed == 0 {
+                self.components.bleed.remove(entity);
+            }
+            let maybe_npc = self.components.npc_type.get(entity).cloned();
+            if let Some(VictimDies) = self.character_damage(entity, damage, DamageKind::Bleed, rng)
+            {
+                if let Some(npc) = maybe_npc {
+                    message_log.push(LogMessage::NpcDies(npc));
+                }
+            } else if let Some(npc) = maybe_npc {
+                message_log.push(LogMessage::NpcBleeds(npc));
+            }
+        }
+    }
+    // Characters with nutrition get hungrier each turn, and take damage once their nutrition
+    // runs out
+    pub fn apply_hunger<R: Rng>(&mut self, message_log: &mut Vec<LogMessage>, rng: &mut R) {
+        let hungry = self
+            .components
+            .nutrition
+            .entities()
+            .filter(|&entity| self.is_living_character(entity))
+            .collect::<Vec<_>>();
+        for entity in hungry {
+            let nutrition = self.components.nutrition.get_mut(entity).unwrap();
+            if *nutrition > 0 {
+                *nutrition -= 1;
+                continue;
+            }
+            // A starving character eats a ration from their inventory if they have one
+            let ration = self
+                .components
+                .inventory
+                .get_mut(entity)
+                .and_then(|inventory| inventory.remove_first_of_type(ItemType::Ration));
+            if let Some(ration) = ration {
+                self.remove_entity(ration);
+                self.character_eat(entity);
+                message_log.push(LogMessage::PlayerEats);
+                continue;
+            }
+            if let Some(VictimDies) = self.character_damage(entity, 1, DamageKind::Starvation, rng)
+            {
+                message_log.push(LogMessage::PlayerStarvesToDeath);
+            } else {
+                message_log.push(LogMessage::PlayerStarving);
+            }
+        }
+    }
+    // Burns any character standing in a fire, then counts down the remaining turns of each fire,
+    // removing fires which have burnt out. Each fire has a small chance of spreading to a
+    // neighbouring floor cell, where it burns for less time than the fire it spread from.
+    pub fn tick_environmental_hazards<R: Rng>(
+        &mut self,
+        message_log: &mut Vec<LogMessage>,
+        rng: &mut R,
+    ) {
+        let mut fires = self.components.fire.entities().collect::<Vec<_>>();
+        self.sort_by_coord(&mut fires);
+        for fire in fires {
+            let coord = self
+                .spatial_table
+                .coord_of(fire)
+                .expect("fire has no coord");
+            if let Some(character) = self.spatial_table.layers_at_checked(coord).character {
+                let maybe_npc = self.components.npc_type.get(character).cloned();
+                if let Some(VictimDies) =
+                    self.character_damage(character, FIRE_DAMAGE, DamageKind::Fire, rng)
+                {
+                    if let Some(npc) = maybe_npc {
+                        message_log.push(LogMessage::NpcDies(npc));
+                    } else {
+                        message_log.push(LogMessage::PlayerDies);
+                    }
+                } else if let Some(npc) = maybe_npc {
+                    message_log.push(LogMessage::NpcIsBurned(npc));
+                } else {
+                    message_log.push(LogMessage::PlayerIsBurned);
+                }
+            }
+            let remaining = self.components.fire.get_mut(fire).unwrap();
+            *remaining -= 1;
+            let remaining = *remaining;
+            if remaining > 0 && rng.gen_range(0..100) < FIRE_SPREAD_PERCENT {
+                let direction = rng.gen::<CardinalDirection>();
+                let _ = self.ignite(coord + direction.coord(), remaining);
+            }
+            if remaining == 0 {
+                self.remove_entity(fire);
+            }
+        }
+    }
+    // Corpses crumble away once they have decayed for long enough. The player's corpse never
+    // decays.
+    pub fn decay_corpses(&mut self, message_log: &mut Vec<LogMessage>) {
+        let mut crumbled = Vec::new();
+        for (entity, decay) in self.components.decay.iter_mut() {
+            *decay = decay.saturating_sub(1);
+            if *decay == 0 {
+                crumbled.push(entity);
+            }
+        }
+        for entity in crumbled {
+            if let Some(&npc_type) = self.components.npc_type.get(entity) {
+                message_log.push(LogMessage::CorpseCrumbles(npc_type));
+            }
+            self.remove_entity(entity);
+        }
+    }
+    // Counts down the remaining turns of haste on each hasted character, removing haste from
+    // characters whose haste has run out
+    pub fn tick_haste(&mut self, message_log: &mut Vec<LogMessage>) {
+        let mut expired = Vec::new();
+        for (entity, haste) in self.components.haste.iter_mut() {
+            *haste = haste.saturating_sub(1);
+            if *haste == 0 {
+                expired.push(entity);
+            }
+        }
+        for entity in expired {
+            self.components.haste.remove(entity);
+            if !self.components.npc_type.contains(entity) {
+                message_log.push(LogMessage::PlayerIsNoLongerHasted);
+            }
+        }
+    }
+    // Counts down the remaining turns of blindness on each blind character, restoring the sight of
+    // characters whose blindness has run out
+    pub fn tick_blindness(&mut self, message_log: &mut Vec<LogMessage>) {
+        let mut cured = Vec::new();
+        for (entity, blind) in self.components.blind.iter_mut() {
+            *blind = blind.saturating_sub(1);
+            if *blind == 0 {
+                cured.push(entity);
+            }
+        }
+        for entity in cured {
+            self.components.blind.remove(entity);
+            if let Some(&npc_type) = self.components.npc_type.get(entity) {
+                message_log.push(LogMessage::NpcIsNoLongerBlind(npc_type));
+            }
+        }
+    }
+    // Counts down the number of turns for which each entangled character is stuck in place
+    pub fn tick_rooted(&mut self, message_log: &mut Vec<LogMessage>) {
+        let mut freed = Vec::new();
+        for (entity, rooted) in self.components.rooted.iter_mut() {
+            *rooted = rooted.saturating_sub(1);
+            if *rooted == 0 {
+                freed.push(entity);
+            }
+        }
+        for entity in freed {
+            self.components.rooted.remove(entity);
+            if let Some(&npc_type) = self.components.npc_type.get(entity) {
+                message_log.push(LogMessage::NpcIsNoLongerRooted(npc_type));
+            }
+        }
+    }
+    pub fn is_rooted(&self, entity: Entity) -> bool {
+        self.components.rooted.contains(entity)
+    }
+    // The number of actions an npc takes for each turn taken by the player. Haste doubles an npc's
+    // speed. Rooted npcs still get all their actions, but can only spend them attacking.
+    pub fn npc_actions_per_turn(&self, entity: Entity) -> u32 {
+        let speed = self.components.speed.get(entity).cloned().unwrap_or(1);
+        if self.entity_is_hasted(entity) {
+            speed * 2
+        } else {
+            speed
+        }
+    }
+    fn character_eat(&mut self, entity: Entity) {
+        if let Some(nutrition) = self.components.nutrition.get_mut(entity) {
+            *nutrition = MAX_NUTRITION.min(*nutrition + RATION_NUTRITION);
+        }
+    }
+    // Moves the entity to a randomly chosen floor cell with no feature or character. Returns
+    // false without moving the entity if no such cell could be found.
+    pub fn teleport_entity<R: Rng>(&mut self, entity: Entity, rng: &mut R) -> bool {
+        const MAX_ATTEMPTS: usize = 1000;
+        let size = self.spatial_table.grid_size();
+        for _ in 0..MAX_ATTEMPTS {
+            let coord = Coord::new(
+                rng.gen_range(0..size.width() as i32),
+                rng.gen_range(0..size.height() as i32),
+            );
+            let layers = self.spatial_table.layers_at_checked(coord);
+            if self.can_npc_enter(coord)
+                && layers.floor.is_some()
+                && layers.feature.is_none()
+                && layers.character.is_none()
+            {
+                self.spatial_table.update_coord(entity, coord).unwrap();
+                return true;
+            }
+        }
+        false
+    }
+    // Every `interval` turns, heal the character by 1 unless it's in view of a hostile character
+    pub fn regenerate(&mut self, character: Entity, interval: u64) {
+        if interval == 0
+            || !self.turn.is_multiple_of(interval)
+            || !self.is_living_character(character)
+        {
+            return;
+        }
+        if !self.is_hostile_in_view(character) {
+            self.character_heal(character, 1);
+        }
+    }
+    // Heals every living npc whose type regenerates. Unlike the player, npcs regenerate even in
+    // combat.
+    pub fn npc_regenerate(&mut self) {
+        let regenerating = self
+            .components
+            .npc_type
+            .iter()
+            .filter(|(_, npc_type)| npc_type.regeneration() > 0)
+            .map(|(entity, npc_type)| (entity, npc_type.regeneration()))
+            .collect::<Vec<_>>();
+        for (entity, amount) in regenerating {
+            if self.is_living_character(entity) {
+                self.character_heal(entity, amount);
+            }
+        }
+    }
+    // Warns the player once when their hit points drop below a quarter of their maximum. Healing
+    // back above the threshold re-arms the warning.
+    pub fn check_player_health_warnings(
+        &mut self,
+        player: Entity,
+        message_log: &mut Vec<LogMessage>,
+    ) {
+        if !self.is_living_character(player) {
+            return;
+        }
+        let hit_points = match self.components.hit_points.get(player) {
+            Some(&hit_points) => hit_points,
+            None => return,
+        };
+        if hit_points.current * 4 < hit_points.max {
+            if !self.components.low_health_warned.contains(player) {
+                self.components.low_health_warned.insert(player, ());
+                message_log.push(LogMessage::PlayerLowHealth);
+            }
+        } else {
+            self.components.low_health_warned.remove(player);
+        }
+    }
+    pub fn is_hostile_in_view(&self, entity: Entity) -> bool {
+        const HOSTILE_VISION_RANGE: u32 = 10;
+        self.nearest_visible_hostile(entity, HOSTILE_VISION_RANGE)
+            .is_some()
+    }
+    // Calls `take_turn` a turn at a time until the player is at full health, a hostile comes into
+    // view, or `max_turns` turns have passed. The conditions are checked before every turn, so
+    // resting stops as soon as a hostile becomes visible.
+    pub fn rest<F: FnMut(&mut Self)>(
+        &mut self,
+        player: Entity,
+        max_turns: u32,
+        mut take_turn: F,
+    ) -> RestOutcome {
+        let mut turns_rested = 0;
+        loop {
+            if self.is_hostile_in_view(player) {
+                return RestOutcome::HostileInView;
+            }
+            let hit_points = match self.hit_points(player) {
+                Some(hit_points) => hit_points,
+                None => return RestOutcome::Interrupted,
+            };
+            if hit_points.current >= hit_points.max {
+                return RestOutcome::FullHitPoints;
+            }
+            if self.has_projectiles() || !self.is_living_character(player) {
+                return RestOutcome::Interrupted;
+            }
+            if turns_rested == max_turns {
+                return RestOutcome::MaxTurnsElapsed;
+            }
+            take_turn(self);
+            turns_rested += 1;
+        }
+    }
+    fn character_heal(&mut self, entity: Entity, amount: u32) {
+        if let Some(hit_points) = self.components.hit_points.get_mut(entity) {
+            hit_points.current = hit_points.max.min(hit_points.current + amount);
+        }
+    }
+    pub fn maybe_drop_item(
+        &mut self,
+        character: Entity,
+        inventory_index: usize,
+        message_log: &mut Vec<LogMessage>,
+    ) -> Result<(), ()> {
+        let coord = self
+            .spatial_table
+            .coord_of(character)
+            .expect("character has no coord");
+        if self.spatial_table.layers_at_checked(coord).object.is_some() {
+            message_log.push(LogMessage::NoSpaceToDropItem);
+            return Err(());
+        }
+        let inventory = self
+            .components
+            .inventory
+            .get_mut(character)
+            .expect("character has no inventory");
+        let item = match inventory.remove(inventory_index) {
+            Ok(item) => item,
+            Err(InventorySlotIsEmpty) => {
+                message_log.push(LogMessage::NoItemInInventorySlot);
+                return Err(());
+            }
+        };
+        self.spatial_table
+            .update(
+                item,
+                Location {
+                    coord,
+                    layer: Some(Layer::Object),
+                },
+            )
+            .unwrap();
+        let &item_type = self
+            .components
+            .item
+            .get(item)
+            .expect("non-item in inventory");
+        message_log.push(LogMessage::PlayerDrops(
+            item_type,
+            self.item_is_identified(character, item),
+        ));
+        Ok(())
+    }
+    pub fn move_projectiles<R: Rng>(&mut self, message_log: &mut Vec<LogMessage>, rng: &mut R) {
+        let mut entities_to_remove = Vec::new();
+        let mut fireball_hit = Vec::new();
+        let mut confusion_hit = Vec::new();
+        let mut blindness_hit = Vec::new();
+        let mut polymorph_hit = Vec::new();
+        let mut chain_lightning_hit = Vec::new();
+        let mut net_hit = Vec::new();
+        let mut arrow_hit = Vec::new();
+        let mut thrown_hit = Vec::new();
+        let mut landed = Vec::new();
+        let mut projectiles = self.components.trajectory.entities().collect::<Vec<_>>();
+        self.sort_by_coord(&mut projectiles);
+        for entity in projectiles {
+            let current_coord = self.spatial_table.coord_of(entity).unwrap();
+            let projectile_type = self.components.projectile.get(entity).cloned();
+            let blast_radius = projectile_type.map(|p| p.blast_radius()).unwrap_or(0);
+            let next_coord = self.components.trajectory.get_mut(entity).unwrap().next();
+            if let Some(new_coord) = next_coord {
+                let dest_layers = self.spatial_table.layers_at_checked(new_coord);
+                let hits_solid_feature = dest_layers
+                    .feature
+                    .map(|feature| self.is_feature_solid(feature))
+                    .unwrap_or(false);
+                if hits_solid_feature {
+                    entities_to_remove.push(entity);
+                    if let Some(&item) = self.components.carried_item.get(entity) {
+                        landed.push((item, current_coord));
+                    }
+                    // A fireball which hits a feature explodes in the cell in front of the feature
+                    if let Some(ProjectileType::Fireball { damage }) = projectile_type {
+                        fireball_hit.push((current_coord, damage, blast_radius));
+                    }
+                } else if dest_layers
+                    .character
+                    .map(|character| self.reflects_projectiles(character))
+                    .unwrap_or(false)
+                {
+                    // The projectile stays in front of the reflecting character this tick, and
+                    // heads back towards where it was launched from
+                    self.components
+                        .trajectory
+                        .get_mut(entity)
+                        .unwrap()
+                        .reflect(current_coord);
+                    if let Some(projectile_type) = projectile_type {
+                        message_log.push(LogMessage::ProjectileReflected(projectile_type));
+                    }
+                    continue;
+                } else if let Some(character) = dest_layers.character {
+                    // A piercing projectile passes through characters until its piercing runs out
+                    match self.components.piercing.get_mut(entity) {
+                        Some(piercing) if *piercing > 0 => *piercing -= 1,
+                        _ => entities_to_remove.push(entity),
+                    }
+                    if let Some(projectile_type) = projectile_type {
+                        match projectile_type {
+                            ProjectileType::Fireball { damage } => {
+                                fireball_hit.push((new_coord, damage, blast_radius));
+                            }
+                            ProjectileType::Confusion { duration } => {
+                                confusion_hit.push((character, duration));
+                            }
+                            ProjectileType::Blindness { duration } => {
+                                blindness_hit.push((character, duration));
+                            }
+                            ProjectileType::Polymorph => polymorph_hit.push(character),
+                            ProjectileType::ChainLightning { damage } => {
+                                chain_lightning_hit.push((character, damage));
+                            }
+                            ProjectileType::Net { duration } => {
+                                net_hit.push((character, duration));
+                            }
+                            ProjectileType::Arrow { damage } => {
+                                arrow_hit.push((character, damage));
+                            }
+                            ProjectileType::Thrown { item_type, damage } => {
+                                thrown_hit.push((character, item_type, damage));
+                            }
+                        }
+                    }
+                    // A thrown item falls to the floor beneath the character it hits
+                    if let Some(&item) = self.components.carried_item.get(entity) {
+                        landed.push((item, new_coord));
+                    }
+                }
+
+                // ignore collisiosns of projectiles
+                let _ = self.spatial_table.update_coord(entity, new_coord);
+            } else {
+                entities_to_remove.push(entity);
+                if let Some(&item) = self.components.carried_item.get(entity) {
+                    landed.push((item, current_coord));
+                }
+                // A fireball which reaches its target without hitting anything explodes there
+                if let Some(ProjectileType::Fireball { damage }) = projectile_type {
+                    fireball_hit.push((current_coord, damage, blast_radius));
+                }
             }
         }
         for entity in entities_to_remove {
             self.remove_entity(entity);
         }
-        for (entity, damage) in fireball_hit {
-            let maybe_npc = self.components.npc_type.get(entity).cloned();
-            if let Some(VictimDies) = self.character_damage(entity, damage) {
-                if let Some(npc) = maybe_npc {
-                    message_log.push(LogMessage::NpcDies(npc));
+        for (centre, damage, radius) in fireball_hit {
+            // The explosion leaves the cell at its centre burning if there's room for a fire
+            let _ = self.ignite(centre, FIRE_DURATION);
+            // The explosion can damage any character in range, including the player who launched
+            // the fireball.
+            for coord in self.coords_within_radius(centre, radius) {
+                if let Some(entity) = self.spatial_table.layers_at_checked(coord).character {
+                    let maybe_npc = self.components.npc_type.get(entity).cloned();
+                    if let Some(VictimDies) =
+                        self.character_attack_damage(entity, damage, DamageKind::Fire, rng)
+                    {
+                        if let Some(npc) = maybe_npc {
+                            message_log.push(LogMessage::NpcDies(npc));
+                        } else {
+                            message_log.push(LogMessage::PlayerDies);
+                        }
+                    }
+                }
+            }
+        }
+        for (entity, damage) in arrow_hit {
+            let maybe_npc = self.components.npc_type.get(entity).cloned();
+            let arrow = ProjectileType::Arrow { damage };
+            if let Some(VictimDies) =
+                self.character_attack_damage(entity, damage, DamageKind::Projectile, rng)
+            {
+                if let Some(npc) = maybe_npc {
+                    message_log.push(LogMessage::NpcDies(npc));
+                } else {
+                    message_log.push(LogMessage::PlayerDies);
+                }
+            } else if maybe_npc.is_none() {
+                message_log.push(LogMessage::PlayerIsHitByProjectile(arrow));
+            }
+        }
+        for (entity, item_type, damage) in thrown_hit {
+            let maybe_npc = self.components.npc_type.get(entity).cloned();
+            if let Some(VictimDies) =
+                self.character_attack_damage(entity, damage, DamageKind::Projectile, rng)
+            {
+                if let Some(npc) = maybe_npc {
+                    message_log.push(LogMessage::NpcDies(npc));
+                } else {
+                    message_log.push(LogMessage::PlayerDies);
+                }
+            } else if let Some(npc) = maybe_npc {
+                message_log.push(LogMessage::ThrownItemHitsNpc(item_type, npc));
+            }
+        }
+        for (item, coord) in landed {
+            self.land_item(item, coord);
+        }
+        for (entity, duration) in confusion_hit {
+            self.components.confusion_countdown.insert(entity, duration);
+            if let Some(&npc_type) = self.components.npc_type.get(entity) {
+                message_log.push(LogMessage::NpcBecomesConfused(npc_type));
+            }
+        }
+        for (entity, duration) in blindness_hit {
+            self.components.blind.insert(entity, duration);
+            if let Some(&npc_type) = self.components.npc_type.get(entity) {
+                message_log.push(LogMessage::NpcIsBlinded(npc_type));
+            }
+        }
+        for entity in polymorph_hit {
+            self.polymorph_npc(entity, message_log, rng);
+        }
+        for (entity, damage) in chain_lightning_hit {
+            self.chain_lightning(entity, damage, message_log, rng);
+        }
+        for (entity, duration) in net_hit {
+            self.components.rooted.insert(entity, duration);
+            if let Some(&npc_type) = self.components.npc_type.get(entity) {
+                message_log.push(LogMessage::NpcIsRooted(npc_type));
+            }
+        }
+    }
+    // Strikes `first` with lightning, which then jumps to the nearest character not yet struck
+    // within range, up to a fixed number of jumps. Each jump deals 1 less damage than the last, and
+    // the lightning doesn't distinguish between friend and foe.
+    fn chain_lightning<R: Rng>(
+        &mut self,
+        first: Entity,
+        damage: u32,
+        message_log: &mut Vec<LogMessage>,
+        rng: &mut R,
+    ) {
+        let mut struck = Vec::new();
+        let mut next = Some(first);
+        let mut damage = damage;
+        for _ in 0..=CHAIN_LIGHTNING_JUMPS {
+            let entity = match next {
+                Some(entity) if damage > 0 => entity,
+                _ => break,
+            };
+            struck.push(entity);
+            let coord = self.spatial_table.coord_of(entity).unwrap();
+            let maybe_npc = self.npc_type(entity);
+            match maybe_npc {
+                Some(npc_type) => message_log.push(LogMessage::PlayerZapsNpc(npc_type)),
+                None => message_log.push(LogMessage::PlayerIsHitByProjectile(
+                    ProjectileType::ChainLightning { damage },
+                )),
+            }
+            if let Some(VictimDies) = self.character_damage(entity, damage, DamageKind::Magic, rng)
+            {
+                if let Some(npc_type) = maybe_npc {
+                    message_log.push(LogMessage::NpcDies(npc_type));
+                } else {
+                    message_log.push(LogMessage::PlayerDies);
+                }
+            }
+            damage -= 1;
+            // The lightning jumps to the nearest visible character not yet struck, breaking ties
+            // by row-major coordinate order as in `nearest_character`
+            next = self
+                .coords_within_radius(coord, CHAIN_LIGHTNING_RADIUS)
+                .into_iter()
+                .filter_map(|other_coord| {
+                    self.spatial_table
+                        .layers_at_checked(other_coord)
+                        .character
+                        .map(|other| (other, other_coord))
+                })
+                .filter(|&(other, other_coord)| {
+                    !struck.contains(&other) && self.has_line_of_sight(coord, other_coord)
+                })
+                .min_by_key(|&(_, other_coord)| {
+                    let delta = other_coord - coord;
+                    (
+                        delta.x * delta.x + delta.y * delta.y,
+                        other_coord.y,
+                        other_coord.x,
+                    )
+                })
+                .map(|(other, _)| other);
+        }
+    }
+    // Turns an npc into a randomly chosen npc of a different type. Characters other than npcs are
+    // unaffected.
+    fn polymorph_npc<R: Rng>(
+        &mut self,
+        entity: Entity,
+        message_log: &mut Vec<LogMessage>,
+        rng: &mut R,
+    ) {
+        let from = match self.components.npc_type.get(entity) {
+            Some(&npc_type) => npc_type,
+            None => return,
+        };
+        let &to = NpcType::ALL
+            .iter()
+            .filter(|&&npc_type| npc_type != from)
+            .choose(rng)
+            .unwrap();
+        self.polymorph_npc_into(entity, to, message_log);
+    }
+    // Turns an npc into an npc of type `to`. The npc's hit points are restored to the full amount
+    // for its new type, scaled by the same difficulty as its old type.
+    fn polymorph_npc_into(
+        &mut self,
+        entity: Entity,
+        to: NpcType,
+        message_log: &mut Vec<LogMessage>,
+    ) {
+        let from = match self.components.npc_type.get(entity) {
+            Some(&npc_type) => npc_type,
+            None => return,
+        };
+        if let Some(hit_points) = self.components.hit_points.get_mut(entity) {
+            let max = (hit_points.max * to.base_hit_points() / from.base_hit_points()).max(1);
+            *hit_points = HitPoints::new_full(max);
+        }
+        self.components.stats.insert(entity, to.stats());
+        let (strength, dexterity) = to.strength_and_dexterity();
+        self.components.strength.insert(entity, strength);
+        self.components.dexterity.insert(entity, dexterity);
+        if to == NpcType::Troll {
+            self.components.knockback.insert(entity, ());
+        } else {
+            self.components.knockback.remove(entity);
+        }
+        self.components.speed.insert(entity, to.speed());
+        self.components.npc_type.insert(entity, to);
+        self.components.tile.insert(entity, Tile::Npc(to));
+        message_log.push(LogMessage::NpcPolymorphs { from, to });
+    }
+    // Places an item which isn't in the spatial table on the floor at `coord`, or in a neighbouring
+    // cell if there's already an object at `coord`. If there's nowhere for the item to go it's
+    // destroyed.
+    fn land_item(&mut self, item: Entity, coord: Coord) {
+        let destination = std::iter::once(coord)
+            .chain(Direction::all().map(|direction| coord + direction.coord()))
+            .find(|&candidate| {
+                self.spatial_table
+                    .layers_at(candidate)
+                    .map(|layers| {
+                        layers.floor.is_some()
+                            && layers.object.is_none()
+                            && !layers
+                                .feature
+                                .map(|feature| self.is_feature_solid(feature))
+                                .unwrap_or(false)
+                    })
+                    .unwrap_or(false)
+            });
+        match destination {
+            Some(destination) => self
+                .spatial_table
+                .update(
+                    item,
+                    Location {
+                        coord: destination,
+                        layer: Some(Layer::Object),
+                    },
+                )
+                .unwrap(),
+            None => self.remove_entity(item),
+        }
+    }
+    // Returns true if aiming the item at `target` would catch the player in the resulting
+    // explosion. The explosion happens where the projectile first hits something, which may be
+    // well short of the target.
+    pub fn aim_would_harm_self(&self, player: Entity, item_type: ItemType, target: Coord) -> bool {
+        let blast_radius = match item_type {
+            ItemType::FireballScroll | ItemType::FireballWand => {
+                ProjectileType::Fireball { damage: 0 }.blast_radius()
+            }
+            _ => return false,
+        };
+        let player_coord = match self.spatial_table.coord_of(player) {
+            Some(coord) => coord,
+            None => return false,
+        };
+        let impact = self
+            .aim_line(player_coord, target)
+            .last()
+            .cloned()
+            .unwrap_or(player_coord);
+        let delta = impact - player_coord;
+        delta.x.abs().max(delta.y.abs()) as u32 <= blast_radius
+    }
+    // The cells a projectile launched from `from` towards `to` would pass through, ending with the
+    // cell where it would land. As with a real projectile, the line stops short of the first solid
+    // feature in the way, and ends at the first character it reaches.
+    pub fn aim_line(&self, from: Coord, to: Coord) -> Vec<Coord> {
+        let mut line = Vec::new();
+        for coord in Trajectory::new(from, to) {
+            let layers = match self.spatial_table.layers_at(coord) {
+                Some(layers) => layers,
+                None => break,
+            };
+            if layers
+                .feature
+                .map(|feature| self.is_feature_solid(feature))
+                .unwrap_or(false)
+            {
+                break;
+            }
+            line.push(coord);
+            if layers.character.is_some() {
+                break;
+            }
+        }
+        line
+    }
+    // Returns every entity on any layer whose coordinate lies within the rectangle, after clamping
+    // the rectangle to the grid. Cells are visited in row major order, and the entities in each
+    // cell are ordered by layer from the floor up.
+    pub fn entities_in_rect(&self, top_left: Coord, size: Size) -> Vec<Entity> {
+        let grid_size = self.spatial_table.grid_size();
+        let start = Coord::new(top_left.x.max(0), top_left.y.max(0));
+        let end = Coord::new(
+            (top_left.x + size.width() as i32).min(grid_size.width() as i32),
+            (top_left.y + size.height() as i32).min(grid_size.height() as i32),
+        );
+        let mut entities = Vec::new();
+        for y in start.y..end.y {
+            for x in start.x..end.x {
+                let layers = self.spatial_table.layers_at_checked(Coord::new(x, y));
+                entities.extend(
+                    [
+                        layers.floor,
+                        layers.feature,
+                        layers.object,
+                        layers.character,
+                        layers.projectile,
+                    ]
+                    .iter()
+                    .flatten(),
+                );
+            }
+        }
+        entities
+    }
+    // Sorts entities into the row-major order of their coordinates, with entities that have no
+    // coordinate last. Component tables iterate in an order which depends on how their storage
+    // happens to be laid out, so anything which consumes random numbers or resolves conflicts
+    // between entities (such as two projectiles arriving in the same turn) processes entities in
+    // this order instead, so a seeded game always plays out the same way. The sort is stable, so
+    // entities sharing a cell (or both lacking a coordinate) keep the order they were passed in.
+    fn sort_by_coord(&self, entities: &mut [Entity]) {
+        entities.sort_by_key(|&entity| match self.spatial_table.coord_of(entity) {
+            Some(coord) => (false, coord.y, coord.x),
+            None => (true, 0, 0),
+        });
+    }
+    // Shakes up the terrain within `radius` of `centre`. Some walls collapse, exposing the floor
+    // beneath them, and new walls rise up from some empty floor. Walls on the edge of the map are
+    // left standing so the map stays enclosed, and no wall rises at `centre`, on a character, on
+    // an object, or on the stairs.
+    fn earthquake<R: Rng>(&mut self, centre: Coord, radius: u32, rng: &mut R) {
+        let size = self.spatial_table.grid_size();
+        for coord in self.coords_within_radius(centre, radius) {
+            if coord == centre
+                || coord.x <= 0
+                || coord.y <= 0
+                || coord.x >= size.width() as i32 - 1
+                || coord.y >= size.height() as i32 - 1
+            {
+                continue;
+            }
+            let layers = self.spatial_table.layers_at_checked(coord);
+            match layers.feature {
+                Some(feature) => {
+                    if matches!(self.components.tile.get(feature), Some(Tile::Wall))
+                        && rng.gen_range(0..100) < EARTHQUAKE_COLLAPSE_PERCENT
+                    {
+                        self.remove_entity(feature);
+                    }
+                }
+                None => {
+                    let is_empty_floor = layers.character.is_none()
+                        && layers.object.is_none()
+                        && layers
+                            .floor
+                            .map(|floor| !self.components.stairs.contains(floor))
+                            .unwrap_or(false);
+                    if is_empty_floor && rng.gen_range(0..100) < EARTHQUAKE_RAISE_PERCENT {
+                        let _ = self.spawn_wall(coord);
+                    }
+                }
+            }
+        }
+    }
+    // Returns all the coordinates within the grid whose chebyshev distance from `centre` is at most
+    // `radius`, in row major order
+    fn coords_within_radius(&self, centre: Coord, radius: u32) -> Vec<Coord> {
+        let size = self.spatial_table.grid_size();
+        let radius = radius as i32;
+        (-radius..=radius)
+            .flat_map(|dy| (-radius..=radius).map(move |dx| centre + Coord::new(dx, dy)))
+            .filter(|coord| coord.is_valid(size))
+            .collect()
+    }
+    pub fn npc_fire_projectile(
+        &mut self,
+        entity: Entity,
+        target: Coord,
+        message_log: &mut Vec<LogMessage>,
+    ) {
+        let npc_coord = self
+            .spatial_table
+            .coord_of(entity)
+            .expect("npc has no coord");
+        let &npc_type = self
+            .components
+            .npc_type
+            .get(entity)
+            .expect("npc has no type");
+        const ARROW_DAMAGE: u32 = 2;
+        // An arrow can pass through one character (e.g. another npc in the way) and keep going
+        const ARROW_PIERCING: u32 = 1;
+        let arrow = ProjectileType::Arrow {
+            damage: ARROW_DAMAGE,
+        };
+        // The archer holds its fire if there's already a projectile in its cell
+        if let Ok(projectile) = self.spawn_projectile(npc_coord, target, arrow) {
+            self.components.piercing.insert(projectile, ARROW_PIERCING);
+            message_log.push(LogMessage::NpcLaunchesProjectile(npc_type, arrow));
+        }
+    }
+    pub fn has_projectiles(&self) -> bool {
+        !self.components.trajectory.is_empty()
+    }
+    // Returns each projectile still in flight along with its position and type. Projectiles which
+    // have been removed from the map but not yet cleaned up are skipped.
+    pub fn projectiles(&self) -> Vec<(Entity, Coord, ProjectileType)> {
+        self.components
+            .trajectory
+            .entities()
+            .filter_map(|entity| {
+                let coord = self.spatial_table.coord_of(entity)?;
+                let &projectile_type = self.components.projectile.get(entity)?;
+                Some((entity, coord, projectile_type))
+            })
+            .collect()
+    }
+    pub fn inventory(&self, entity: Entity) -> Option<&Inventory> {
+        self.components.inventory.get(entity)
+    }
+    pub fn item_type(&self, entity: Entity) -> Option<ItemType> {
+        self.components.item.get(entity).cloned()
+    }
+    pub fn npc_type(&self, entity: Entity) -> Option<NpcType> {
+        self.components.npc_type.get(entity).cloned()
+    }
+    // Returns the living character closest to `from` (by euclidean distance) for which
+    // `predicate` returns true, along with its coordinate. Ties are broken by row-major
+    // coordinate order.
+    pub fn nearest_character(
+        &self,
+        from: Coord,
+        predicate: impl Fn(Entity) -> bool,
+    ) -> Option<(Entity, Coord)> {
+        self.components
+            .hit_points
+            .entities()
+            .filter(|&entity| self.is_living_character(entity) && predicate(entity))
+            .filter_map(|entity| {
+                let coord = self.spatial_table.coord_of(entity)?;
+                let delta = coord - from;
+                let distance_squared = delta.x * delta.x + delta.y * delta.y;
+                Some(((distance_squared, coord.y, coord.x), (entity, coord)))
+            })
+            .min_by_key(|&(key, _)| key)
+            .map(|(_, nearest)| nearest)
+    }
+    // Returns the closest living character hostile to `entity` which is within `range` and in
+    // line of sight of `entity`. Ties are broken by row-major coordinate order.
+    pub fn nearest_visible_hostile(&self, entity: Entity, range: u32) -> Option<Entity> {
+        let coord = self.spatial_table.coord_of(entity)?;
+        let range_squared = (range * range) as i32;
+        self.nearest_character(coord, |other| {
+            if !self.is_hostile_to(entity, other) {
+                return false;
+            }
+            let other_coord = match self.spatial_table.coord_of(other) {
+                Some(other_coord) => other_coord,
+                None => return false,
+            };
+            let delta = other_coord - coord;
+            delta.x * delta.x + delta.y * delta.y <= range_squared
+                && self.has_line_of_sight(coord, other_coord)
+        })
+        .map(|(other, _)| other)
+    }
+    // Returns the coordinate of the next living character hostile to the player after `from` in
+    // reading order, among the characters at `visible_coords`. Wraps around to the first such
+    // character after the last.
+    pub fn next_visible_character(&self, from: Coord, visible_coords: &[Coord]) -> Option<Coord> {
+        let mut coords = visible_coords
+            .iter()
+            .cloned()
+            .filter(|&coord| {
+                self.spatial_table
+                    .layers_at(coord)
+                    .and_then(|layers| layers.character)
+                    .map(|character| {
+                        self.is_living_character(character)
+                            && self
+                                .faction(character)
+                                .map(|faction| faction.is_hostile_to(Faction::Player))
+                                .unwrap_or(false)
+                    })
+                    .unwrap_or(false)
+            })
+            .collect::<Vec<_>>();
+        coords.sort_by_key(|coord| (coord.y, coord.x));
+        coords
+            .iter()
+            .cloned()
+            .find(|coord| (coord.y, coord.x) > (from.y, from.x))
+            .or_else(|| coords.first().cloned())
+    }
+    pub fn faction(&self, entity: Entity) -> Option<Faction> {
+        self.components.faction.get(entity).cloned()
+    }
+    fn is_hostile_to(&self, entity: Entity, other: Entity) -> bool {
+        match (
+            self.components.faction.get(entity),
+            self.components.faction.get(other),
+        ) {
+            (Some(&faction), Some(&other_faction)) => faction.is_hostile_to(other_faction),
+            _ => false,
+        }
+    }
+    pub fn entity_is_blind(&self, entity: Entity) -> bool {
+        self.components.blind.contains(entity)
+    }
+    pub fn entity_is_hasted(&self, entity: Entity) -> bool {
+        self.components.haste.contains(entity)
+    }
+    pub fn is_confused(&self, entity: Entity) -> bool {
+        self.components.confusion_countdown.contains(entity)
+    }
+    // Returns the coordinates of every living character. Dead characters remain in the world as
+    // corpses, so they are skipped.
+    pub fn living_character_coords(&self) -> Vec<Coord> {
+        self.components
+            .hit_points
+            .entities()
+            .filter(|&entity| self.is_living_character(entity))
+            .filter_map(|entity| self.spatial_table.coord_of(entity))
+            .collect()
+    }
+    pub fn is_living_character(&self, entity: Entity) -> bool {
+        self.spatial_table.layer_of(entity) == Some(Layer::Character)
+    }
+    pub fn remove_entity(&mut self, entity: Entity) {
+        self.components.remove_entity(entity);
+        self.spatial_table.remove(entity);
+        self.entity_allocator.free(entity);
+    }
+    pub fn size(&self) -> Size {
+        self.spatial_table.grid_size()
+    }
+    // Every coordinate containing a floor (including those beneath walls), in row-major order
+    pub fn all_floor_coords(&self) -> Vec<Coord> {
+        let size = self.spatial_table.grid_size();
+        (0..size.height() as i32)
+            .flat_map(|y| (0..size.width() as i32).map(move |x| Coord::new(x, y)))
+            .filter(|&coord| self.spatial_table.layers_at_checked(coord).floor.is_some())
+            .collect()
+    }
+    // Every entity which has both a tile and a location on a layer, ordered by layer (from the
+    // bottom up) and then by row-major coordinate so that rendering is stable
+    pub fn entities_with_tiles(&self) -> impl Iterator<Item = (Entity, Coord, Tile, Layer)> {
+        fn layer_depth(layer: Layer) -> u8 {
+            match layer {
+                Layer::Floor => 0,
+                Layer::Feature => 1,
+                Layer::Object => 2,
+                Layer::Character => 3,
+                Layer::Projectile => 4,
+            }
+        }
+        let mut entities = self
+            .components
+            .tile
+            .iter()
+            .filter(|&(entity, _)| self.components.hidden.get(entity) != Some(&true))
+            .filter_map(|(entity, &tile)| {
+                let location = self.spatial_table.location_of(entity)?;
+                Some((entity, location.coord, tile, location.layer?))
+            })
+            .collect::<Vec<_>>();
+        entities.sort_by_key(|&(_, coord, _, layer)| (layer_depth(layer), coord.y, coord.x));
+        entities.into_iter()
+    }
+    pub fn opacity_at(&self, coord: Coord) -> u8 {
+        if self
+            .spatial_table
+            .layers_at_checked(coord)
+            .feature
+            .map(|feature| self.is_feature_solid(feature))
+            .unwrap_or(false)
+        {
+            255
+        } else {
+            0
+        }
+    }
+    pub fn hit_points(&self, entity: Entity) -> Option<HitPoints> {
+        self.components.hit_points.get(entity).cloned()
+    }
+    pub fn entity_coord(&self, entity: Entity) -> Option<Coord> {
+        self.spatial_table.coord_of(entity)
+    }
+    pub fn can_npc_enter_ignoring_other_npcs(&self, coord: Coord) -> bool {
+        self.spatial_table
+            .layers_at(coord)
+            .map(|layers| self.can_npc_pass_feature(layers.feature))
+            .unwrap_or(false)
+    }
+    // Npcs can pass through any door, opening it first if it's closed, and walk over traps they
+    // don't know are there. Unlike traps, fire is plain to see, so npcs won't walk into it
+    // (though fire can still spread into a cell an npc is standing in).
+    fn can_npc_pass_feature(&self, feature: Option<Entity>) -> bool {
+        feature
+            .map(|feature| {
+                (self.components.door.contains(feature) && !self.components.lock.contains(feature))
+                    || self.components.trap.contains(feature)
+            })
+            .unwrap_or(true)
+    }
+    // Breadth-first search for a shortest path from `from` to `to` through cells which npcs can
+    // enter, returning the first step of the path. The destination itself need not be enterable
+    // as it's typically occupied by the character being approached.
+    pub fn path_toward(&self, from: Coord, to: Coord) -> Option<CardinalDirection> {
+        self.path_toward_through(from, to, |coord| self.can_npc_enter(coord))
+    }
+    // As `path_toward`, but the path may also pass through walls which could be dug out
+    pub fn path_toward_digging(&self, from: Coord, to: Coord) -> Option<CardinalDirection> {
+        self.path_toward_through(from, to, |coord| {
+            self.can_npc_enter(coord) || self.can_npc_dig(coord)
+        })
+    }
+    fn path_toward_through<F: Fn(Coord) -> bool>(
+        &self,
+        from: Coord,
+        to: Coord,
+        can_enter: F,
+    ) -> Option<CardinalDirection> {
+        let mut first_steps: Grid<Option<CardinalDirection>> =
+            Grid::new_copy(self.spatial_table.grid_size(), None);
+        let mut queue = VecDeque::new();
+        for direction in CardinalDirection::all() {
+            let coord = from + direction.coord();
+            if coord == to {
+                return Some(direction);
+            }
+            if can_enter(coord) {
+                *first_steps.get_checked_mut(coord) = Some(direction);
+                queue.push_back(coord);
+            }
+        }
+        while let Some(coord) = queue.pop_front() {
+            let first_step = *first_steps.get_checked(coord);
+            for direction in CardinalDirection::all() {
+                let next_coord = coord + direction.coord();
+                if next_coord == to {
+                    return first_step;
+                }
+                if next_coord == from
+                    || !can_enter(next_coord)
+                    || first_steps.get_checked(next_coord).is_some()
+                {
+                    continue;
+                }
+                *first_steps.get_checked_mut(next_coord) = first_step;
+                queue.push_back(next_coord);
+            }
+        }
+        None
+    }
+    // Flood fills outwards from `coord` through cells with a floor and no feature, returning every
+    // cell reached. Traps never stop the fill, and doors only stop it if `doors_are_boundaries` is
+    // true. Returns an empty vector if `coord` itself can't be filled.
+    pub fn room_coords_containing(&self, coord: Coord, doors_are_boundaries: bool) -> Vec<Coord> {
+        let can_fill = |coord: Coord| {
+            self.spatial_table
+                .layers_at(coord)
+                .map(|layers| {
+                    layers.floor.is_some()
+                        && layers
+                            .feature
+                            .map(|feature| {
+                                self.components.trap.contains(feature)
+                                    || (!doors_are_boundaries
+                                        && self.components.door.contains(feature))
+                            })
+                            .unwrap_or(true)
+                })
+                .unwrap_or(false)
+        };
+        if !can_fill(coord) {
+            return Vec::new();
+        }
+        let mut visited = Grid::new_copy(self.spatial_table.grid_size(), false);
+        let mut queue = VecDeque::new();
+        let mut room = Vec::new();
+        *visited.get_checked_mut(coord) = true;
+        queue.push_back(coord);
+        while let Some(coord) = queue.pop_front() {
+            room.push(coord);
+            for direction in CardinalDirection::all() {
+                let next_coord = coord + direction.coord();
+                if !can_fill(next_coord) || *visited.get_checked(next_coord) {
+                    continue;
+                }
+                *visited.get_checked_mut(next_coord) = true;
+                queue.push_back(next_coord);
+            }
+        }
+        room
+    }
+    // The player can only step into cells with a floor and no character or solid feature. Moving
+    // into other cells may still do something, such as attacking or opening a door.
+    pub fn can_player_enter(&self, coord: Coord) -> bool {
+        self.spatial_table
+            .layers_at(coord)
+            .map(|layers| {
+                layers.floor.is_some()
+                    && layers.character.is_none()
+                    && layers
+                        .feature
+                        .is_none_or(|feature| !self.is_feature_solid(feature))
+            })
+            .unwrap_or(false)
+    }
+    // Returns the character the player would attack by moving into `coord`, if any
+    pub fn player_would_attack_at(&self, player: Entity, coord: Coord) -> Option<Entity> {
+        self.spatial_table
+            .layers_at(coord)?
+            .character
+            .filter(|&character| self.is_hostile_to(player, character))
+    }
+    pub fn can_npc_enter(&self, coord: Coord) -> bool {
+        self.spatial_table
+            .layers_at(coord)
+            .map(|layers| {
+                let contains_npc = layers
+                    .character
+                    .map(|entity| self.components.npc_type.contains(entity))
+                    .unwrap_or(false);
+                !contains_npc && self.can_npc_pass_feature(layers.feature)
+            })
+            .unwrap_or(false)
+    }
+    // Returns true if and only if none of the cells strictly between `from` and `to` are opaque.
+    // The endpoints themselves are never considered, so a coord can always see itself and its
+    // neighbours.
+    pub fn has_line_of_sight(&self, from: Coord, to: Coord) -> bool {
+        if from == to {
+            return true;
+        }
+        LineSegment::new(from, to)
+            .iter()
+            .filter(|&coord| coord != from && coord != to)
+            .all(|coord| self.opacity_at(coord) < 255)
+    }
+    // The top-most entity at `coord` which the player can interact with, preferring a character
+    // over an object
+    pub fn entity_at_cursor(&self, coord: Coord) -> Option<Entity> {
+        let layers = self.spatial_table.layers_at(coord)?;
+        layers.character.or(layers.object)
+    }
+    // Items are described as `player` knows them
+    pub fn examine_cell(&self, player: Entity, coord: Coord) -> Option<ExamineCell> {
+        self.entity_at_cursor(coord)
+            .and_then(|entity| {
+                self.components
+                    .tile
+                    .get(entity)
+                    .and_then(|&tile| match tile {
+                        Tile::Npc(npc_type) => self
+                            .components
+                            .hit_points
+                            .get(entity)
+                            .map(|&hit_points| ExamineCell::Npc(npc_type, hit_points)),
+                        Tile::NpcCorpse(npc_type) => Some(ExamineCell::NpcCorpse(npc_type)),
+                        Tile::Item(item_type) => Some(ExamineCell::Item(
+                            item_type,
+                            self.item_is_identified(player, entity),
+                        )),
+                        Tile::Player => Some(ExamineCell::Player),
+                        _ => None,
+                    })
+            })
+            .or_else(|| self.examine_terrain(coord))
+    }
+    pub fn examine_terrain(&self, coord: Coord) -> Option<ExamineCell> {
+        let layers = self.spatial_table.layers_at(coord)?;
+        let feature_tile = layers
+            .feature
+            .and_then(|feature| self.components.tile.get(feature));
+        match feature_tile {
+            Some(Tile::Wall) | Some(Tile::SecretWall) => return Some(ExamineCell::Wall),
+            Some(Tile::DoorClosed) | Some(Tile::DoorOpen) | Some(Tile::LockedDoor(_)) => {
+                return Some(ExamineCell::Door)
+            }
+            _ => (),
+        }
+        let floor = layers.floor?;
+        if self.components.stairs.contains(floor) {
+            Some(ExamineCell::Stairs)
+        } else {
+            Some(ExamineCell::Floor)
+        }
+    }
+    fn remove_entity_data(&mut self, entity: Entity) -> EntityData {
+        self.entity_allocator.free(entity);
+        self.spatial_table.remove(entity);
+        self.components.remove_entity_data(entity)
+    }
+    pub fn remove_character(&mut self, entity: Entity) -> CharacterData {
+        let mut entity_data = self.remove_entity_data(entity);
+        // Remove the inventory from the character. An inventory contains entities referring data
+        // in the current world. These data will also be removed here, and combined with the
+        // `EntityData` of the character to form a `CharacterData`. When the `CharacterData` is
+        // re-inserted into the world, the inventory item data will be inserted first, at which
+        // point each item will be assigned a fresh entity. The character will get a brand new
+        // inventory containing the new entities.
+        let inventory_entity_data = entity_data
+            .inventory
+            .take()
+            .expect("character missing inventory")
+            .slots
+            .into_iter()
+            .map(|maybe_slot| {
+                maybe_slot.map(|slot| {
+                    slot.entities
+                        .into_iter()
+                        .map(|entity| self.remove_entity_data(entity))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        // Equipped items are removed in the same way as inventory items
+        let equipment_entity_data = entity_data
+            .equipment
+            .take()
+            .map(|equipment| {
+                equipment
+                    .iter()
+                    .map(|(slot, entity)| (slot, self.remove_entity_data(entity)))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        CharacterData {
+            entity_data,
+            inventory_entity_data,
+            equipment_entity_data,
+        }
+    }
+    pub fn replace_character(
+        &mut self,
+        entity: Entity,
+        CharacterData {
+            mut entity_data,
+            inventory_entity_data,
+            equipment_entity_data,
+        }: CharacterData,
+    ) {
+        // Before inserting the character's data, create new entities to contain each item in the
+        // character's inventory.
+        let inventory_slots = inventory_entity_data
+            .into_iter()
+            .map(|maybe_slot_entity_data| {
+                maybe_slot_entity_data.map(|slot_entity_data| {
+                    let item_type = slot_entity_data
+                        .first()
+                        .and_then(|entity_data| entity_data.item)
+                        .expect("non-item in inventory");
+                    let entities = slot_entity_data
+                        .into_iter()
+                        .map(|entity_data| {
+                            let entity = self.entity_allocator.alloc();
+                            self.components.update_entity_data(entity, entity_data);
+                            entity
+                        })
+                        .collect::<Vec<_>>();
+                    InventorySlot {
+                        item_type,
+                        entities,
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        // Make a new inventory containing the newly created entities, and add it to the character.
+        entity_data.inventory = Some(Inventory {
+            slots: inventory_slots,
+        });
+        let mut equipment = EquipmentSlots::default();
+        for (slot, item_entity_data) in equipment_entity_data {
+            let item = self.entity_allocator.alloc();
+            self.components.update_entity_data(item, item_entity_data);
+            *equipment.slot_mut(slot) = Some(item);
+        }
+        entity_data.equipment = Some(equipment);
+        self.components.update_entity_data(entity, entity_data);
+    }
+    pub fn coord_contains_stairs(&self, coord: Coord) -> bool {
+        self.spatial_table
+            .layers_at_checked(coord)
+            .floor
+            .map(|floor_entity| self.components.stairs.contains(floor_entity))
+            .unwrap_or(false)
+    }
+    // Returns the total weight of the items a character is carrying, and the most they can carry.
+    // Stronger characters can carry more.
+    pub fn player_carry_weight(&self, entity: Entity) -> (u32, u32) {
+        let carried = self
+            .components
+            .inventory
+            .get(entity)
+            .map(Inventory::total_weight)
+            .unwrap_or(0);
+        let strength = self.components.strength.get(entity).cloned().unwrap_or(0);
+        let capacity = BASE_CARRY_CAPACITY + CARRY_CAPACITY_PER_STRENGTH * strength.max(0) as u32;
+        (carried, capacity)
+    }
+    // Strength below zero gives no bonus, so it's reported as zero
+    pub fn player_strength(&self, entity: Entity) -> Option<u32> {
+        self.components
+            .strength
+            .get(entity)
+            .map(|&strength| strength.max(0) as u32)
+    }
+    pub fn player_gold(&self, entity: Entity) -> u32 {
+        self.components.gold.get(entity).cloned().unwrap_or(0)
+    }
+    // Lists every status effect currently applied to the entity
+    pub fn active_status_effects(&self, entity: Entity) -> Vec<StatusEffect> {
+        let components = &self.components;
+        [
+            components
+                .poison
+                .get(entity)
+                .map(|&turns| StatusEffect::Poisoned(turns)),
+            components
+                .bleed
+                .get(entity)
+                .map(|&turns| StatusEffect::Bleeding(turns)),
+            components
+                .confusion_countdown
+                .get(entity)
+                .map(|&turns| StatusEffect::Confused(turns)),
+            components
+                .haste
+                .get(entity)
+                .map(|&turns| StatusEffect::Hasted(turns)),
+            components
+                .blind
+                .get(entity)
+                .map(|&turns| StatusEffect::Blind(turns)),
+            components
+                .rooted
+                .get(entity)
+                .map(|&turns| StatusEffect::Rooted(turns)),
+        ]
+        .iter()
+        .flatten()
+        .cloned()
+        .collect()
+    }
+    pub fn player_nutrition(&self, entity: Entity) -> Option<u32> {
+        self.components.nutrition.get(entity).cloned()
+    }
+    pub fn dexterity(&self, entity: Entity) -> Option<i32> {
+        self.components.dexterity.get(entity).cloned()
+    }
+    pub fn intelligence(&self, entity: Entity) -> Option<i32> {
+        self.components.intelligence.get(entity).cloned()
+    }
+    pub fn level_up_character(&mut self, character_entity: Entity, level_up: LevelUp) {
+        match level_up {
+            LevelUp::Strength => {
+                *self
+                    .components
+                    .strength
+                    .get_mut(character_entity)
+                    .expect("character lacks strength") += 1;
+            }
+            LevelUp::Dexterity => {
+                *self
+                    .components
+                    .dexterity
+                    .get_mut(character_entity)
+                    .expect("character lacks dexterity") += 1;
+            }
+            LevelUp::Intelligence => {
+                *self
+                    .components
+                    .intelligence
+                    .get_mut(character_entity)
+                    .expect("character lacks intelligence") += 1;
+            }
+            LevelUp::Health => {
+                let hit_points = self
+                    .components
+                    .hit_points
+                    .get_mut(character_entity)
+                    .expect("character lacks hit points");
+                const INCREASE: u32 = 5;
+                hit_points.current += INCREASE;
+                hit_points.max += INCREASE;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::behaviour::{BehaviourContext, NpcAction};
+
+    // A world whose every cell is floor, so tests can place characters and features anywhere
+    fn open_world(size: Size) -> World {
+        let mut world = World::new(size);
+        for coord in size.coord_iter_row_major() {
+            world.spawn_floor(coord).unwrap();
+        }
+        world
+    }
+
+    // Builds a world from rows of text, where '#' is a wall and every other cell is floor
+    fn world_from_rows(rows: &[&str]) -> World {
+        let size = Size::new(rows[0].len() as u32, rows.len() as u32);
+        let mut world = open_world(size);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, ch) in row.chars().enumerate() {
+                if ch == '#' {
+                    world.spawn_wall(Coord::new(x as i32, y as i32)).unwrap();
+                }
+            }
+        }
+        world
+    }
+
+    fn rng() -> Isaac64Rng {
+        Isaac64Rng::seed_from_u64(0)
+    }
+
+    // A player whose attacks always deal exactly their base damage plus their weapon damage
+    fn steady_player_config(starting_items: Vec<ItemType>) -> PlayerConfig {
+        PlayerConfig {
+            strength: 0,
+            dexterity: 0,
+            starting_items,
+            ..Default::default()
+        }
+    }
+
+    // An npc which can't dodge, so every attack against it deals a predictable amount of damage
+    fn spawn_steady_npc(world: &mut World, coord: Coord, npc_type: NpcType) -> Entity {
+        let entity = world
+            .spawn_npc(coord, npc_type, DifficultyScale::default())
+            .unwrap();
+        world.components.dexterity.insert(entity, 0);
+        entity
+    }
+
+    fn manhattan_distance(a: Coord, b: Coord) -> i32 {
+        let delta = a - b;
+        delta.x.abs() + delta.y.abs()
+    }
+
+    #[test]
+    fn sword_kills_orc_in_one_hit() {
+        let mut world = open_world(Size::new(5, 5));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(
+                Coord::new(1, 1),
+                &steady_player_config(vec![ItemType::Sword]),
+            )
+            .unwrap();
+        let orc = spawn_steady_npc(&mut world, Coord::new(2, 1), NpcType::Orc);
+        world
+            .maybe_use_item(player, 0, &mut message_log, &mut rng)
+            .unwrap();
+        assert_eq!(
+            world.maybe_move_character(player, Direction::East, &mut message_log, &mut rng),
+            MoveResult::Attacked { killed: true }
+        );
+        assert!(!world.is_living_character(orc));
+    }
+
+    #[test]
+    fn unarmed_player_kills_orc_in_two_hits() {
+        let mut world = open_world(Size::new(5, 5));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(Coord::new(1, 1), &steady_player_config(Vec::new()))
+            .unwrap();
+        spawn_steady_npc(&mut world, Coord::new(2, 1), NpcType::Orc);
+        assert_eq!(
+            world.maybe_move_character(player, Direction::East, &mut message_log, &mut rng),
+            MoveResult::Attacked { killed: false }
+        );
+        assert_eq!(
+            world.maybe_move_character(player, Direction::East, &mut message_log, &mut rng),
+            MoveResult::Attacked { killed: true }
+        );
+    }
+
+    #[test]
+    fn armour_reduces_fireball_damage() {
+        let mut world = open_world(Size::new(5, 5));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(Coord::new(3, 1), &PlayerConfig::default())
+            .unwrap();
+        let armour = world.create_item(ItemType::Armour);
+        let shield = world.create_item(ItemType::Shield);
+        world.components.equipment.insert(
+            player,
+            EquipmentSlots {
+                armour: Some(armour),
+                offhand: Some(shield),
+                ..Default::default()
+            },
+        );
+        assert_eq!(world.defense_modifier(player), 2);
+        world
+            .spawn_projectile(
+                Coord::new(1, 1),
+                Coord::new(3, 1),
+                ProjectileType::Fireball { damage: 2 },
+            )
+            .unwrap();
+        while world.has_projectiles() {
+            world.move_projectiles(&mut message_log, &mut rng);
+        }
+        assert_eq!(world.hit_points(player).unwrap().current, 19);
+    }
+
+    #[test]
+    fn confused_orc_wanders_instead_of_approaching() {
+        // The player is in the middle of the room so the orc never loses sight of them
+        let mut world = open_world(Size::new(11, 11));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(Coord::new(5, 5), &PlayerConfig::default())
+            .unwrap();
+        let orc = world
+            .spawn_npc(Coord::new(8, 5), NpcType::Orc, DifficultyScale::default())
+            .unwrap();
+        const DURATION: u32 = 8;
+        world.components.confusion_countdown.insert(orc, DURATION);
+        let mut agent = Agent::new();
+        let mut behaviour_context = BehaviourContext::new(world.size());
+        let distance_to_player = |world: &World| {
+            manhattan_distance(
+                world.entity_coord(orc).unwrap(),
+                world.entity_coord(player).unwrap(),
+            )
+        };
+        let mut confused_turns = 0;
+        let mut approaching_turns = 0;
+        while world.is_confused(orc) {
+            let distance_before = distance_to_player(&world);
+            behaviour_context.update(player, &world);
+            if let NpcAction::Move(direction) =
+                agent.act(orc, player, &world, &mut behaviour_context, &mut rng)
+            {
+                world.maybe_move_character(orc, direction.direction(), &mut message_log, &mut rng);
+            }
+            if distance_to_player(&world) < distance_before {
+                approaching_turns += 1;
+            }
+            confused_turns += 1;
+        }
+        // The countdown reaches zero on the last confused turn, and is removed on the turn after
+        assert_eq!(confused_turns, DURATION + 1);
+        assert!(approaching_turns < confused_turns);
+        // Once the confusion wears off the orc heads straight for the player
+        behaviour_context.update(player, &world);
+        let orc_coord = world.entity_coord(orc).unwrap();
+        match agent.act(orc, player, &world, &mut behaviour_context, &mut rng) {
+            NpcAction::Move(direction) => assert!(
+                manhattan_distance(
+                    orc_coord + direction.coord(),
+                    world.entity_coord(player).unwrap()
+                ) < manhattan_distance(orc_coord, world.entity_coord(player).unwrap())
+            ),
+            _ => panic!("orc didn't approach the player"),
+        }
+    }
+
+    #[test]
+    fn archer_with_line_of_sight_fires_arrow() {
+        let mut world = open_world(Size::new(8, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(Coord::new(6, 1), &PlayerConfig::default())
+            .unwrap();
+        let archer = world
+            .spawn_npc(
+                Coord::new(1, 1),
+                NpcType::Archer,
+                DifficultyScale::default(),
+            )
+            .unwrap();
+        let mut agent = Agent::new();
+        let mut behaviour_context = BehaviourContext::new(world.size());
+        behaviour_context.update(player, &world);
+        match agent.act(archer, player, &world, &mut behaviour_context, &mut rng) {
+            NpcAction::FireProjectile(target) => {
+                assert_eq!(target, Coord::new(6, 1));
+                world.npc_fire_projectile(archer, target, &mut message_log);
+            }
+            _ => panic!("archer didn't fire at the player"),
+        }
+        let projectiles = world.projectiles();
+        assert_eq!(projectiles.len(), 1);
+        assert!(matches!(projectiles[0].2, ProjectileType::Arrow { .. }));
+    }
+
+    #[test]
+    fn wall_blocks_line_of_sight() {
+        let mut world = open_world(Size::new(7, 3));
+        assert!(world.has_line_of_sight(Coord::new(1, 1), Coord::new(5, 1)));
+        world.spawn_wall(Coord::new(3, 1)).unwrap();
+        assert!(!world.has_line_of_sight(Coord::new(1, 1), Coord::new(5, 1)));
+        assert!(!world.has_line_of_sight(Coord::new(5, 1), Coord::new(1, 1)));
+        // The wall itself and the cells on either side of it can still be seen
+        assert!(world.has_line_of_sight(Coord::new(1, 1), Coord::new(3, 1)));
+        assert!(world.has_line_of_sight(Coord::new(2, 1), Coord::new(3, 1)));
+        assert!(world.has_line_of_sight(Coord::new(3, 1), Coord::new(3, 1)));
+        // Cells off to the side of the wall are unaffected
+        assert!(world.has_line_of_sight(Coord::new(1, 0), Coord::new(5, 0)));
+    }
+
+    #[test]
+    fn save_and_load_preserves_player_and_projectiles() {
+        let mut world = World::new(Size::new(40, 30));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let Populate { player_entity, .. } = world.populate(
+            1,
+            &PlayerConfig::default(),
+            &MonsterSpawnTable::default(),
+            &GenerationConfig::default(),
+            DifficultyScale::default(),
+            &mut rng,
+        );
+        world
+            .components
+            .hit_points
+            .get_mut(player_entity)
+            .unwrap()
+            .current -= 3;
+        let player_coord = world.entity_coord(player_entity).unwrap();
+        world
+            .spawn_projectile(
+                player_coord,
+                Coord::new(0, 0),
+                ProjectileType::Fireball { damage: 2 },
+            )
+            .unwrap();
+        world.move_projectiles(&mut message_log, &mut rng);
+        let projectiles = world.projectiles();
+        let mut loaded = World::load(&world.save()).unwrap();
+        assert_eq!(loaded.entity_coord(player_entity), Some(player_coord));
+        assert_eq!(
+            loaded.hit_points(player_entity).unwrap().current,
+            world.hit_points(player_entity).unwrap().current
+        );
+        assert_eq!(
+            loaded
+                .projectiles()
+                .iter()
+                .map(|&(entity, coord, _)| (entity, coord))
+                .collect::<Vec<_>>(),
+            projectiles
+                .iter()
+                .map(|&(entity, coord, _)| (entity, coord))
+                .collect::<Vec<_>>()
+        );
+        // The projectile carries on along its trajectory after loading
+        loaded.move_projectiles(&mut message_log, &mut rng);
+        world.move_projectiles(&mut message_log, &mut rng);
+        assert_eq!(
+            loaded
+                .projectiles()
+                .iter()
+                .map(|&(_, coord, _)| coord)
+                .collect::<Vec<_>>(),
+            world
+                .projectiles()
+                .iter()
+                .map(|&(_, coord, _)| coord)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn thrown_health_potion_heals_adjacent_npc() {
+        let mut world = open_world(Size::new(5, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(
+                Coord::new(1, 1),
+                &steady_player_config(vec![ItemType::HealthPotion]),
+            )
+            .unwrap();
+        let troll = world
+            .spawn_npc(Coord::new(2, 1), NpcType::Troll, DifficultyScale::default())
+            .unwrap();
+        world.components.hit_points.get_mut(troll).unwrap().current = 1;
+        world
+            .maybe_throw_item(player, 0, Coord::new(2, 1), &mut message_log, &mut rng)
+            .unwrap();
+        assert_eq!(world.hit_points(troll).unwrap().current, 1 + HEALTH_TO_HEAL);
+        assert!(world.inventory_item_type(player, 0).is_none());
+    }
+
+    #[test]
+    fn health_potion_cannot_be_thrown_through_wall() {
+        let mut world = open_world(Size::new(5, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(
+                Coord::new(1, 1),
+                &steady_player_config(vec![ItemType::HealthPotion]),
+            )
+            .unwrap();
+        world.spawn_wall(Coord::new(2, 1)).unwrap();
+        let troll = world
+            .spawn_npc(Coord::new(3, 1), NpcType::Troll, DifficultyScale::default())
+            .unwrap();
+        world.components.hit_points.get_mut(troll).unwrap().current = 1;
+        assert!(world
+            .maybe_throw_item(player, 0, Coord::new(3, 1), &mut message_log, &mut rng)
+            .is_err());
+        assert_eq!(world.hit_points(troll).unwrap().current, 1);
+        assert_eq!(
+            world.inventory_item_type(player, 0),
+            Some(ItemType::HealthPotion)
+        );
+    }
+
+    #[test]
+    fn player_moves_and_attacks_diagonally() {
+        let mut world = open_world(Size::new(5, 5));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(Coord::new(1, 3), &steady_player_config(Vec::new()))
+            .unwrap();
+        assert_eq!(
+            world.maybe_move_character(player, Direction::NorthEast, &mut message_log, &mut rng),
+            MoveResult::Moved
+        );
+        assert_eq!(world.entity_coord(player), Some(Coord::new(2, 2)));
+        let orc = spawn_steady_npc(&mut world, Coord::new(3, 1), NpcType::Orc);
+        assert_eq!(
+            world.maybe_move_character(player, Direction::NorthEast, &mut message_log, &mut rng),
+            MoveResult::Attacked { killed: false }
+        );
+        assert_eq!(world.entity_coord(player), Some(Coord::new(2, 2)));
+        assert_eq!(world.hit_points(orc).unwrap().current, 1);
+    }
+
+    #[test]
+    fn dying_npcs_drop_loot_beside_their_corpse() {
+        let mut num_drops = 0;
+        for seed in 0..100 {
+            let mut world = open_world(Size::new(5, 5));
+            let mut rng = Isaac64Rng::seed_from_u64(seed);
+            let troll = world
+                .spawn_npc(Coord::new(2, 2), NpcType::Troll, DifficultyScale::default())
+                .unwrap();
+            assert!(world
+                .character_damage(troll, 100, DamageKind::Melee, &mut rng)
+                .is_some());
+            assert_eq!(
+                world
+                    .spatial_table
+                    .layers_at_checked(Coord::new(2, 2))
+                    .object,
+                Some(troll)
+            );
+            let loot = Direction::all()
+                .filter_map(|direction| {
+                    world
+                        .spatial_table
+                        .layers_at_checked(Coord::new(2, 2) + direction.coord())
+                        .object
+                })
+                .collect::<Vec<_>>();
+            assert!(loot.len() <= 1);
+            for item in loot {
+                assert!(world.components.item.contains(item));
+                num_drops += 1;
+            }
+        }
+        assert!(num_drops > 0);
+    }
+
+    #[test]
+    fn poison_deals_damage_each_turn_until_it_wears_off() {
+        let mut world = open_world(Size::new(3, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let orc = world
+            .spawn_npc(
+                Coord::new(1, 1),
+                NpcType::Orc,
+                DifficultyScale {
+                    hit_points_multiplier: 2.0,
+                },
+            )
+            .unwrap();
+        world.character_poison(orc, 3);
+        for _ in 0..4 {
+            world.apply_status_effects(&mut message_log, &mut rng);
+        }
+        assert_eq!(world.hit_points(orc).unwrap().current, 1);
+        assert!(!world.components.poison.contains(orc));
+    }
+
+    #[test]
+    fn poison_is_removed_when_character_dies() {
+        let mut world = open_world(Size::new(3, 3));
+        let mut rng = rng();
+        let orc = world
+            .spawn_npc(Coord::new(1, 1), NpcType::Orc, DifficultyScale::default())
+            .unwrap();
+        world.character_poison(orc, 3);
+        assert!(world
+            .character_damage(orc, 2, DamageKind::Melee, &mut rng)
+            .is_some());
+        assert!(!world.components.poison.contains(orc));
+    }
+
+    #[test]
+    fn populate_gives_player_starting_items() {
+        let mut world = World::new(Size::new(40, 30));
+        let player_config = PlayerConfig {
+            starting_items: vec![ItemType::FireballScroll, ItemType::FireballScroll],
+            ..Default::default()
+        };
+        let Populate { player_entity, .. } = world.populate(
+            1,
+            &player_config,
+            &MonsterSpawnTable::default(),
+            &GenerationConfig::default(),
+            DifficultyScale::default(),
+            &mut rng(),
+        );
+        let inventory = world.inventory(player_entity).unwrap();
+        let num_fireball_scrolls = inventory
+            .slots()
+            .iter()
+            .flatten()
+            .filter(|slot| slot.item_type == ItemType::FireballScroll)
+            .map(InventorySlot::count)
+            .sum::<usize>();
+        assert_eq!(num_fireball_scrolls, 2);
+        assert_eq!(inventory.slots().len(), player_config.inventory_capacity);
+    }
+
+    #[test]
+    fn move_results() {
+        let mut world = open_world(Size::new(6, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(Coord::new(0, 1), &steady_player_config(Vec::new()))
+            .unwrap();
+        let mut step = |world: &mut World, direction| {
+            world.maybe_move_character(player, direction, &mut message_log, &mut rng)
+        };
+        // Out of bounds
+        assert_eq!(step(&mut world, Direction::West), MoveResult::Blocked);
+        assert_eq!(world.entity_coord(player), Some(Coord::new(0, 1)));
+        assert_eq!(step(&mut world, Direction::East), MoveResult::Moved);
+        assert_eq!(world.entity_coord(player), Some(Coord::new(1, 1)));
+        world.spawn_wall(Coord::new(1, 0)).unwrap();
+        assert_eq!(step(&mut world, Direction::North), MoveResult::Blocked);
+        world.spawn_door(Coord::new(1, 2)).unwrap();
+        assert_eq!(step(&mut world, Direction::South), MoveResult::OpenedDoor);
+        assert_eq!(world.entity_coord(player), Some(Coord::new(1, 1)));
+        spawn_steady_npc(&mut world, Coord::new(2, 1), NpcType::Orc);
+        assert_eq!(
+            step(&mut world, Direction::East),
+            MoveResult::Attacked { killed: false }
+        );
+        assert_eq!(
+            step(&mut world, Direction::East),
+            MoveResult::Attacked { killed: true }
+        );
+        let neutral = spawn_steady_npc(&mut world, Coord::new(0, 1), NpcType::Orc);
+        world.components.faction.insert(neutral, Faction::Neutral);
+        assert_eq!(step(&mut world, Direction::West), MoveResult::Talked);
+        assert_eq!(world.entity_coord(player), Some(Coord::new(1, 1)));
+    }
+
+    #[test]
+    fn player_attacks_hostile_npc() {
+        let mut world = open_world(Size::new(5, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(Coord::new(1, 1), &steady_player_config(Vec::new()))
+            .unwrap();
+        let orc = spawn_steady_npc(&mut world, Coord::new(2, 1), NpcType::Orc);
+        assert!(matches!(
+            world.maybe_move_character(player, Direction::East, &mut message_log, &mut rng),
+            MoveResult::Attacked { .. }
+        ));
+        assert_eq!(world.hit_points(orc).unwrap().current, 1);
+    }
+
+    #[test]
+    fn hostile_npcs_do_not_attack_each_other() {
+        let mut world = open_world(Size::new(5, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let orc = spawn_steady_npc(&mut world, Coord::new(2, 1), NpcType::Orc);
+        let troll = spawn_steady_npc(&mut world, Coord::new(3, 1), NpcType::Troll);
+        assert!(!matches!(
+            world.maybe_move_character(troll, Direction::West, &mut message_log, &mut rng),
+            MoveResult::Attacked { .. }
+        ));
+        assert_eq!(world.hit_points(orc).unwrap().current, 2);
+    }
+
+    #[test]
+    fn confused_hostile_npc_attacks_other_hostile_npcs() {
+        let mut world = open_world(Size::new(3, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let troll = spawn_steady_npc(&mut world, Coord::new(1, 1), NpcType::Troll);
+        // Whichever way the confused troll stumbles, it runs into an orc
+        let orcs = CardinalDirection::all()
+            .map(|direction| {
+                spawn_steady_npc(
+                    &mut world,
+                    Coord::new(1, 1) + direction.coord(),
+                    NpcType::Orc,
+                )
+            })
+            .collect::<Vec<_>>();
+        world.components.confusion_countdown.insert(troll, 1);
+        assert!(matches!(
+            world.maybe_move_character(troll, Direction::North, &mut message_log, &mut rng),
+            MoveResult::Attacked { .. }
+        ));
+        let num_orcs_hurt = orcs
+            .iter()
+            .filter(|&&orc| world.hit_points(orc).unwrap().current < 2)
+            .count();
+        assert_eq!(num_orcs_hurt, 1);
+    }
+
+    #[test]
+    fn npc_follows_l_shaped_corridor_to_player() {
+        let mut world = world_from_rows(&[
+            "#####", //
+            "#.###", //
+            "#.###", //
+            "#...#", //
+            "#####", //
+        ]);
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(Coord::new(3, 3), &PlayerConfig::default())
+            .unwrap();
+        let orc = world
+            .spawn_npc(Coord::new(1, 1), NpcType::Orc, DifficultyScale::default())
+            .unwrap();
+        let player_coord = world.entity_coord(player).unwrap();
+        // Stepping directly towards the player would walk into the wall to the east
+        assert_eq!(
+            world.path_toward(Coord::new(1, 1), player_coord),
+            Some(CardinalDirection::South)
+        );
+        for _ in 0..3 {
+            let orc_coord = world.entity_coord(orc).unwrap();
+            let direction = world.path_toward(orc_coord, player_coord).unwrap();
+            assert_eq!(
+                world.maybe_move_character(orc, direction.direction(), &mut message_log, &mut rng),
+                MoveResult::Moved
+            );
+        }
+        assert_eq!(world.entity_coord(orc), Some(Coord::new(2, 3)));
+        assert_eq!(
+            world.path_toward(Coord::new(2, 3), player_coord),
+            Some(CardinalDirection::East)
+        );
+    }
+
+    #[test]
+    fn lightning_strikes_nearest_orc() {
+        let mut world = open_world(Size::new(8, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(
+                Coord::new(1, 1),
+                &steady_player_config(vec![ItemType::LightningScroll]),
+            )
+            .unwrap();
+        let far_orc = spawn_steady_npc(&mut world, Coord::new(6, 1), NpcType::Orc);
+        let near_orc = spawn_steady_npc(&mut world, Coord::new(3, 1), NpcType::Orc);
+        assert!(matches!(
+            world.maybe_use_item(player, 0, &mut message_log, &mut rng),
+            Ok(ItemUsage::Immediate)
+        ));
+        assert!(world.hit_points(near_orc).unwrap().current < 2);
+        assert_eq!(world.hit_points(far_orc).unwrap().current, 2);
+    }
+
+    #[test]
+    fn player_regenerates_only_out_of_sight_of_hostiles() {
+        let mut world = world_from_rows(&[
+            "#######", //
+            "#..#..#", //
+            "#######", //
+        ]);
+        let player = world
+            .spawn_player(Coord::new(1, 1), &PlayerConfig::default())
+            .unwrap();
+        world.components.hit_points.get_mut(player).unwrap().current = 10;
+        // An orc on the other side of the wall can't see the player
+        let orc = world
+            .spawn_npc(Coord::new(5, 1), NpcType::Orc, DifficultyScale::default())
+            .unwrap();
+        world.regenerate(player, 1);
+        assert_eq!(world.hit_points(player).unwrap().current, 11);
+        world
+            .spatial_table
+            .update_coord(orc, Coord::new(2, 1))
+            .unwrap();
+        world.advance_turn();
+        world.regenerate(player, 1);
+        assert_eq!(world.hit_points(player).unwrap().current, 11);
+    }
+
+    #[test]
+    fn entities_with_tiles_includes_characters() {
+        let mut world = open_world(Size::new(5, 5));
+        let player = world
+            .spawn_player(Coord::new(1, 1), &PlayerConfig::default())
+            .unwrap();
+        let orc = world
+            .spawn_npc(Coord::new(3, 2), NpcType::Orc, DifficultyScale::default())
+            .unwrap();
+        let characters = world
+            .entities_with_tiles()
+            .filter(|&(_, _, _, layer)| layer == Layer::Character)
+            .collect::<Vec<_>>();
+        assert_eq!(characters.len(), 2);
+        assert_eq!(characters[0].0, player);
+        assert_eq!(characters[0].1, Coord::new(1, 1));
+        assert!(matches!(characters[0].2, Tile::Player));
+        assert_eq!(characters[1].0, orc);
+        assert_eq!(characters[1].1, Coord::new(3, 2));
+        assert!(matches!(characters[1].2, Tile::Npc(NpcType::Orc)));
+        // Every floor cell is drawn beneath the characters
+        assert_eq!(world.entities_with_tiles().count(), 25 + 2);
+    }
+
+    #[test]
+    fn bumping_door_opens_it_and_lets_light_through() {
+        let mut world = open_world(Size::new(5, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(Coord::new(1, 1), &PlayerConfig::default())
+            .unwrap();
+        let door = world.spawn_door(Coord::new(2, 1)).unwrap();
+        assert!(!world.has_line_of_sight(Coord::new(1, 1), Coord::new(3, 1)));
+        assert_eq!(
+            world.maybe_move_character(player, Direction::East, &mut message_log, &mut rng),
+            MoveResult::OpenedDoor
+        );
+        assert!(matches!(
+            message_log.last(),
+            Some(LogMessage::PlayerOpensDoor)
+        ));
+        assert_eq!(world.components.door.get(door), Some(&true));
+        assert_eq!(world.entity_coord(player), Some(Coord::new(1, 1)));
+        assert!(world.has_line_of_sight(Coord::new(1, 1), Coord::new(3, 1)));
+        assert_eq!(
+            world.maybe_move_character(player, Direction::East, &mut message_log, &mut rng),
+            MoveResult::Moved
+        );
+        assert_eq!(world.entity_coord(player), Some(Coord::new(2, 1)));
+    }
+
+    #[test]
+    fn teleport_scroll_moves_player_to_walkable_cell() {
+        let mut world = world_from_rows(&[
+            "##########", //
+            "#....#...#", //
+            "#....#...#", //
+            "##########", //
+        ]);
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let start = Coord::new(1, 1);
+        let player = world
+            .spawn_player(start, &steady_player_config(vec![ItemType::TeleportScroll]))
+            .unwrap();
+        world
+            .maybe_use_item(player, 0, &mut message_log, &mut rng)
+            .unwrap();
+        let destination = world.entity_coord(player).unwrap();
+        assert_ne!(destination, start);
+        let layers = world.spatial_table.layers_at_checked(destination);
+        assert!(layers.floor.is_some());
+        assert!(layers.feature.is_none());
+        assert!(world.inventory_item_type(player, 0).is_none());
+        assert!(matches!(
+            message_log.last(),
+            Some(LogMessage::PlayerTeleports)
+        ));
+    }
+
+    #[test]
+    fn spawning_two_walls_on_one_cell_is_an_error() {
+        let mut world = World::new(Size::new(3, 3));
+        let first = world.spawn_wall(Coord::new(1, 1)).unwrap();
+        let error = world.spawn_wall(Coord::new(1, 1)).unwrap_err();
+        assert!(matches!(
+            error,
+            SpawnError::Occupied {
+                coord,
+                layer: Layer::Feature,
+                occupied_by,
+            } if coord == Coord::new(1, 1) && occupied_by == first
+        ));
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "can't spawn entity on Feature layer at (1, 1): already occupied by {:?}",
+                first
+            )
+        );
+        assert!(matches!(
+            world.spawn_wall(Coord::new(3, 1)),
+            Err(SpawnError::OutOfBounds { .. })
+        ));
+    }
+
+    #[test]
+    fn aimed_scroll_is_kept_if_its_projectile_cannot_be_launched() {
+        let mut world = open_world(Size::new(5, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(
+                Coord::new(1, 1),
+                &steady_player_config(vec![ItemType::FireballScroll]),
+            )
+            .unwrap();
+        // Another projectile is passing through the player's cell
+        world
+            .spawn_projectile(
+                Coord::new(1, 1),
+                Coord::new(1, 0),
+                ProjectileType::Arrow { damage: 1 },
+            )
+            .unwrap();
+        assert!(world
+            .maybe_use_item_aim(player, 0, Coord::new(3, 1), &mut message_log, &mut rng)
+            .is_err());
+        assert_eq!(
+            world.inventory_item_type(player, 0),
+            Some(ItemType::FireballScroll)
+        );
+        assert!(message_log.is_empty());
+    }
+
+    #[test]
+    fn examining_wounded_orc_reports_its_hit_points() {
+        let mut world = open_world(Size::new(3, 3));
+        let mut rng = rng();
+        let player = world
+            .spawn_player(Coord::new(0, 0), &PlayerConfig::default())
+            .unwrap();
+        let orc = spawn_steady_npc(&mut world, Coord::new(1, 1), NpcType::Orc);
+        assert!(world
+            .character_damage(orc, 1, DamageKind::Melee, &mut rng)
+            .is_none());
+        match world.examine_cell(player, Coord::new(1, 1)) {
+            Some(ExamineCell::Npc(NpcType::Orc, hit_points)) => {
+                assert_eq!(hit_points.current, 1);
+                assert_eq!(hit_points.max, 2);
+            }
+            other => panic!("unexpected examine result: {:?}", other),
+        }
+        assert!(world
+            .character_damage(orc, 1, DamageKind::Melee, &mut rng)
+            .is_some());
+        assert!(matches!(
+            world.examine_cell(player, Coord::new(1, 1)),
+            Some(ExamineCell::NpcCorpse(NpcType::Orc))
+        ));
+    }
+
+    #[test]
+    fn piercing_arrow_damages_two_lined_up_trolls() {
+        let mut world = open_world(Size::new(10, 3));
+        let mut rng = rng();
+        let mut message_log = Vec::new();
+        let first = spawn_steady_npc(&mut world, Coord::new(3, 1), NpcType::Troll);
+        let second = spawn_steady_npc(&mut world, Coord::new(4, 1), NpcType::Troll);
+        let max = world.hit_points(first).unwrap().max;
+        let arrow = world
+            .spawn_projectile(
+                Coord::new(1, 1),
+                Coord::new(9, 1),
+                ProjectileType::Arrow { damage: 2 },
+            )
+            .unwrap();
+        world.components.piercing.insert(arrow, 1);
+        for _ in 0..20 {
+            if !world.has_projectiles() {
+                break;
+            }
+            world.move_projectiles(&mut message_log, &mut rng);
+        }
+        assert!(!world.has_projectiles());
+        assert_eq!(world.hit_points(first).unwrap().current, max - 2);
+        assert_eq!(world.hit_points(second).unwrap().current, max - 2);
+    }
+
+    #[test]
+    fn starvation_damage_begins_once_nutrition_runs_out() {
+        let mut world = open_world(Size::new(3, 3));
+        let mut rng = rng();
+        let mut message_log = Vec::new();
+        let player = world
+            .spawn_player(Coord::new(1, 1), &steady_player_config(vec![]))
+            .unwrap();
+        let max_hit_points = world.hit_points(player).unwrap().current;
+        for _ in 0..MAX_NUTRITION {
+            world.apply_hunger(&mut message_log, &mut rng);
+        }
+        assert_eq!(world.player_nutrition(player), Some(0));
+        assert_eq!(world.hit_points(player).unwrap().current, max_hit_points);
+        assert!(message_log.is_empty());
+        world.apply_hunger(&mut message_log, &mut rng);
+        assert_eq!(
+            world.hit_points(player).unwrap().current,
+            max_hit_points - 1
+        );
+        assert!(matches!(
+            message_log.as_slice(),
+            [LogMessage::PlayerStarving]
+        ));
+    }
+
+    #[test]
+    fn knockback_pushes_victim_back_or_into_wall() {
+        let mut world = world_from_rows(&["#####", "#   #", "#####"]);
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(Coord::new(1, 1), &steady_player_config(Vec::new()))
+            .unwrap();
+        world.components.knockback.insert(player, ());
+        let troll = spawn_steady_npc(&mut world, Coord::new(2, 1), NpcType::Troll);
+        let max = world.hit_points(troll).unwrap().max;
+        assert_eq!(
+            world.maybe_move_character(player, Direction::East, &mut message_log, &mut rng),
+            MoveResult::Attacked { killed: false }
+        );
+        assert_eq!(world.entity_coord(troll), Some(Coord::new(3, 1)));
+        assert_eq!(world.hit_points(troll).unwrap().current, max - 1);
+        // With a wall behind it, the troll takes collision damage instead of moving
+        world
+            .spatial_table
+            .update_coord(player, Coord::new(2, 1))
+            .unwrap();
+        assert_eq!(
+            world.maybe_move_character(player, Direction::East, &mut message_log, &mut rng),
+            MoveResult::Attacked { killed: false }
+        );
+        assert_eq!(world.entity_coord(troll), Some(Coord::new(3, 1)));
+        assert_eq!(world.hit_points(troll).unwrap().current, max - 3);
+    }
+
+    #[test]
+    fn diagonal_projectile_passes_through_each_cell_to_its_target() {
+        let mut world = open_world(Size::new(6, 6));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let projectile = world
+            .spawn_projectile(
+                Coord::new(0, 0),
+                Coord::new(4, 4),
+                ProjectileType::Arrow { damage: 1 },
+            )
+            .unwrap();
+        let mut visited = Vec::new();
+        while world.has_projectiles() {
+            world.move_projectiles(&mut message_log, &mut rng);
+            if let Some(coord) = world.entity_coord(projectile) {
+                visited.push(coord);
+            }
+        }
+        assert_eq!(
+            visited,
+            vec![
+                Coord::new(1, 1),
+                Coord::new(2, 2),
+                Coord::new(3, 3),
+                Coord::new(4, 4),
+            ]
+        );
+        // Lines which aren't exactly diagonal still take single steps all the way to the target
+        let mut trajectory = Trajectory::new(Coord::new(0, 0), Coord::new(2, 5));
+        let mut current = Coord::new(0, 0);
+        for coord in &mut trajectory {
+            let delta = coord - current;
+            assert!(delta.x.abs() <= 1 && delta.y.abs() <= 1);
+            current = coord;
+        }
+        assert_eq!(current, Coord::new(2, 5));
+    }
+
+    #[test]
+    fn nearest_character_returns_closest_of_three_orcs() {
+        let mut world = open_world(Size::new(10, 10));
+        let far = spawn_steady_npc(&mut world, Coord::new(9, 9), NpcType::Orc);
+        let near = spawn_steady_npc(&mut world, Coord::new(3, 2), NpcType::Orc);
+        let middle = spawn_steady_npc(&mut world, Coord::new(1, 6), NpcType::Orc);
+        let from = Coord::new(1, 1);
+        assert_eq!(
+            world.nearest_character(from, |_| true),
+            Some((near, Coord::new(3, 2)))
+        );
+        assert_eq!(
+            world.nearest_character(from, |entity| entity != near),
+            Some((middle, Coord::new(1, 6)))
+        );
+        assert_eq!(
+            world.nearest_character(from, |entity| entity == far),
+            Some((far, Coord::new(9, 9)))
+        );
+        assert_eq!(world.nearest_character(from, |_| false), None);
+    }
+
+    #[test]
+    fn pickaxe_digs_out_adjacent_wall() {
+        let mut world = world_from_rows(&["#####", "#  ##", "#####"]);
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(
+                Coord::new(2, 1),
+                &steady_player_config(vec![ItemType::Pickaxe]),
+            )
+            .unwrap();
+        let wall = Coord::new(3, 1);
+        assert!(!world.can_npc_enter(wall));
+        assert!(matches!(
+            world.maybe_use_item_aim(player, 0, wall, &mut message_log, &mut rng),
+            Ok(None)
+        ));
+        assert!(world.can_npc_enter(wall));
+        assert_eq!(
+            world.inventory_item_type(player, 0),
+            Some(ItemType::Pickaxe)
+        );
+    }
+
+    #[test]
+    fn mapping_scroll_is_consumed_and_all_floor_coords_are_listed() {
+        let mut world = World::new(Size::new(4, 3));
+        let floor_coords = (0..4).map(|x| Coord::new(x, 1)).collect::<Vec<_>>();
+        for &coord in &floor_coords {
+            world.spawn_floor(coord).unwrap();
+        }
+        world.spawn_wall(Coord::new(0, 0)).unwrap();
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(
+                Coord::new(1, 1),
+                &steady_player_config(vec![ItemType::MappingScroll]),
+            )
+            .unwrap();
+        assert!(matches!(
+            world.maybe_use_item(player, 0, &mut message_log, &mut rng),
+            Ok(ItemUsage::Immediate)
+        ));
+        assert_eq!(world.inventory_item_type(player, 0), None);
+        assert!(matches!(
+            message_log.as_slice(),
+            [LogMessage::PlayerMapsLevel]
+        ));
+        assert_eq!(world.all_floor_coords(), floor_coords);
+    }
+
+    #[test]
+    fn doubled_difficulty_scale_doubles_troll_hit_points() {
+        let monster_spawn_table = MonsterSpawnTable {
+            weights: vec![terrain::MonsterSpawnWeight {
+                npc_type: NpcType::Troll,
+                base: 1,
+                per_level: 0,
+            }],
+        };
+        let difficulty_scale = DifficultyScale {
+            hit_points_multiplier: 2.0,
+        };
+        let mut world = World::new(Size::new(40, 30));
+        world.populate(
+            1,
+            &PlayerConfig::default(),
+            &monster_spawn_table,
+            &GenerationConfig::default(),
+            difficulty_scale,
+            &mut rng(),
+        );
+        let trolls = world
+            .components
+            .npc_type
+            .iter()
+            .filter(|&(_, &npc_type)| npc_type == NpcType::Troll)
+            .map(|(entity, _)| entity)
+            .collect::<Vec<_>>();
+        assert!(!trolls.is_empty());
+        for troll in trolls {
+            assert_eq!(world.hit_points(troll).unwrap().max, 12);
+        }
+    }
+
+    #[test]
+    fn removing_first_potion_leaves_other_items_in_place() {
+        let mut world = World::new(Size::new(1, 1));
+        let sword = world.create_item(ItemType::Sword);
+        let potion = world.create_item(ItemType::HealthPotion);
+        let mut inventory = Inventory::new(3);
+        assert!(inventory.insert(sword, ItemType::Sword).is_ok());
+        assert!(inventory.insert(potion, ItemType::HealthPotion).is_ok());
+        assert_eq!(
+            inventory.remove_first_of_type(ItemType::HealthPotion),
+            Some(potion)
+        );
+        assert_eq!(inventory.get(0).unwrap(), sword);
+        assert!(inventory.get(1).is_err());
+        assert_eq!(inventory.remove_first_of_type(ItemType::HealthPotion), None);
+    }
+
+    #[test]
+    fn orc_corpse_crumbles_after_its_decay_duration() {
+        let mut world = open_world(Size::new(3, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let orc = spawn_steady_npc(&mut world, Coord::new(1, 1), NpcType::Orc);
+        assert!(world
+            .character_damage(orc, 100, DamageKind::Melee, &mut rng)
+            .is_some());
+        for _ in 1..NpcType::Orc.corpse_decay_duration() {
+            world.decay_corpses(&mut message_log);
+        }
+        assert_eq!(world.entity_coord(orc), Some(Coord::new(1, 1)));
+        assert!(message_log.is_empty());
+        world.decay_corpses(&mut message_log);
+        assert_eq!(world.entity_coord(orc), None);
+        assert!(world.components.tile.get(orc).is_none());
+        assert!(matches!(
+            message_log.as_slice(),
+            [LogMessage::CorpseCrumbles(NpcType::Orc)]
+        ));
+    }
+
+    #[test]
+    fn stepping_onto_hidden_trap_reveals_it_and_deals_damage() {
+        let mut world = open_world(Size::new(4, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(Coord::new(1, 1), &steady_player_config(Vec::new()))
+            .unwrap();
+        let trap = world.spawn_trap(Coord::new(2, 1)).unwrap();
+        let hit_points = world.hit_points(player).unwrap().current;
+        assert_eq!(
+            world.maybe_move_character(player, Direction::East, &mut message_log, &mut rng),
+            MoveResult::Moved
+        );
+        assert_eq!(world.entity_coord(player), Some(Coord::new(2, 1)));
+        assert_eq!(
+            world.hit_points(player).unwrap().current,
+            hit_points - TRAP_DAMAGE
+        );
+        assert_eq!(world.components.hidden.get(trap), Some(&false));
+        assert!(message_log
+            .iter()
+            .any(|message| matches!(message, LogMessage::TrapTriggers)));
+    }
+
+    #[test]
+    fn detecting_traps_only_reveals_those_in_range() {
+        let mut world = open_world(Size::new(10, 3));
+        let near = world.spawn_trap(Coord::new(2, 1)).unwrap();
+        let far = world.spawn_trap(Coord::new(8, 1)).unwrap();
+        assert_eq!(world.detect_traps_near(Coord::new(1, 1), 2), 1);
+        assert_eq!(world.components.hidden.get(near), Some(&false));
+        assert_eq!(world.components.hidden.get(far), Some(&true));
+    }
+
+    #[test]
+    fn picking_up_gold_adds_to_purse_and_removes_pile() {
+        let mut world = open_world(Size::new(3, 3));
+        let mut message_log = Vec::new();
+        let player = world
+            .spawn_player(Coord::new(1, 1), &steady_player_config(Vec::new()))
+            .unwrap();
+        let gold = world.player_gold(player);
+        let pile = world.spawn_gold(Coord::new(1, 1), 7).unwrap();
+        assert!(world.maybe_get_item(player, &mut message_log).is_ok());
+        assert_eq!(world.player_gold(player), gold + 7);
+        assert_eq!(world.entity_coord(pile), None);
+        assert!(world.components.gold_pile.get(pile).is_none());
+        assert!(world
+            .inventory(player)
+            .unwrap()
+            .slots()
+            .iter()
+            .all(Option::is_none));
+        assert!(matches!(
+            message_log.as_slice(),
+            [LogMessage::PlayerGetsGold(7)]
+        ));
+    }
+
+    #[test]
+    fn haste_lasts_for_its_duration_then_expires() {
+        let mut world = open_world(Size::new(3, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(
+                Coord::new(1, 1),
+                &steady_player_config(vec![ItemType::HasteScroll]),
+            )
+            .unwrap();
+        assert!(!world.entity_is_hasted(player));
+        assert!(world
+            .maybe_use_item(player, 0, &mut message_log, &mut rng)
+            .is_ok());
+        assert_eq!(world.components.haste.get(player), Some(&HASTE_DURATION));
+        for _ in 1..HASTE_DURATION {
+            world.tick_haste(&mut message_log);
+            assert!(world.entity_is_hasted(player));
+        }
+        world.tick_haste(&mut message_log);
+        assert!(!world.entity_is_hasted(player));
+        assert!(matches!(
+            message_log.as_slice(),
+            [LogMessage::PlayerHasted, LogMessage::PlayerIsNoLongerHasted]
+        ));
+    }
+
+    #[test]
+    fn wall_truncates_aim_line_before_target() {
+        let world = world_from_rows(&["       ", "   #   ", "       "]);
+        assert_eq!(
+            world.aim_line(Coord::new(0, 1), Coord::new(6, 1)),
+            vec![Coord::new(1, 1), Coord::new(2, 1)]
+        );
+        assert_eq!(
+            world.aim_line(Coord::new(0, 0), Coord::new(6, 0)).last(),
+            Some(&Coord::new(6, 0))
+        );
+    }
+
+    #[test]
+    fn blindness_lasts_for_its_duration() {
+        let mut world = open_world(Size::new(5, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let orc = spawn_steady_npc(&mut world, Coord::new(3, 1), NpcType::Orc);
+        let duration = 3;
+        world
+            .spawn_projectile(
+                Coord::new(1, 1),
+                Coord::new(3, 1),
+                ProjectileType::Blindness { duration },
+            )
+            .unwrap();
+        while world.has_projectiles() {
+            world.move_projectiles(&mut message_log, &mut rng);
+        }
+        assert!(matches!(
+            message_log.as_slice(),
+            [LogMessage::NpcIsBlinded(NpcType::Orc)]
+        ));
+        for _ in 0..duration {
+            assert!(world.entity_is_blind(orc));
+            world.tick_blindness(&mut message_log);
+        }
+        assert!(!world.entity_is_blind(orc));
+    }
+
+    #[test]
+    fn equipping_shield_returns_previous_offhand_to_inventory() {
+        let mut world = open_world(Size::new(3, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(
+                Coord::new(1, 1),
+                &steady_player_config(vec![ItemType::ReflectShield, ItemType::Shield]),
+            )
+            .unwrap();
+        assert!(world
+            .maybe_use_item(player, 0, &mut message_log, &mut rng)
+            .is_ok());
+        assert_eq!(
+            world.equipped_in_slot(player, EquipSlot::Offhand),
+            Some(ItemType::ReflectShield)
+        );
+        assert!(world
+            .maybe_use_item(player, 1, &mut message_log, &mut rng)
+            .is_ok());
+        assert_eq!(
+            world.equipped_in_slot(player, EquipSlot::Offhand),
+            Some(ItemType::Shield)
+        );
+        assert_eq!(
+            world.inventory_item_type(player, 0),
+            Some(ItemType::ReflectShield)
+        );
+        assert_eq!(world.inventory_item_type(player, 1), None);
+    }
+
+    #[test]
+    fn equipping_fails_without_changes_when_unequipped_items_do_not_fit() {
+        let mut world = open_world(Size::new(3, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(
+                Coord::new(1, 1),
+                &PlayerConfig {
+                    inventory_capacity: 3,
+                    ..steady_player_config(vec![ItemType::Sword, ItemType::Shield, ItemType::Staff])
+                },
+            )
+            .unwrap();
+        assert!(world
+            .maybe_use_item(player, 0, &mut message_log, &mut rng)
+            .is_ok());
+        assert!(world
+            .maybe_use_item(player, 1, &mut message_log, &mut rng)
+            .is_ok());
+        // Fill the slots freed up by equipping the sword and shield
+        for &item_type in &[ItemType::Dagger, ItemType::Armour] {
+            let item = world.create_item(item_type);
+            assert!(world
+                .components
+                .inventory
+                .get_mut(player)
+                .unwrap()
+                .insert(item, item_type)
+                .is_ok());
+        }
+        // The staff takes both hands, so equipping it would unequip both the sword and the shield
+        assert!(world
+            .maybe_use_item(player, 2, &mut message_log, &mut rng)
+            .is_err());
+        assert!(matches!(
+            message_log.last(),
+            Some(LogMessage::PlayerInventoryIsFull)
+        ));
+        assert_eq!(
+            world.equipped_in_slot(player, EquipSlot::Weapon),
+            Some(ItemType::Sword)
+        );
+        assert_eq!(
+            world.equipped_in_slot(player, EquipSlot::Offhand),
+            Some(ItemType::Shield)
+        );
+        assert_eq!(world.inventory_item_type(player, 2), Some(ItemType::Staff));
+        assert!(world
+            .inventory(player)
+            .unwrap()
+            .slots()
+            .iter()
+            .all(Option::is_some));
+    }
+
+    #[test]
+    fn room_flood_fill_stops_at_walls_and_optionally_doors() {
+        let mut world = world_from_rows(&[
+            "#######", //
+            "#  #  #", //
+            "#     #", //
+            "#  #  #", //
+            "#######", //
+        ]);
+        world.spawn_door(Coord::new(3, 2)).unwrap();
+        let mut left_room = world.room_coords_containing(Coord::new(1, 1), true);
+        left_room.sort_by_key(|coord| (coord.y, coord.x));
+        assert_eq!(
+            left_room,
+            vec![
+                Coord::new(1, 1),
+                Coord::new(2, 1),
+                Coord::new(1, 2),
+                Coord::new(2, 2),
+                Coord::new(1, 3),
+                Coord::new(2, 3),
+            ]
+        );
+        let both_rooms = world.room_coords_containing(Coord::new(1, 1), false);
+        assert_eq!(both_rooms.len(), 13);
+        assert!(both_rooms.contains(&Coord::new(5, 3)));
+        assert!(world
+            .room_coords_containing(Coord::new(0, 0), true)
+            .is_empty());
+    }
+
+    #[test]
+    fn room_flood_fill_on_generated_level_is_bounded_by_walls_and_doors() {
+        let mut world = World::new(Size::new(40, 30));
+        let Populate { player_entity, .. } = world.populate(
+            1,
+            &PlayerConfig::default(),
+            &MonsterSpawnTable::default(),
+            &GenerationConfig::default(),
+            DifficultyScale::default(),
+            &mut rng(),
+        );
+        let start = world.entity_coord(player_entity).unwrap();
+        let room = world.room_coords_containing(start, true);
+        assert!(room.contains(&start));
+        for &coord in &room {
+            for direction in CardinalDirection::all() {
+                let neighbour = coord + direction.coord();
+                if room.contains(&neighbour) {
+                    continue;
+                }
+                let feature = world
+                    .spatial_table
+                    .layers_at(neighbour)
+                    .and_then(|layers| layers.feature);
+                match feature.and_then(|feature| world.components.tile.get(feature)) {
+                    Some(Tile::Wall)
+                    | Some(Tile::SecretWall)
+                    | Some(Tile::DoorClosed)
+                    | Some(Tile::DoorOpen)
+                    | Some(Tile::LockedDoor(_)) => (),
+                    _ => panic!("room leaks into {:?}", neighbour),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn guaranteed_critical_hit_kills_orc_in_one_hit() {
+        let mut world = open_world(Size::new(5, 5));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(Coord::new(1, 1), &steady_player_config(Vec::new()))
+            .unwrap();
+        world.components.crit_chance.insert(player, 100);
+        spawn_steady_npc(&mut world, Coord::new(2, 1), NpcType::Orc);
+        assert_eq!(
+            world.maybe_move_character(player, Direction::East, &mut message_log, &mut rng),
+            MoveResult::Attacked { killed: true }
+        );
+        assert!(message_log
+            .iter()
+            .any(|message| matches!(message, LogMessage::PlayerCriticalHit(NpcType::Orc))));
+    }
+
+    #[test]
+    fn unknown_potion_is_identified_once_used() {
+        let mut world = open_world(Size::new(3, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(
+                Coord::new(1, 1),
+                &steady_player_config(vec![ItemType::HealthPotion]),
+            )
+            .unwrap();
+        let floor_potion = world
+            .spawn_item(Coord::new(2, 1), ItemType::HealthPotion)
+            .unwrap();
+        assert!(!world.item_is_identified(player, floor_potion));
+        let identified = world.item_type_is_identified(player, ItemType::HealthPotion);
+        assert_eq!(
+            ItemType::HealthPotion.display_name(identified),
+            "unknown potion"
+        );
+        assert!(matches!(
+            world.examine_cell(player, Coord::new(2, 1)),
+            Some(ExamineCell::Item(ItemType::HealthPotion, false))
+        ));
+        // The used potion is gone from the inventory, but potions are still recognised afterwards
+        assert!(world
+            .maybe_use_item(player, 0, &mut message_log, &mut rng)
+            .is_ok());
+        assert_eq!(world.inventory_item_type(player, 0), None);
+        let identified = world.item_type_is_identified(player, ItemType::HealthPotion);
+        assert_eq!(
+            ItemType::HealthPotion.display_name(identified),
+            "health potion"
+        );
+        assert!(world.item_is_identified(player, floor_potion));
+        assert!(matches!(
+            world.examine_cell(player, Coord::new(2, 1)),
+            Some(ExamineCell::Item(ItemType::HealthPotion, true))
+        ));
+    }
+
+    #[test]
+    fn moving_while_bleeding_deals_more_damage_than_staying_put() {
+        let mut world = open_world(Size::new(6, 4));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let mover = spawn_steady_npc(&mut world, Coord::new(1, 1), NpcType::Troll);
+        let stayer = spawn_steady_npc(&mut world, Coord::new(1, 2), NpcType::Troll);
+        world.character_bleed(mover, 1);
+        world.character_bleed(stayer, 1);
+        let max = world.hit_points(mover).unwrap().max;
+        for _ in 0..2 {
+            assert_eq!(
+                world.maybe_move_character(mover, Direction::East, &mut message_log, &mut rng),
+                MoveResult::Moved
+            );
+            world.apply_status_effects(&mut message_log, &mut rng);
+        }
+        let mover_damage = max - world.hit_points(mover).unwrap().current;
+        let stayer_damage = max - world.hit_points(stayer).unwrap().current;
+        assert_eq!(stayer_damage, 1);
+        assert_eq!(mover_damage, 4);
+        assert!(message_log
+            .iter()
+            .any(|message| matches!(message, LogMessage::NpcBleeds(NpcType::Troll))));
+    }
+
+    #[test]
+    fn player_swaps_places_with_adjacent_ally() {
+        let mut world = open_world(Size::new(4, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(Coord::new(1, 1), &steady_player_config(Vec::new()))
+            .unwrap();
+        let ally = spawn_steady_npc(&mut world, Coord::new(2, 1), NpcType::Orc);
+        world.components.faction.insert(ally, Faction::Player);
+        assert_eq!(
+            world.maybe_move_character(player, Direction::East, &mut message_log, &mut rng),
+            MoveResult::Moved
+        );
+        assert_eq!(world.entity_coord(player), Some(Coord::new(2, 1)));
+        assert_eq!(world.entity_coord(ally), Some(Coord::new(1, 1)));
+        // Hostile npcs can't be swapped with
+        let orc = spawn_steady_npc(&mut world, Coord::new(3, 1), NpcType::Orc);
+        assert!(world.maybe_swap_places(player, Direction::East).is_err());
+        assert_eq!(world.entity_coord(orc), Some(Coord::new(3, 1)));
+    }
+
+    #[test]
+    fn two_charge_wand_fails_on_third_use() {
+        let mut world = open_world(Size::new(12, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(
+                Coord::new(1, 1),
+                &steady_player_config(vec![ItemType::FireballWand]),
+            )
+            .unwrap();
+        let wand = world.inventory_item_entity(player, 0).unwrap();
+        world.components.charges.insert(wand, 2);
+        let target = Coord::new(10, 1);
+        for remaining in (0..2).rev() {
+            assert!(matches!(
+                world.maybe_use_item_aim(player, 0, target, &mut message_log, &mut rng),
+                Ok(None)
+            ));
+            assert_eq!(world.item_charges(wand), Some(remaining));
+            while world.has_projectiles() {
+                world.move_projectiles(&mut message_log, &mut rng);
+            }
+        }
+        assert!(world
+            .maybe_use_item_aim(player, 0, target, &mut message_log, &mut rng)
+            .is_err());
+        assert!(matches!(message_log.last(), Some(LogMessage::WandIsEmpty)));
+        assert!(!world.has_projectiles());
+        assert_eq!(
+            world.inventory_item_type(player, 0),
+            Some(ItemType::FireballWand)
+        );
+    }
+
+    #[test]
+    fn entities_in_rect_only_returns_entities_inside_it() {
+        let mut world = World::new(Size::new(5, 5));
+        let inside_floor = world.spawn_floor(Coord::new(1, 1)).unwrap();
+        let inside_orc = spawn_steady_npc(&mut world, Coord::new(1, 1), NpcType::Orc);
+        let inside_wall = world.spawn_wall(Coord::new(2, 0)).unwrap();
+        let outside_floor = world.spawn_floor(Coord::new(3, 1)).unwrap();
+        let outside_orc = spawn_steady_npc(&mut world, Coord::new(0, 4), NpcType::Orc);
+        let entities = world.entities_in_rect(Coord::new(1, -1), Size::new(2, 3));
+        assert_eq!(entities, vec![inside_wall, inside_floor, inside_orc]);
+        assert!(!entities.contains(&outside_floor));
+        assert!(!entities.contains(&outside_orc));
+        // The rectangle is clamped to the grid
+        assert_eq!(
+            world
+                .entities_in_rect(Coord::new(-10, -10), Size::new(100, 100))
+                .len(),
+            5
+        );
+    }
+
+    #[test]
+    fn monster_wandering_into_view_interrupts_rest() {
+        let mut world = open_world(Size::new(16, 3));
+        let player = world
+            .spawn_player(Coord::new(0, 1), &steady_player_config(Vec::new()))
+            .unwrap();
+        world.components.hit_points.get_mut(player).unwrap().current = 1;
+        let orc = spawn_steady_npc(&mut world, Coord::new(14, 1), NpcType::Orc);
+        let mut turns_taken = 0;
+        // The orc steps towards the player each turn, and comes into view once it's 10 cells away
+        let outcome = world.rest(player, 100, |world| {
+            let orc_coord = world.entity_coord(orc).unwrap();
+            world
+                .spatial_table
+                .update_coord(orc, orc_coord + Coord::new(-1, 0))
+                .unwrap();
+            turns_taken += 1;
+        });
+        assert!(matches!(outcome, RestOutcome::HostileInView));
+        assert_eq!(turns_taken, 4);
+        assert_eq!(world.entity_coord(orc), Some(Coord::new(10, 1)));
+    }
+
+    #[test]
+    fn rest_stops_at_full_hit_points_or_max_turns() {
+        let mut world = open_world(Size::new(3, 3));
+        let player = world
+            .spawn_player(Coord::new(1, 1), &steady_player_config(Vec::new()))
+            .unwrap();
+        world.components.hit_points.get_mut(player).unwrap().current = 1;
+        let mut turns_taken = 0;
+        let outcome = world.rest(player, 5, |_| turns_taken += 1);
+        assert!(matches!(outcome, RestOutcome::MaxTurnsElapsed));
+        assert_eq!(turns_taken, 5);
+        let max = world.hit_points(player).unwrap().max;
+        world.components.hit_points.get_mut(player).unwrap().current = max - 2;
+        let mut turns_taken = 0;
+        let outcome = world.rest(player, 5, |world| {
+            world.character_heal(player, 1);
+            turns_taken += 1;
+        });
+        assert!(matches!(outcome, RestOutcome::FullHitPoints));
+        assert_eq!(turns_taken, 2);
+        assert_eq!(world.hit_points(player).unwrap().current, max);
+    }
+
+    #[test]
+    fn fire_burns_character_each_tick_until_it_expires() {
+        // A single cell world, so the fire has nowhere to spread
+        let mut world = open_world(Size::new(1, 1));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let troll = spawn_steady_npc(&mut world, Coord::new(0, 0), NpcType::Troll);
+        world.ignite(Coord::new(0, 0), FIRE_DURATION).unwrap();
+        assert!(!world.can_npc_enter_ignoring_other_npcs(Coord::new(0, 0)));
+        let max = world.hit_points(troll).unwrap().max;
+        for tick in 1..=FIRE_DURATION {
+            world.tick_environmental_hazards(&mut message_log, &mut rng);
+            assert_eq!(
+                world.hit_points(troll).unwrap().current,
+                max - tick * FIRE_DAMAGE
+            );
+        }
+        assert!(world.components.fire.is_empty());
+        world.tick_environmental_hazards(&mut message_log, &mut rng);
+        assert_eq!(
+            world.hit_points(troll).unwrap().current,
+            max - FIRE_DURATION * FIRE_DAMAGE
+        );
+    }
+
+    #[test]
+    fn thrown_acid_potion_damages_npcs_within_splash_radius() {
+        let mut world = open_world(Size::new(9, 4));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(
+                Coord::new(1, 1),
+                &steady_player_config(vec![ItemType::AcidPotion]),
+            )
+            .unwrap();
+        let target = spawn_steady_npc(&mut world, Coord::new(4, 1), NpcType::Troll);
+        let diagonal = spawn_steady_npc(&mut world, Coord::new(5, 2), NpcType::Troll);
+        let outside = spawn_steady_npc(&mut world, Coord::new(7, 1), NpcType::Troll);
+        let max = world.hit_points(target).unwrap().max;
+        world
+            .maybe_throw_item(player, 0, Coord::new(4, 1), &mut message_log, &mut rng)
+            .unwrap();
+        assert_eq!(world.hit_points(target).unwrap().current, max - ACID_DAMAGE);
+        assert_eq!(
+            world.hit_points(diagonal).unwrap().current,
+            max - ACID_DAMAGE
+        );
+        assert_eq!(world.hit_points(outside).unwrap().current, max);
+        assert_eq!(
+            world.hit_points(player).unwrap().current,
+            world.hit_points(player).unwrap().max
+        );
+        assert!(world.inventory_item_type(player, 0).is_none());
+    }
+
+    #[test]
+    fn sort_by_coord_keeps_order_of_ties() {
+        let mut world = open_world(Size::new(3, 3));
+        let floor = world
+            .spatial_table
+            .layers_at_checked(Coord::new(1, 1))
+            .floor
+            .unwrap();
+        let orc = spawn_steady_npc(&mut world, Coord::new(1, 1), NpcType::Orc);
+        let first_item = world.create_item(ItemType::Sword);
+        let second_item = world.create_item(ItemType::Dagger);
+        // Entities with no coordinate come last, and entities which tie stay in the order given
+        let mut forwards = vec![first_item, floor, second_item, orc];
+        world.sort_by_coord(&mut forwards);
+        assert_eq!(forwards, vec![floor, orc, first_item, second_item]);
+        let mut backwards = vec![orc, second_item, floor, first_item];
+        world.sort_by_coord(&mut backwards);
+        assert_eq!(backwards, vec![orc, floor, second_item, first_item]);
+    }
+
+    #[test]
+    fn simultaneous_projectiles_resolve_the_same_way_every_run() {
+        let run = || {
+            let mut world = open_world(Size::new(9, 3));
+            let mut message_log = Vec::new();
+            let mut rng = rng();
+            let troll = spawn_steady_npc(&mut world, Coord::new(4, 1), NpcType::Troll);
+            world
+                .spawn_projectile(
+                    Coord::new(1, 1),
+                    Coord::new(4, 1),
+                    ProjectileType::Fireball { damage: 2 },
+                )
+                .unwrap();
+            world
+                .spawn_projectile(
+                    Coord::new(7, 1),
+                    Coord::new(4, 1),
+                    ProjectileType::Arrow { damage: 1 },
+                )
+                .unwrap();
+            let mut ticks = 0;
+            while world.has_projectiles() {
+                world.move_projectiles(&mut message_log, &mut rng);
+                ticks += 1;
+            }
+            let log = message_log
+                .iter()
+                .map(|message| format!("{:?}", message))
+                .collect::<Vec<_>>();
+            let hit_points = world.hit_points(troll).unwrap();
+            (ticks, hit_points.max - hit_points.current, log)
+        };
+        let first = run();
+        // Both projectiles hit the troll
+        assert!(first.1 > 2);
+        for _ in 0..5 {
+            assert_eq!(run(), first);
+        }
+    }
+
+    #[test]
+    fn wounded_orc_flees_from_player() {
+        let mut world = open_world(Size::new(11, 11));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(Coord::new(5, 5), &PlayerConfig::default())
+            .unwrap();
+        let orc = spawn_steady_npc(&mut world, Coord::new(7, 5), NpcType::Orc);
+        world.components.hit_points.get_mut(orc).unwrap().current = NpcType::Orc.flee_threshold();
+        let mut agent = Agent::new();
+        let mut behaviour_context = BehaviourContext::new(world.size());
+        let distance_to_player = |world: &World| {
+            manhattan_distance(
+                world.entity_coord(orc).unwrap(),
+                world.entity_coord(player).unwrap(),
+            )
+        };
+        for _ in 0..3 {
+            let distance_before = distance_to_player(&world);
+            behaviour_context.update(player, &world);
+            match agent.act(orc, player, &world, &mut behaviour_context, &mut rng) {
+                NpcAction::Move(direction) => {
+                    world.maybe_move_character(
+                        orc,
+                        direction.direction(),
+                        &mut message_log,
+                        &mut rng,
+                    );
+                }
+                _ => panic!("wounded orc didn't move"),
+            }
+            assert!(agent.is_fleeing());
+            assert!(distance_to_player(&world) > distance_before);
+        }
+    }
+
+    #[test]
+    fn wounded_troll_never_flees() {
+        let mut world = open_world(Size::new(11, 11));
+        let mut rng = rng();
+        let player = world
+            .spawn_player(Coord::new(5, 5), &PlayerConfig::default())
+            .unwrap();
+        let troll = spawn_steady_npc(&mut world, Coord::new(7, 5), NpcType::Troll);
+        world.components.hit_points.get_mut(troll).unwrap().current = 1;
+        let mut agent = Agent::new();
+        let mut behaviour_context = BehaviourContext::new(world.size());
+        behaviour_context.update(player, &world);
+        assert!(matches!(
+            agent.act(troll, player, &world, &mut behaviour_context, &mut rng),
+            NpcAction::Move(CardinalDirection::West)
+        ));
+        assert!(!agent.is_fleeing());
+    }
+
+    #[test]
+    fn sorting_inventory_groups_items_and_moves_gaps_to_the_end() {
+        let mut world = open_world(Size::new(3, 3));
+        let player = world
+            .spawn_player(Coord::new(1, 1), &steady_player_config(Vec::new()))
+            .unwrap();
+        let item_types = [
+            ItemType::Sword,
+            ItemType::HealthPotion,
+            ItemType::Staff,
+            ItemType::Dagger,
+            ItemType::Sword,
+        ];
+        let items = item_types
+            .iter()
+            .map(|&item_type| world.create_item(item_type))
+            .collect::<Vec<_>>();
+        {
+            let inventory = world.components.inventory.get_mut(player).unwrap();
+            for (&item, &item_type) in items.iter().zip(item_types.iter()) {
+                assert!(inventory.insert(item, item_type).is_ok());
+            }
+            assert_eq!(inventory.remove(2).unwrap(), items[2]);
+            assert_eq!(inventory.remove(0).unwrap(), items[0]);
+        }
+        world.sort_player_inventory(player);
+        let inventory = world.components.inventory.get(player).unwrap();
+        let layout = inventory
+            .slots()
+            .iter()
+            .map(|slot| slot.as_ref().map(|slot| slot.entity()))
+            .collect::<Vec<_>>();
+        let mut expected = vec![Some(items[1]), Some(items[4]), Some(items[3])];
+        expected.resize(layout.len(), None);
+        assert_eq!(layout, expected);
+    }
+
+    #[test]
+    fn entity_at_cursor_prefers_character_over_item() {
+        let mut world = open_world(Size::new(3, 3));
+        let coord = Coord::new(1, 1);
+        let item = world.spawn_item(coord, ItemType::HealthPotion).unwrap();
+        assert_eq!(world.entity_at_cursor(coord), Some(item));
+        let orc = spawn_steady_npc(&mut world, coord, NpcType::Orc);
+        assert_eq!(world.entity_at_cursor(coord), Some(orc));
+        assert_eq!(world.entity_at_cursor(Coord::new(0, 0)), None);
+    }
+
+    #[test]
+    fn thrown_dagger_lands_as_retrievable_item() {
+        let mut world = open_world(Size::new(7, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(
+                Coord::new(1, 1),
+                &steady_player_config(vec![ItemType::Dagger]),
+            )
+            .unwrap();
+        let dagger = world.inventory_item_entity(player, 0).unwrap();
+        let target = Coord::new(4, 1);
+        world
+            .maybe_throw_item(player, 0, target, &mut message_log, &mut rng)
+            .unwrap();
+        assert!(world.inventory_item_type(player, 0).is_none());
+        while world.has_projectiles() {
+            world.move_projectiles(&mut message_log, &mut rng);
+        }
+        assert_eq!(
+            world.spatial_table.layers_at_checked(target).object,
+            Some(dagger)
+        );
+        assert_eq!(world.spatial_table.coord_of(player), Some(Coord::new(1, 1)));
+        world.spatial_table.update_coord(player, target).unwrap();
+        world.maybe_get_item(player, &mut message_log).unwrap();
+        assert_eq!(world.inventory_item_entity(player, 0), Some(dagger));
+        assert_eq!(world.inventory_item_type(player, 0), Some(ItemType::Dagger));
+    }
+
+    #[test]
+    fn key_unlocks_matching_door_and_is_used_up() {
+        let mut world = open_world(Size::new(4, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(
+                Coord::new(1, 1),
+                &steady_player_config(vec![ItemType::Key(KeyColour::Red)]),
+            )
+            .unwrap();
+        let key = world.inventory_item_entity(player, 0).unwrap();
+        let door = world
+            .spawn_locked_door(Coord::new(2, 1), KeyColour::Red)
+            .unwrap();
+        assert_eq!(
+            world.maybe_move_character(player, Direction::East, &mut message_log, &mut rng),
+            MoveResult::OpenedDoor
+        );
+        assert!(message_log
+            .iter()
+            .any(|message| matches!(message, LogMessage::PlayerUnlocksDoor(KeyColour::Red))));
+        assert!(!world.components.lock.contains(door));
+        assert_eq!(world.components.door.get(door), Some(&true));
+        assert!(world.inventory_item_type(player, 0).is_none());
+        assert!(!world.components.item.contains(key));
+        assert_eq!(
+            world.maybe_move_character(player, Direction::East, &mut message_log, &mut rng),
+            MoveResult::Moved
+        );
+    }
+
+    #[test]
+    fn locked_door_stays_shut_without_matching_key() {
+        let mut world = open_world(Size::new(4, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(
+                Coord::new(1, 1),
+                &steady_player_config(vec![ItemType::Key(KeyColour::Blue)]),
+            )
+            .unwrap();
+        let door = world
+            .spawn_locked_door(Coord::new(2, 1), KeyColour::Red)
+            .unwrap();
+        assert_eq!(
+            world.maybe_move_character(player, Direction::East, &mut message_log, &mut rng),
+            MoveResult::Blocked
+        );
+        assert!(message_log
+            .iter()
+            .any(|message| matches!(message, LogMessage::DoorIsLocked(KeyColour::Red))));
+        assert!(world
+            .try_unlock_door(player, Coord::new(2, 1), &mut message_log)
+            .is_err());
+        assert!(world.components.lock.contains(door));
+        assert_eq!(world.entity_coord(player), Some(Coord::new(1, 1)));
+        assert_eq!(
+            world.inventory_item_type(player, 0),
+            Some(ItemType::Key(KeyColour::Blue))
+        );
+    }
+
+    #[test]
+    fn attacking_orc_alerts_nearby_sleeping_orc_but_not_distant_one() {
+        const ALERT_RADIUS: u32 = 8;
+        // The wall hides the other orcs from the player, so only the alert can wake them
+        let mut world = world_from_rows(&[
+            "......#.............",
+            "......#.............",
+            "......#.............",
+            "......#.............",
+            "....................",
+        ]);
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(Coord::new(1, 1), &steady_player_config(Vec::new()))
+            .unwrap();
+        let victim_coord = Coord::new(2, 1);
+        spawn_steady_npc(&mut world, victim_coord, NpcType::Orc);
+        let near_orc = spawn_steady_npc(&mut world, Coord::new(8, 1), NpcType::Orc);
+        let far_orc = spawn_steady_npc(&mut world, Coord::new(18, 1), NpcType::Orc);
+        let mut near_agent = Agent::new_asleep();
+        let mut far_agent = Agent::new_asleep();
+        assert!(matches!(
+            world.maybe_move_character(player, Direction::East, &mut message_log, &mut rng),
+            MoveResult::Attacked { .. }
+        ));
+        let alerted = world.propagate_alert(victim_coord, ALERT_RADIUS);
+        assert!(alerted.contains(&near_orc));
+        assert!(!alerted.contains(&far_orc));
+        assert!(!alerted.contains(&player));
+        near_agent.alert();
+        let mut behaviour_context = BehaviourContext::new(world.size());
+        behaviour_context.update(player, &world);
+        assert!(matches!(
+            near_agent.act(near_orc, player, &world, &mut behaviour_context, &mut rng),
+            NpcAction::Move(_)
+        ));
+        assert!(!near_agent.is_asleep());
+        assert!(matches!(
+            far_agent.act(far_orc, player, &world, &mut behaviour_context, &mut rng),
+            NpcAction::Wait
+        ));
+        assert!(far_agent.is_asleep());
+    }
+
+    #[test]
+    fn sleeping_orc_wakes_once_player_comes_into_view() {
+        let mut world = world_from_rows(&[
+            "..........",
+            "......#...",
+            "......#...",
+            "......#...",
+            "..........",
+        ]);
+        let mut rng = rng();
+        let player = world
+            .spawn_player(Coord::new(1, 2), &steady_player_config(Vec::new()))
+            .unwrap();
+        let orc_coord = Coord::new(8, 2);
+        let orc = spawn_steady_npc(&mut world, orc_coord, NpcType::Orc);
+        let mut agent = Agent::new_asleep();
+        let mut behaviour_context = BehaviourContext::new(world.size());
+        for _ in 0..3 {
+            behaviour_context.update(player, &world);
+            assert!(matches!(
+                agent.act(orc, player, &world, &mut behaviour_context, &mut rng),
+                NpcAction::Wait
+            ));
+            assert!(agent.is_asleep());
+        }
+        assert_eq!(world.entity_coord(orc), Some(orc_coord));
+        world
+            .spatial_table
+            .update_coord(player, Coord::new(7, 4))
+            .unwrap();
+        behaviour_context.update(player, &world);
+        assert!(matches!(
+            agent.act(orc, player, &world, &mut behaviour_context, &mut rng),
+            NpcAction::Move(_)
+        ));
+        assert!(!agent.is_asleep());
+    }
+
+    #[test]
+    fn projectiles_reports_each_projectile_in_flight() {
+        let mut world = open_world(Size::new(10, 5));
+        assert!(world.projectiles().is_empty());
+        let fireball = world
+            .spawn_projectile(
+                Coord::new(1, 1),
+                Coord::new(8, 1),
+                ProjectileType::Fireball { damage: 3 },
+            )
+            .unwrap();
+        let arrow = world
+            .spawn_projectile(
+                Coord::new(2, 3),
+                Coord::new(8, 3),
+                ProjectileType::Arrow { damage: 1 },
+            )
+            .unwrap();
+        let projectiles = world.projectiles();
+        assert_eq!(projectiles.len(), 2);
+        let find = |entity| {
+            projectiles
+                .iter()
+                .find(|&&(projectile, _, _)| projectile == entity)
+                .map(|&(_, coord, projectile_type)| (coord, projectile_type))
+        };
+        assert!(matches!(
+            find(fireball),
+            Some((coord, ProjectileType::Fireball { damage: 3 })) if coord == Coord::new(1, 1)
+        ));
+        assert!(matches!(
+            find(arrow),
+            Some((coord, ProjectileType::Arrow { damage: 1 })) if coord == Coord::new(2, 3)
+        ));
+        world.remove_entity(arrow);
+        let projectiles = world.projectiles();
+        assert_eq!(projectiles.len(), 1);
+        assert_eq!(projectiles[0].0, fireball);
+    }
+
+    #[test]
+    fn cursed_sword_can_only_be_dropped_after_remove_curse() {
+        let mut world = open_world(Size::new(3, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(
+                Coord::new(1, 1),
+                &steady_player_config(vec![ItemType::Sword, ItemType::RemoveCurseScroll]),
+            )
+            .unwrap();
+        let sword = world.inventory_item_entity(player, 0).unwrap();
+        world.components.cursed.insert(sword, true);
+        world
+            .maybe_use_item(player, 0, &mut message_log, &mut rng)
+            .unwrap();
+        assert_eq!(
+            world.equipped_entity(player, EquipSlot::Weapon),
+            Some(sword)
+        );
+        message_log.clear();
+        assert!(world
+            .maybe_unequip_item(player, EquipSlot::Weapon, &mut message_log)
+            .is_err());
+        assert!(message_log
+            .iter()
+            .any(|message| matches!(message, LogMessage::ItemIsCursed(ItemType::Sword))));
+        assert_eq!(
+            world.equipped_entity(player, EquipSlot::Weapon),
+            Some(sword)
+        );
+        assert!(world.inventory_item_type(player, 0).is_none());
+        world
+            .maybe_use_item(player, 1, &mut message_log, &mut rng)
+            .unwrap();
+        world
+            .maybe_unequip_item(player, EquipSlot::Weapon, &mut message_log)
+            .unwrap();
+        assert_eq!(world.equipped_entity(player, EquipSlot::Weapon), None);
+        assert_eq!(world.inventory_item_entity(player, 0), Some(sword));
+        world.maybe_drop_item(player, 0, &mut message_log).unwrap();
+        assert_eq!(
+            world
+                .spatial_table
+                .layers_at_checked(Coord::new(1, 1))
+                .object,
+            Some(sword)
+        );
+    }
+
+    #[test]
+    fn troll_polymorphed_into_orc_takes_on_orc_hit_points_and_tile() {
+        let mut world = open_world(Size::new(3, 3));
+        let mut message_log = Vec::new();
+        let troll = spawn_steady_npc(&mut world, Coord::new(1, 1), NpcType::Troll);
+        world.components.hit_points.get_mut(troll).unwrap().current = 1;
+        world.polymorph_npc_into(troll, NpcType::Orc, &mut message_log);
+        let hit_points = world.hit_points(troll).unwrap();
+        assert_eq!(hit_points.max, NpcType::Orc.base_hit_points());
+        assert_eq!(hit_points.current, hit_points.max);
+        assert_eq!(world.npc_type(troll), Some(NpcType::Orc));
+        assert!(matches!(
+            world.components.tile.get(troll),
+            Some(Tile::Npc(NpcType::Orc))
+        ));
+        assert!(!world.components.knockback.contains(troll));
+        assert!(message_log.iter().any(|message| matches!(
+            message,
+            LogMessage::NpcPolymorphs {
+                from: NpcType::Troll,
+                to: NpcType::Orc
+            }
+        )));
+    }
+
+    #[test]
+    fn polymorph_always_changes_npc_type() {
+        let mut world = open_world(Size::new(3, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let npc = spawn_steady_npc(&mut world, Coord::new(1, 1), NpcType::Troll);
+        for _ in 0..10 {
+            let before = world.npc_type(npc).unwrap();
+            world.polymorph_npc(npc, &mut message_log, &mut rng);
+            assert_ne!(world.npc_type(npc), Some(before));
+        }
+    }
+
+    #[test]
+    fn wounded_player_with_vampiric_dagger_heals_by_killing_orc() {
+        let mut world = open_world(Size::new(5, 5));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(
+                Coord::new(1, 1),
+                &steady_player_config(vec![ItemType::VampiricDagger]),
+            )
+            .unwrap();
+        world
+            .maybe_use_item(player, 0, &mut message_log, &mut rng)
+            .unwrap();
+        let max = world.hit_points(player).unwrap().max;
+        world.components.hit_points.get_mut(player).unwrap().current = max - 3;
+        let orc = spawn_steady_npc(&mut world, Coord::new(2, 1), NpcType::Orc);
+        let orc_hit_points = world.hit_points(orc).unwrap().current;
+        assert_eq!(
+            world.maybe_move_character(player, Direction::East, &mut message_log, &mut rng),
+            MoveResult::Attacked { killed: true }
+        );
+        assert_eq!(
+            world.hit_points(player).unwrap().current,
+            max - 3 + orc_hit_points * VAMPIRIC_DAGGER_LIFESTEAL / 100
+        );
+        assert!(world.hit_points(player).unwrap().current > max - 3);
+        assert!(message_log
+            .iter()
+            .any(|message| matches!(message, LogMessage::PlayerDrainsLife)));
+    }
+
+    #[test]
+    fn chain_lightning_damages_clustered_orcs_with_diminishing_damage() {
+        let mut world = open_world(Size::new(7, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let orcs = [2, 3, 4]
+            .iter()
+            .map(|&x| {
+                let orc = spawn_steady_npc(&mut world, Coord::new(x, 1), NpcType::Orc);
+                world
+                    .components
+                    .hit_points
+                    .insert(orc, HitPoints::new_full(10));
+                orc
+            })
+            .collect::<Vec<_>>();
+        world.chain_lightning(orcs[0], 3, &mut message_log, &mut rng);
+        let damage_taken = orcs
+            .iter()
+            .map(|&orc| 10 - world.hit_points(orc).unwrap().current)
+            .collect::<Vec<_>>();
+        assert_eq!(damage_taken, vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn chain_lightning_does_not_jump_through_walls() {
+        let mut world = world_from_rows(&[".......", "...#...", "......."]);
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let near = spawn_steady_npc(&mut world, Coord::new(2, 1), NpcType::Troll);
+        let hidden = spawn_steady_npc(&mut world, Coord::new(4, 1), NpcType::Troll);
+        let hidden_hit_points = world.hit_points(hidden).unwrap().current;
+        world.chain_lightning(near, 3, &mut message_log, &mut rng);
+        assert_eq!(world.hit_points(hidden).unwrap().current, hidden_hit_points);
+    }
+
+    #[test]
+    fn digger_tunnels_through_wall_over_two_turns() {
+        let mut world = world_from_rows(&["#######", "#..#..#", "#######"]);
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(Coord::new(2, 1), &steady_player_config(Vec::new()))
+            .unwrap();
+        let digger = spawn_steady_npc(&mut world, Coord::new(4, 1), NpcType::Digger);
+        let wall_coord = Coord::new(3, 1);
+        let mut agent = Agent::new();
+        agent.alert();
+        let mut behaviour_context = BehaviourContext::new(world.size());
+        behaviour_context.update(player, &world);
+        match agent.act(digger, player, &world, &mut behaviour_context, &mut rng) {
+            NpcAction::Dig(coord) => {
+                assert_eq!(coord, wall_coord);
+                world.npc_dig(digger, coord, &mut message_log).unwrap();
+            }
+            _ => panic!("digger didn't dig"),
+        }
+        assert_eq!(world.entity_coord(digger), Some(Coord::new(4, 1)));
+        assert!(world.can_npc_enter(wall_coord));
+        behaviour_context.update(player, &world);
+        match agent.act(digger, player, &world, &mut behaviour_context, &mut rng) {
+            NpcAction::Move(direction) => {
+                assert_eq!(direction, CardinalDirection::West);
+                world.maybe_move_character(
+                    digger,
+                    direction.direction(),
+                    &mut message_log,
+                    &mut rng,
+                );
+            }
+            _ => panic!("digger didn't move"),
+        }
+        assert_eq!(world.entity_coord(digger), Some(wall_coord));
+    }
+
+    #[test]
+    fn orc_hit_by_two_fireballs_at_once_dies_once() {
+        let mut world = open_world(Size::new(9, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let orc = spawn_steady_npc(&mut world, Coord::new(4, 1), NpcType::Orc);
+        for &from in &[Coord::new(1, 1), Coord::new(7, 1)] {
+            world
+                .spawn_projectile(
+                    from,
+                    Coord::new(4, 1),
+                    ProjectileType::Fireball { damage: 5 },
+                )
+                .unwrap();
+        }
+        while world.has_projectiles() {
+            world.move_projectiles(&mut message_log, &mut rng);
+        }
+        assert!(!world.is_living_character(orc));
+        let num_deaths = message_log
+            .iter()
+            .filter(|message| matches!(message, LogMessage::NpcDies(NpcType::Orc)))
+            .count();
+        assert_eq!(num_deaths, 1);
+    }
+
+    #[test]
+    fn examining_terrain_reports_walls_and_floors() {
+        let mut world = world_from_rows(&["...", ".#.", "..."]);
+        let player = world
+            .spawn_player(Coord::new(0, 0), &steady_player_config(Vec::new()))
+            .unwrap();
+        assert!(matches!(
+            world.examine_cell(player, Coord::new(1, 1)),
+            Some(ExamineCell::Wall)
+        ));
+        assert!(matches!(
+            world.examine_cell(player, Coord::new(2, 2)),
+            Some(ExamineCell::Floor)
+        ));
+        assert!(world.examine_cell(player, Coord::new(5, 5)).is_none());
+    }
+
+    #[test]
+    fn netted_orc_stays_put_until_net_wears_off() {
+        let mut world = open_world(Size::new(7, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(Coord::new(1, 1), &steady_player_config(vec![ItemType::Net]))
+            .unwrap();
+        let orc_coord = Coord::new(4, 1);
+        let orc = spawn_steady_npc(&mut world, orc_coord, NpcType::Orc);
+        world
+            .maybe_use_item_aim(player, 0, orc_coord, &mut message_log, &mut rng)
+            .unwrap();
+        while world.has_projectiles() {
+            world.move_projectiles(&mut message_log, &mut rng);
+        }
+        assert!(world.is_rooted(orc));
+        assert!(message_log
+            .iter()
+            .any(|message| matches!(message, LogMessage::NpcIsRooted(NpcType::Orc))));
+        for _ in 0..NET_DURATION {
+            assert_eq!(
+                world.maybe_move_character(orc, Direction::East, &mut message_log, &mut rng),
+                MoveResult::Blocked
+            );
+            assert_eq!(world.entity_coord(orc), Some(orc_coord));
+            world.tick_rooted(&mut message_log);
+        }
+        assert!(!world.is_rooted(orc));
+        assert_eq!(
+            world.maybe_move_character(orc, Direction::East, &mut message_log, &mut rng),
+            MoveResult::Moved
+        );
+        assert_eq!(world.entity_coord(orc), Some(Coord::new(5, 1)));
+    }
+
+    #[test]
+    fn strength_potion_increases_attack_damage() {
+        const NUM_ATTACKS: u32 = 20;
+        let mut world = open_world(Size::new(5, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(
+                Coord::new(1, 1),
+                &steady_player_config(vec![ItemType::StrengthPotion]),
+            )
+            .unwrap();
+        world.components.crit_chance.insert(player, 0);
+        let troll = spawn_steady_npc(&mut world, Coord::new(2, 1), NpcType::Troll);
+        world
+            .components
+            .hit_points
+            .insert(troll, HitPoints::new_full(1000));
+        let mut attack_repeatedly = |world: &mut World| {
+            let before = world.hit_points(troll).unwrap().current;
+            for _ in 0..NUM_ATTACKS {
+                world.maybe_move_character(player, Direction::East, &mut message_log, &mut rng);
+            }
+            before - world.hit_points(troll).unwrap().current
+        };
+        let weak_damage = attack_repeatedly(&mut world);
+        let strength = world.player_strength(player).unwrap();
+        world
+            .maybe_use_item(
+                player,
+                0,
+                &mut Vec::new(),
+                &mut Isaac64Rng::seed_from_u64(1),
+            )
+            .unwrap();
+        assert_eq!(world.player_strength(player), Some(strength + 1));
+        let strong_damage = attack_repeatedly(&mut world);
+        assert!(strong_damage > weak_damage);
+    }
+
+    #[test]
+    fn player_move_checks_distinguish_wall_npc_and_floor() {
+        let mut world = world_from_rows(&["...", "#..", "..."]);
+        let player = world
+            .spawn_player(Coord::new(0, 0), &steady_player_config(Vec::new()))
+            .unwrap();
+        let wall = Coord::new(0, 1);
+        let npc_coord = Coord::new(1, 0);
+        let floor = Coord::new(1, 1);
+        let orc = spawn_steady_npc(&mut world, npc_coord, NpcType::Orc);
+        assert!(!world.can_player_enter(wall));
+        assert_eq!(world.player_would_attack_at(player, wall), None);
+        assert!(!world.can_player_enter(npc_coord));
+        assert_eq!(world.player_would_attack_at(player, npc_coord), Some(orc));
+        assert!(world.can_player_enter(floor));
+        assert_eq!(world.player_would_attack_at(player, floor), None);
+    }
+
+    // Returns the damage dealt to an orc with plenty of hit points by a single bump attack from a
+    // player with the given stats against an orc with the given stats
+    fn bump_attack_damage(player_stats: Stats, orc_stats: Stats) -> u32 {
+        let mut world = open_world(Size::new(5, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(Coord::new(1, 1), &steady_player_config(Vec::new()))
+            .unwrap();
+        world.components.crit_chance.insert(player, 0);
+        world.components.stats.insert(player, player_stats);
+        let orc = spawn_steady_npc(&mut world, Coord::new(2, 1), NpcType::Orc);
+        world.components.stats.insert(orc, orc_stats);
+        world
+            .components
+            .hit_points
+            .insert(orc, HitPoints::new_full(100));
+        assert!(matches!(
+            world.maybe_move_character(player, Direction::East, &mut message_log, &mut rng),
+            MoveResult::Attacked { killed: false }
+        ));
+        100 - world.hit_points(orc).unwrap().current
+    }
+
+    #[test]
+    fn high_defense_victim_takes_minimum_damage() {
+        let player_stats = Stats {
+            attack: 5,
+            defense: 0,
+        };
+        let orc_stats = Stats {
+            attack: 1,
+            defense: 50,
+        };
+        assert_eq!(bump_attack_damage(player_stats, orc_stats), 1);
+    }
+
+    #[test]
+    fn high_attack_attacker_deals_more_damage() {
+        let orc_stats = Stats {
+            attack: 1,
+            defense: 2,
+        };
+        let weak = Stats {
+            attack: 3,
+            defense: 0,
+        };
+        let strong = Stats {
+            attack: 10,
+            defense: 0,
+        };
+        assert_eq!(bump_attack_damage(weak, orc_stats), 1);
+        assert_eq!(bump_attack_damage(strong, orc_stats), 8);
+    }
+
+    #[test]
+    fn hits_which_beat_the_dodge_deal_damage_through_armour() {
+        let mut world = open_world(Size::new(5, 3));
+        let mut rng = rng();
+        let attacker = spawn_steady_npc(&mut world, Coord::new(1, 1), NpcType::Orc);
+        world.components.strength.insert(attacker, 0);
+        let victim = spawn_steady_npc(&mut world, Coord::new(2, 1), NpcType::Orc);
+        world.components.stats.insert(
+            victim,
+            Stats {
+                attack: 1,
+                defense: 0,
+            },
+        );
+        world.equip_npc_armour(victim, ItemType::Armour);
+        let armour = world.equipped_entity(victim, EquipSlot::Armour).unwrap();
+        world.components.armour_defense.insert(armour, 50);
+        let set_attack = |world: &mut World, attack| {
+            world
+                .components
+                .stats
+                .insert(attacker, Stats { attack, defense: 0 });
+        };
+        // an attack which the dodge roll matches deals no damage
+        set_attack(&mut world, 0);
+        assert_eq!(world.resolve_attack(attacker, victim, &mut rng), 0);
+        // an attack which beats the dodge roll deals at least 1 damage however heavy the armour
+        set_attack(&mut world, 3);
+        assert_eq!(world.resolve_attack(attacker, victim, &mut rng), 1);
+        // a dodgy victim avoids some attacks entirely, but the rest still deal the minimum
+        world.components.dexterity.insert(victim, 5);
+        let damage = (0..100)
+            .map(|_| world.resolve_attack(attacker, victim, &mut rng))
+            .collect::<Vec<_>>();
+        assert!(damage.iter().all(|&damage| damage <= 1));
+        assert!(damage.contains(&0));
+        assert!(damage.contains(&1));
+    }
+
+    #[test]
+    fn living_character_coords_excludes_corpses() {
+        let mut world = open_world(Size::new(5, 3));
+        let mut rng = rng();
+        let living_coord = Coord::new(1, 1);
+        let corpse_coord = Coord::new(3, 1);
+        spawn_steady_npc(&mut world, living_coord, NpcType::Orc);
+        let dead = spawn_steady_npc(&mut world, corpse_coord, NpcType::Orc);
+        assert!(world
+            .character_damage(dead, 100, DamageKind::Melee, &mut rng)
+            .is_some());
+        assert_eq!(world.entity_coord(dead), Some(corpse_coord));
+        assert!(matches!(
+            world.components.tile.get(dead),
+            Some(Tile::NpcCorpse(NpcType::Orc))
+        ));
+        assert_eq!(world.living_character_coords(), vec![living_coord]);
+    }
+
+    #[test]
+    fn deeper_orcs_drop_better_loot() {
+        const NUM_DROPS: usize = 1000;
+        let drops = |level: u32| {
+            let mut rng = rng();
+            (0..NUM_DROPS)
+                .filter_map(|_| NpcType::Orc.loot_table().choose(level, &mut rng))
+                .collect::<Vec<_>>()
+        };
+        let count = |drops: &[ItemType], item_type: ItemType| {
+            drops.iter().filter(|&&drop| drop == item_type).count()
+        };
+        let shallow = drops(1);
+        let deep = drops(5);
+        for &item_type in &[ItemType::FireballScroll, ItemType::Sword] {
+            assert_eq!(count(&shallow, item_type), 0);
+            assert!(count(&deep, item_type) > 0);
+        }
+        assert!(count(&shallow, ItemType::HealthPotion) > 0);
+        // Most orcs drop nothing at any depth
+        assert!(shallow.len() < NUM_DROPS / 2);
+        assert!(deep.len() < NUM_DROPS / 2);
+        assert!(deep.len() > shallow.len());
+    }
+
+    #[test]
+    fn swapping_exchanges_item_when_inventory_is_full() {
+        let mut world = open_world(Size::new(3, 3));
+        let mut message_log = Vec::new();
+        let coord = Coord::new(1, 1);
+        let player = world
+            .spawn_player(
+                coord,
+                &PlayerConfig {
+                    inventory_capacity: 2,
+                    ..steady_player_config(vec![ItemType::Sword, ItemType::Staff])
+                },
+            )
+            .unwrap();
+        let sword = world.inventory_item_entity(player, 0).unwrap();
+        let staff = world.inventory_item_entity(player, 1).unwrap();
+        let dagger = world.spawn_item(coord, ItemType::Dagger).unwrap();
+        assert!(world.maybe_get_item(player, &mut message_log).is_err());
+        world.maybe_swap_item(player, 0, &mut message_log).unwrap();
+        assert_eq!(world.inventory_item_entity(player, 0), Some(dagger));
+        assert_eq!(world.inventory_item_entity(player, 1), Some(staff));
+        assert_eq!(
+            world.spatial_table.layers_at_checked(coord).object,
+            Some(sword)
+        );
+    }
+
+    #[test]
+    fn swapping_fails_when_object_under_player_is_not_an_item() {
+        let mut world = open_world(Size::new(3, 3));
+        let mut message_log = Vec::new();
+        let coord = Coord::new(1, 1);
+        let player = world
+            .spawn_player(coord, &steady_player_config(vec![ItemType::Sword]))
+            .unwrap();
+        let sword = world.inventory_item_entity(player, 0).unwrap();
+        let gold = world.spawn_gold(coord, 10).unwrap();
+        assert!(world.maybe_swap_item(player, 0, &mut message_log).is_err());
+        assert!(message_log
+            .iter()
+            .any(|message| matches!(message, LogMessage::NoItemUnderPlayer)));
+        assert_eq!(world.inventory_item_entity(player, 0), Some(sword));
+        assert_eq!(
+            world.spatial_table.layers_at_checked(coord).object,
+            Some(gold)
+        );
+    }
+
+    #[test]
+    fn fast_orc_closes_distance_twice_as_quickly() {
+        let mut world = open_world(Size::new(15, 7));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(Coord::new(1, 3), &steady_player_config(Vec::new()))
+            .unwrap();
+        let slow_orc = spawn_steady_npc(&mut world, Coord::new(9, 0), NpcType::Orc);
+        let fast_orc = spawn_steady_npc(&mut world, Coord::new(9, 6), NpcType::Orc);
+        world.components.speed.insert(slow_orc, 1);
+        world.components.speed.insert(fast_orc, 2);
+        assert_eq!(world.npc_actions_per_turn(fast_orc), 2);
+        let mut agents = [(slow_orc, Agent::new()), (fast_orc, Agent::new())];
+        let mut behaviour_context = BehaviourContext::new(world.size());
+        let player_coord = world.entity_coord(player).unwrap();
+        let distance =
+            |world: &World, orc| manhattan_distance(world.entity_coord(orc).unwrap(), player_coord);
+        let slow_start = distance(&world, slow_orc);
+        let fast_start = distance(&world, fast_orc);
+        // A single player turn
+        behaviour_context.update(player, &world);
+        for (orc, agent) in agents.iter_mut() {
+            for _ in 0..world.npc_actions_per_turn(*orc) {
+                if let NpcAction::Move(direction) =
+                    agent.act(*orc, player, &world, &mut behaviour_context, &mut rng)
+                {
+                    world.maybe_move_character(
+                        *orc,
+                        direction.direction(),
+                        &mut message_log,
+                        &mut rng,
+                    );
                 }
             }
         }
-        for (entity, duration) in confusion_hit {
-            self.components.confusion_countdown.insert(entity, duration);
-            if let Some(&npc_type) = self.components.npc_type.get(entity) {
-                message_log.push(LogMessage::NpcBecomesConfused(npc_type));
+        assert_eq!(slow_start - distance(&world, slow_orc), 1);
+        assert_eq!(fast_start - distance(&world, fast_orc), 2);
+    }
+
+    #[test]
+    fn reflecting_shield_sends_fireball_back_the_way_it_came() {
+        let mut world = open_world(Size::new(9, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(
+                Coord::new(6, 1),
+                &steady_player_config(vec![ItemType::ReflectShield]),
+            )
+            .unwrap();
+        world
+            .maybe_use_item(player, 0, &mut message_log, &mut rng)
+            .unwrap();
+        let hit_points = world.hit_points(player).unwrap().current;
+        let fireball = world
+            .spawn_projectile(
+                Coord::new(1, 1),
+                Coord::new(6, 1),
+                ProjectileType::Fireball { damage: 3 },
+            )
+            .unwrap();
+        let mut reflected_at = None;
+        while reflected_at.is_none() && world.has_projectiles() {
+            world.move_projectiles(&mut message_log, &mut rng);
+            if message_log
+                .iter()
+                .any(|message| matches!(message, LogMessage::ProjectileReflected(_)))
+            {
+                reflected_at = world.entity_coord(fireball);
             }
         }
+        let reflected_at = reflected_at.expect("fireball wasn't reflected");
+        assert_eq!(reflected_at, Coord::new(5, 1));
+        world.move_projectiles(&mut message_log, &mut rng);
+        assert_eq!(world.entity_coord(fireball), Some(Coord::new(4, 1)));
+        while world.has_projectiles() {
+            world.move_projectiles(&mut message_log, &mut rng);
+        }
+        assert_eq!(world.hit_points(player).unwrap().current, hit_points);
     }
-    pub fn has_projectiles(&self) -> bool {
-        !self.components.trajectory.is_empty()
+
+    // Returns a description of every entity placed on a level generated from `seed`, in the order
+    // they're rendered
+    fn seeded_level_layout(seed: u64) -> Vec<(Coord, String)> {
+        let mut world = World::new(Size::new(40, 30));
+        let (_, returned_seed) = world.populate_seeded(
+            1,
+            &PlayerConfig::default(),
+            &MonsterSpawnTable::default(),
+            &GenerationConfig::default(),
+            DifficultyScale::default(),
+            seed,
+        );
+        assert_eq!(returned_seed, seed);
+        world
+            .entities_with_tiles()
+            .map(|(_, coord, tile, _)| (coord, format!("{:?}", tile)))
+            .collect()
     }
-    pub fn inventory(&self, entity: Entity) -> Option<&Inventory> {
-        self.components.inventory.get(entity)
+
+    #[test]
+    fn same_seed_populates_identical_levels() {
+        let first = seeded_level_layout(42);
+        assert_eq!(seeded_level_layout(42), first);
+        let monsters = |layout: &[(Coord, String)]| {
+            layout
+                .iter()
+                .filter(|(_, tile)| tile.starts_with("Npc("))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        assert!(!monsters(&first).is_empty());
+        assert_ne!(seeded_level_layout(43), first);
     }
-    pub fn item_type(&self, entity: Entity) -> Option<ItemType> {
-        self.components.item.get(entity).cloned()
+
+    #[test]
+    fn backpack_grows_inventory_up_to_the_cap() {
+        let mut world = open_world(Size::new(3, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(
+                Coord::new(1, 1),
+                &steady_player_config(vec![ItemType::Backpack; 4]),
+            )
+            .unwrap();
+        let capacity = |world: &World| world.inventory(player).unwrap().slots().len();
+        assert_eq!(capacity(&world), 10);
+        let backpack_index = |world: &World| {
+            (0..capacity(world))
+                .find(|&index| world.inventory_item_type(player, index) == Some(ItemType::Backpack))
+                .unwrap()
+        };
+        let index = backpack_index(&world);
+        world
+            .maybe_use_item(player, index, &mut message_log, &mut rng)
+            .unwrap();
+        assert_eq!(capacity(&world), 15);
+        assert!(message_log
+            .iter()
+            .any(|message| matches!(message, LogMessage::PlayerInventoryExpands)));
+        let index = backpack_index(&world);
+        world
+            .maybe_use_item(player, index, &mut message_log, &mut rng)
+            .unwrap();
+        assert_eq!(capacity(&world), MAX_INVENTORY_CAPACITY);
+        let index = backpack_index(&world);
+        assert!(world
+            .maybe_use_item(player, index, &mut message_log, &mut rng)
+            .is_err());
+        assert_eq!(capacity(&world), MAX_INVENTORY_CAPACITY);
+        assert!(message_log
+            .iter()
+            .any(|message| matches!(message, LogMessage::PlayerInventoryCannotExpand)));
     }
-    pub fn is_living_character(&self, entity: Entity) -> bool {
-        self.spatial_table.layer_of(entity) == Some(Layer::Character)
+
+    #[test]
+    fn active_status_effects_lists_poison_and_haste_with_durations() {
+        let mut world = open_world(Size::new(3, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(
+                Coord::new(1, 1),
+                &steady_player_config(vec![ItemType::HasteScroll]),
+            )
+            .unwrap();
+        assert!(world.active_status_effects(player).is_empty());
+        world.character_poison(player, 5);
+        assert!(world
+            .maybe_use_item(player, 0, &mut message_log, &mut rng)
+            .is_ok());
+        let effects = world.active_status_effects(player);
+        assert_eq!(effects.len(), 2);
+        assert!(effects.contains(&StatusEffect::Poisoned(5)));
+        assert!(effects.contains(&StatusEffect::Hasted(HASTE_DURATION)));
     }
-    pub fn remove_entity(&mut self, entity: Entity) {
-        self.components.remove_entity(entity);
-        self.spatial_table.remove(entity);
-        self.entity_allocator.free(entity);
+
+    #[test]
+    fn nearest_character_breaks_ties_in_row_major_order() {
+        let from = Coord::new(2, 2);
+        let upper = Coord::new(2, 0);
+        let left = Coord::new(0, 2);
+        // Spawning in either order must choose the same orc, since the upper one comes first
+        // in row-major order
+        for &(first, second) in &[(upper, left), (left, upper)] {
+            let mut world = open_world(Size::new(5, 5));
+            spawn_steady_npc(&mut world, first, NpcType::Orc);
+            spawn_steady_npc(&mut world, second, NpcType::Orc);
+            let (_, coord) = world.nearest_character(from, |_| true).unwrap();
+            assert_eq!(coord, upper);
+        }
     }
-    pub fn size(&self) -> Size {
-        self.spatial_table.grid_size()
+
+    #[test]
+    fn repeated_searching_reveals_adjacent_secret_wall() {
+        let mut world = world_from_rows(&["#####", "#   #", "#####"]);
+        let secret = Coord::new(3, 1);
+        world.spawn_secret_wall(secret).unwrap();
+        let mut rng = rng();
+        assert!(!world.can_npc_enter(secret));
+        let mut num_searches = 0;
+        while world.search(Coord::new(2, 1), &mut rng) == 0 {
+            assert!(!world.can_npc_enter(secret));
+            num_searches += 1;
+            assert!(num_searches < 100, "secret wall was never found");
+        }
+        assert!(world.can_npc_enter(secret));
+        assert_eq!(world.spatial_table.layers_at_checked(secret).feature, None);
     }
-    pub fn opacity_at(&self, coord: Coord) -> u8 {
-        if self
-            .spatial_table
-            .layers_at_checked(coord)
-            .feature
-            .is_some()
-        {
-            255
-        } else {
-            0
+
+    #[test]
+    fn bumping_neutral_npc_talks_instead_of_attacking() {
+        let mut world = open_world(Size::new(5, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(Coord::new(1, 1), &steady_player_config(Vec::new()))
+            .unwrap();
+        let neutral = spawn_steady_npc(&mut world, Coord::new(2, 1), NpcType::Troll);
+        world.components.faction.insert(neutral, Faction::Neutral);
+        let hit_points_before = world.hit_points(neutral).unwrap().current;
+        assert_eq!(
+            world.maybe_move_character(player, Direction::East, &mut message_log, &mut rng),
+            MoveResult::Talked
+        );
+        assert_eq!(
+            world.hit_points(neutral).unwrap().current,
+            hit_points_before
+        );
+        assert_eq!(world.entity_coord(player), Some(Coord::new(1, 1)));
+        assert!(matches!(
+            message_log.as_slice(),
+            [LogMessage::PlayerTalksTo(NpcType::Troll)]
+        ));
+    }
+
+    #[test]
+    fn earthquake_scroll_collapses_some_walls_around_player() {
+        let mut world = world_from_rows(&[
+            "###########",
+            "#         #",
+            "#         #",
+            "#  #####  #",
+            "#  #   #  #",
+            "#  #   #  #",
+            "#  #   #  #",
+            "#  #####  #",
+            "#         #",
+            "#         #",
+            "###########",
+        ]);
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let centre = Coord::new(5, 5);
+        let player = world
+            .spawn_player(
+                centre,
+                &steady_player_config(vec![ItemType::EarthquakeScroll]),
+            )
+            .unwrap();
+        let is_wall = |world: &World, coord: Coord| {
+            world
+                .spatial_table
+                .layers_at_checked(coord)
+                .feature
+                .map(|feature| matches!(world.components.tile.get(feature), Some(Tile::Wall)))
+                .unwrap_or(false)
+        };
+        let inner_walls = world
+            .coords_within_radius(centre, 2)
+            .into_iter()
+            .filter(|&coord| is_wall(&world, coord))
+            .collect::<Vec<_>>();
+        assert_eq!(inner_walls.len(), 16);
+        assert!(matches!(
+            world.maybe_use_item(player, 0, &mut message_log, &mut rng),
+            Ok(ItemUsage::Immediate)
+        ));
+        assert!(inner_walls.iter().any(|&coord| !is_wall(&world, coord)));
+        assert!(!is_wall(&world, centre));
+        assert_eq!(world.entity_coord(player), Some(centre));
+        assert!(matches!(message_log.as_slice(), [LogMessage::Earthquake]));
+    }
+
+    #[test]
+    fn wounded_troll_regenerates_but_wounded_orc_does_not() {
+        let mut world = open_world(Size::new(5, 3));
+        let troll = spawn_steady_npc(&mut world, Coord::new(1, 1), NpcType::Troll);
+        let orc = spawn_steady_npc(&mut world, Coord::new(3, 1), NpcType::Orc);
+        world.components.hit_points.get_mut(troll).unwrap().current = 1;
+        world.components.hit_points.get_mut(orc).unwrap().current = 1;
+        let troll_max = world.hit_points(troll).unwrap().max;
+        for turn in 1..troll_max {
+            world.npc_regenerate();
+            assert_eq!(world.hit_points(troll).unwrap().current, 1 + turn);
+            assert_eq!(world.hit_points(orc).unwrap().current, 1);
         }
+        world.npc_regenerate();
+        assert_eq!(world.hit_points(troll).unwrap().current, troll_max);
     }
-    pub fn hit_points(&self, entity: Entity) -> Option<HitPoints> {
-        self.components.hit_points.get(entity).cloned()
+
+    #[test]
+    fn item_over_weight_limit_is_rejected_despite_free_slots() {
+        let mut world = open_world(Size::new(3, 3));
+        let mut message_log = Vec::new();
+        let coord = Coord::new(1, 1);
+        let player = world
+            .spawn_player(coord, &steady_player_config(Vec::new()))
+            .unwrap();
+        let (carried, capacity) = world.player_carry_weight(player);
+        assert_eq!(carried, 0);
+        // Pick up suits of armour until another one would be too heavy to carry
+        let weight = ItemType::Armour.weight();
+        let mut num_picked_up = 0;
+        while world.player_carry_weight(player).0 + weight <= capacity {
+            world.spawn_item(coord, ItemType::Armour).unwrap();
+            assert!(world.maybe_get_item(player, &mut message_log).is_ok());
+            num_picked_up += 1;
+        }
+        assert_eq!(
+            world.player_carry_weight(player),
+            (num_picked_up * weight, capacity)
+        );
+        assert!((num_picked_up as usize) < steady_player_config(Vec::new()).inventory_capacity);
+        let armour = world.spawn_item(coord, ItemType::Armour).unwrap();
+        assert!(world.maybe_get_item(player, &mut message_log).is_err());
+        assert!(matches!(
+            message_log.last(),
+            Some(LogMessage::TooHeavy(ItemType::Armour, _))
+        ));
+        assert_eq!(world.entity_coord(armour), Some(coord));
+        assert!(world
+            .inventory_item_type(player, num_picked_up as usize)
+            .is_none());
     }
-    pub fn entity_coord(&self, entity: Entity) -> Option<Coord> {
-        self.spatial_table.coord_of(entity)
+
+    #[test]
+    fn sweep_attack_hits_every_adjacent_hostile_but_not_allies() {
+        let mut world = open_world(Size::new(5, 5));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let player = world
+            .spawn_player(
+                Coord::new(2, 2),
+                &steady_player_config(vec![ItemType::Staff]),
+            )
+            .unwrap();
+        assert!(world
+            .maybe_use_item(player, 0, &mut message_log, &mut rng)
+            .is_ok());
+        let orcs = [Coord::new(1, 1), Coord::new(3, 2), Coord::new(2, 3)]
+            .iter()
+            .map(|&coord| spawn_steady_npc(&mut world, coord, NpcType::Orc))
+            .collect::<Vec<_>>();
+        let ally = spawn_steady_npc(&mut world, Coord::new(1, 2), NpcType::Orc);
+        world.components.faction.insert(ally, Faction::Player);
+        let player_hit_points = world.hit_points(player).unwrap().current;
+        message_log.clear();
+        assert_eq!(
+            world.sweep_attack(player, &mut message_log, &mut rng),
+            Ok(3)
+        );
+        for &orc in &orcs {
+            let hurt = world
+                .hit_points(orc)
+                .map(|hit_points| hit_points.current < hit_points.max)
+                .unwrap_or(true);
+            assert!(hurt || !world.is_living_character(orc));
+        }
+        let ally_hit_points = world.hit_points(ally).unwrap();
+        assert_eq!(ally_hit_points.current, ally_hit_points.max);
+        assert_eq!(world.hit_points(player).unwrap().current, player_hit_points);
+        assert_eq!(
+            message_log
+                .iter()
+                .filter(|message| matches!(
+                    message,
+                    LogMessage::PlayerAttacksNpc(NpcType::Orc)
+                        | LogMessage::PlayerKillsNpc(NpcType::Orc)
+                ))
+                .count(),
+            3
+        );
     }
-    pub fn can_npc_enter_ignoring_other_npcs(&self, coord: Coord) -> bool {
-        self.spatial_table
-            .layers_at(coord)
-            .map(|layers| layers.feature.is_none())
-            .unwrap_or(false)
+
+    #[test]
+    fn populating_with_custom_max_hit_points_spawns_player_with_them() {
+        let mut world = World::new(Size::new(40, 30));
+        let mut rng = rng();
+        let Populate { player_entity, .. } = world.populate(
+            1,
+            &PlayerConfig {
+                max_hit_points: 30,
+                ..Default::default()
+            },
+            &MonsterSpawnTable::default(),
+            &GenerationConfig::default(),
+            DifficultyScale::default(),
+            &mut rng,
+        );
+        let hit_points = world.hit_points(player_entity).unwrap();
+        assert_eq!(hit_points.max, 30);
+        assert_eq!(hit_points.current, 30);
+        assert_eq!(PlayerConfig::default().max_hit_points, 20);
     }
-    pub fn can_npc_enter(&self, coord: Coord) -> bool {
-        self.spatial_table
-            .layers_at(coord)
-            .map(|layers| {
-                let contains_npc = layers
-                    .character
-                    .map(|entity| self.components.npc_type.contains(entity))
-                    .unwrap_or(false);
-                let contains_feature = layers.feature.is_some();
-                !(contains_npc || contains_feature)
-            })
-            .unwrap_or(false)
+
+    #[test]
+    fn next_visible_character_cycles_through_orcs_and_wraps() {
+        let mut world = open_world(Size::new(6, 6));
+        let first = Coord::new(4, 1);
+        let second = Coord::new(1, 3);
+        let third = Coord::new(3, 3);
+        for &coord in &[third, first, second] {
+            spawn_steady_npc(&mut world, coord, NpcType::Orc);
+        }
+        // An orc outside the visible cells is skipped
+        let hidden = Coord::new(2, 5);
+        spawn_steady_npc(&mut world, hidden, NpcType::Orc);
+        let visible_coords = Size::new(6, 5).coord_iter_row_major().collect::<Vec<_>>();
+        let mut cursor = Coord::new(0, 0);
+        let mut visited = Vec::new();
+        for _ in 0..4 {
+            cursor = world
+                .next_visible_character(cursor, &visible_coords)
+                .unwrap();
+            visited.push(cursor);
+        }
+        assert_eq!(visited, vec![first, second, third, first]);
+        assert_eq!(world.next_visible_character(cursor, &[]), None);
     }
-    pub fn can_npc_see_through_cell(&self, coord: Coord) -> bool {
-        self.spatial_table
-            .layers_at(coord)
-            .map(|layers| layers.feature.is_none())
-            .unwrap_or(false)
+
+    #[test]
+    fn fireball_hit_records_damage_event_at_victim() {
+        let mut world = open_world(Size::new(5, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let troll_coord = Coord::new(3, 1);
+        let troll = spawn_steady_npc(&mut world, troll_coord, NpcType::Troll);
+        world
+            .spawn_projectile(
+                Coord::new(1, 1),
+                troll_coord,
+                ProjectileType::Fireball { damage: 3 },
+            )
+            .unwrap();
+        while world.has_projectiles() {
+            world.move_projectiles(&mut message_log, &mut rng);
+        }
+        let hit_points = world.hit_points(troll).unwrap();
+        assert_eq!(hit_points.current, hit_points.max - 3);
+        assert!(matches!(
+            world.damage_events(),
+            [DamageEvent {
+                coord,
+                amount: 3,
+                kind: DamageKind::Fire,
+                ..
+            }] if *coord == troll_coord
+        ));
     }
-    pub fn examine_cell(&self, coord: Coord) -> Option<ExamineCell> {
-        let layers = self.spatial_table.layers_at(coord)?;
-        layers
-            .character
-            .or_else(|| layers.object)
-            .and_then(|entity| {
-                self.components
-                    .tile
-                    .get(entity)
-                    .and_then(|&tile| match tile {
-                        Tile::Npc(npc_type) => Some(ExamineCell::Npc(npc_type)),
-                        Tile::NpcCorpse(npc_type) => Some(ExamineCell::NpcCorpse(npc_type)),
-                        Tile::Item(item_type) => Some(ExamineCell::Item(item_type)),
-                        Tile::Player => Some(ExamineCell::Player),
-                        _ => None,
-                    })
-            })
+
+    #[test]
+    fn aiming_fireball_next_to_player_would_harm_them() {
+        let mut world = open_world(Size::new(7, 3));
+        let player = world
+            .spawn_player(Coord::new(1, 1), &steady_player_config(Vec::new()))
+            .unwrap();
+        assert_eq!(ProjectileType::Fireball { damage: 0 }.blast_radius(), 1);
+        assert!(world.aim_would_harm_self(player, ItemType::FireballScroll, Coord::new(2, 1)));
+        assert!(world.aim_would_harm_self(player, ItemType::FireballWand, Coord::new(2, 2)));
+        assert!(!world.aim_would_harm_self(player, ItemType::FireballScroll, Coord::new(4, 1)));
+        assert!(!world.aim_would_harm_self(player, ItemType::ConfusionScroll, Coord::new(2, 1)));
+        // A wall in the way stops the fireball short, right next to the player
+        world.spawn_wall(Coord::new(3, 1)).unwrap();
+        assert!(world.aim_would_harm_self(player, ItemType::FireballScroll, Coord::new(5, 1)));
     }
-    fn remove_entity_data(&mut self, entity: Entity) -> EntityData {
-        self.entity_allocator.free(entity);
-        self.spatial_table.remove(entity);
-        self.components.remove_entity_data(entity)
+
+    #[test]
+    fn teleport_other_scroll_moves_aimed_orc_to_walkable_cell() {
+        let mut world = world_from_rows(&["#######", "#     #", "#  #  #", "#     #", "#######"]);
+        let mut message_log = Vec::new();
+        let mut rng = Isaac64Rng::seed_from_u64(7);
+        let player = world
+            .spawn_player(
+                Coord::new(1, 1),
+                &steady_player_config(vec![
+                    ItemType::TeleportOtherScroll,
+                    ItemType::TeleportOtherScroll,
+                ]),
+            )
+            .unwrap();
+        let orc_coord = Coord::new(2, 1);
+        let orc = spawn_steady_npc(&mut world, orc_coord, NpcType::Orc);
+        assert!(world
+            .maybe_use_item_aim(player, 0, orc_coord, &mut message_log, &mut rng)
+            .is_ok());
+        let new_coord = world.entity_coord(orc).unwrap();
+        assert_ne!(new_coord, orc_coord);
+        let layers = world.spatial_table.layers_at_checked(new_coord);
+        assert!(layers.floor.is_some());
+        assert!(layers.feature.is_none());
+        assert!(matches!(
+            message_log.as_slice(),
+            [LogMessage::NpcTeleports(NpcType::Orc)]
+        ));
+        // Aiming at an empty cell wastes the scroll
+        message_log.clear();
+        assert!(world
+            .maybe_use_item_aim(player, 0, orc_coord, &mut message_log, &mut rng)
+            .is_ok());
+        assert_eq!(world.entity_coord(orc), Some(new_coord));
+        assert!(world.inventory_item_type(player, 0).is_none());
+        assert!(matches!(
+            message_log.as_slice(),
+            [LogMessage::NothingHappens]
+        ));
     }
-    pub fn remove_character(&mut self, entity: Entity) -> CharacterData {
-        let mut entity_data = self.remove_entity_data(entity);
-        // Remove the inventory from the character. An inventory contains entities referring data
-        // in the current world. These data will also be removed here, and combined with the
-        // `EntityData` of the character to form a `CharacterData`. When the `CharacterData` is
-        // re-inserted into the world, the inventory item data will be inserted first, at which
-        // point each item will be assigned a fresh entity. The character will get a brand new
-        // inventory containing the new entities.
-        let inventory_entity_data = entity_data
-            .inventory
-            .take()
-            .expect("character missing inventory")
-            .slots()
-            .iter()
-            .map(|maybe_slot| maybe_slot.map(|entity| self.remove_entity_data(entity)))
-            .collect::<Vec<_>>();
-        CharacterData {
-            entity_data,
-            inventory_entity_data,
+
+    #[test]
+    fn auto_pickup_takes_matching_potion_but_leaves_cursed_weapon() {
+        let coord = Coord::new(1, 1);
+        let run = |item_type: ItemType, cursed: bool| {
+            let mut world = open_world(Size::new(3, 3));
+            let mut message_log = Vec::new();
+            let player = world
+                .spawn_player(coord, &steady_player_config(Vec::new()))
+                .unwrap();
+            let item = world.spawn_item(coord, item_type).unwrap();
+            world.components.cursed.insert(item, cursed);
+            let result = world.auto_pickup(player, ItemType::is_potion, &mut message_log);
+            let picked_up = world.inventory_item_type(player, 0) == Some(item_type);
+            (result, picked_up, world.entity_coord(item))
+        };
+        let (result, picked_up, _) = run(ItemType::HealthPotion, false);
+        assert!(result.is_ok());
+        assert!(picked_up);
+        let (result, picked_up, sword_coord) = run(ItemType::Sword, true);
+        assert!(result.is_err());
+        assert!(!picked_up);
+        assert_eq!(sword_coord, Some(coord));
+    }
+
+    #[test]
+    fn low_health_warning_fires_once_and_rearms_after_healing() {
+        let mut world = open_world(Size::new(3, 3));
+        let mut message_log = Vec::new();
+        let player = world
+            .spawn_player(
+                Coord::new(1, 1),
+                &PlayerConfig {
+                    max_hit_points: 20,
+                    ..steady_player_config(Vec::new())
+                },
+            )
+            .unwrap();
+        let count_warnings = |message_log: &[LogMessage]| {
+            message_log
+                .iter()
+                .filter(|message| matches!(message, LogMessage::PlayerLowHealth))
+                .count()
+        };
+        // Exactly a quarter of the player's hit points isn't low enough to warn about
+        world.components.hit_points.get_mut(player).unwrap().current = 5;
+        world.check_player_health_warnings(player, &mut message_log);
+        assert_eq!(count_warnings(&message_log), 0);
+        for &current in &[4, 4, 2] {
+            world.components.hit_points.get_mut(player).unwrap().current = current;
+            world.check_player_health_warnings(player, &mut message_log);
         }
+        assert_eq!(count_warnings(&message_log), 1);
+        world.character_heal(player, 10);
+        world.check_player_health_warnings(player, &mut message_log);
+        assert_eq!(count_warnings(&message_log), 1);
+        world.components.hit_points.get_mut(player).unwrap().current = 3;
+        world.check_player_health_warnings(player, &mut message_log);
+        assert_eq!(count_warnings(&message_log), 2);
     }
-    pub fn replace_character(
-        &mut self,
-        entity: Entity,
-        CharacterData {
-            mut entity_data,
-            inventory_entity_data,
-        }: CharacterData,
-    ) {
-        // Before inserting the character's data, create new entities to contain each item in the
-        // character's inventory.
-        let inventory_slots = inventory_entity_data
-            .into_iter()
-            .map(|maybe_entity_data| {
-                maybe_entity_data.map(|entity_data| {
-                    let entity = self.entity_allocator.alloc();
-                    self.components.update_entity_data(entity, entity_data);
-                    entity
-                })
-            })
-            .collect::<Vec<_>>();
-        // Make a new inventory containing the newly created entities, and add it to the character.
-        entity_data.inventory = Some(Inventory {
-            slots: inventory_slots,
-        });
-        self.components.update_entity_data(entity, entity_data);
+
+    #[test]
+    fn tagged_troll_is_found_by_tag() {
+        let mut world = open_world(Size::new(5, 3));
+        spawn_steady_npc(&mut world, Coord::new(1, 1), NpcType::Orc);
+        let troll = spawn_steady_npc(&mut world, Coord::new(3, 1), NpcType::Troll);
+        assert!(world.entities_with_tag(EntityTag::Boss).is_empty());
+        world.components.tag.insert(troll, EntityTag::Boss);
+        assert_eq!(world.entities_with_tag(EntityTag::Boss), vec![troll]);
     }
-    pub fn coord_contains_stairs(&self, coord: Coord) -> bool {
-        self.spatial_table
-            .layers_at_checked(coord)
-            .floor
-            .map(|floor_entity| self.components.stairs.contains(floor_entity))
-            .unwrap_or(false)
+
+    // Launches a fireball along a corridor at a troll, with `obstacle` spawning something in the
+    // fireball's path, and returns the damage the troll takes
+    fn fireball_damage_past(obstacle: impl FnOnce(&mut World, Coord)) -> u32 {
+        let mut world = open_world(Size::new(7, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let troll_coord = Coord::new(5, 1);
+        let troll = spawn_steady_npc(&mut world, troll_coord, NpcType::Troll);
+        obstacle(&mut world, Coord::new(3, 1));
+        world
+            .spawn_projectile(
+                Coord::new(1, 1),
+                troll_coord,
+                ProjectileType::Fireball { damage: 3 },
+            )
+            .unwrap();
+        while world.has_projectiles() {
+            world.move_projectiles(&mut message_log, &mut rng);
+        }
+        let hit_points = world.hit_points(troll).unwrap();
+        hit_points.max - hit_points.current
     }
-    pub fn strength(&self, entity: Entity) -> Option<i32> {
-        self.components.strength.get(entity).cloned()
+
+    #[test]
+    fn fireball_passes_through_open_door() {
+        let damage = fireball_damage_past(|world, coord| {
+            let door = world.spawn_door(coord).unwrap();
+            world.components.door.insert(door, true);
+            world.components.tile.insert(door, Tile::DoorOpen);
+        });
+        assert_eq!(damage, 3);
     }
-    pub fn dexterity(&self, entity: Entity) -> Option<i32> {
-        self.components.dexterity.get(entity).cloned()
+
+    #[test]
+    fn fireball_is_stopped_by_closed_door() {
+        let damage = fireball_damage_past(|world, coord| {
+            world.spawn_door(coord).unwrap();
+        });
+        assert_eq!(damage, 0);
     }
-    pub fn intelligence(&self, entity: Entity) -> Option<i32> {
-        self.components.intelligence.get(entity).cloned()
+
+    #[test]
+    fn fireball_flies_over_item_on_floor() {
+        let damage = fireball_damage_past(|world, coord| {
+            let item = world.spawn_item(coord, ItemType::Sword).unwrap();
+            assert_eq!(
+                world.spatial_table.layers_at_checked(coord).object,
+                Some(item)
+            );
+        });
+        assert_eq!(damage, 3);
     }
-    pub fn level_up_character(&mut self, character_entity: Entity, level_up: LevelUp) {
-        match level_up {
-            LevelUp::Strength => {
-                *self
-                    .components
-                    .strength
-                    .get_mut(character_entity)
-                    .expect("character lacks strength") += 1;
-            }
-            LevelUp::Dexterity => {
-                *self
-                    .components
-                    .dexterity
-                    .get_mut(character_entity)
-                    .expect("character lacks dexterity") += 1;
-            }
-            LevelUp::Intelligence => {
-                *self
-                    .components
-                    .intelligence
-                    .get_mut(character_entity)
-                    .expect("character lacks intelligence") += 1;
-            }
-            LevelUp::Health => {
-                let hit_points = self
-                    .components
-                    .hit_points
-                    .get_mut(character_entity)
-                    .expect("character lacks hit points");
-                const INCREASE: u32 = 5;
-                hit_points.current += INCREASE;
-                hit_points.max += INCREASE;
-            }
+
+    #[test]
+    fn lines_from_a_cell_to_itself_are_empty() {
+        let mut world = open_world(Size::new(3, 3));
+        let mut message_log = Vec::new();
+        let mut rng = rng();
+        let coord = Coord::new(1, 1);
+        assert!(world.aim_line(coord, coord).is_empty());
+        assert_eq!(Trajectory::new(coord, coord).next(), None);
+        let mut trajectory = Trajectory::new(Coord::new(0, 1), coord);
+        assert_eq!(trajectory.next(), Some(coord));
+        trajectory.reflect(Coord::new(0, 1));
+        assert_eq!(trajectory.next(), None);
+        world
+            .spawn_projectile(coord, coord, ProjectileType::Fireball { damage: 1 })
+            .unwrap();
+        while world.has_projectiles() {
+            world.move_projectiles(&mut message_log, &mut rng);
         }
     }
-    pub fn equipped_inventory_indices(&self, entity: Entity) -> EquippedInventoryIndices {
-        let held = self
-            .components
-            .equipment_held_inventory_index
-            .get(entity)
-            .cloned();
-        let worn = self
-            .components
-            .equipment_worn_inventory_index
-            .get(entity)
-            .cloned();
-        EquippedInventoryIndices { held, worn }
+
+    #[test]
+    fn unhurt_npcs_never_start_out_fleeing() {
+        for &npc_type in &NpcType::ALL {
+            assert!(npc_type.flee_threshold() < npc_type.base_hit_points());
+        }
     }
 }